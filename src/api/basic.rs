@@ -43,6 +43,22 @@ basic! {
     }
 }
 
+impl BasicType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BasicType::LUA_TNONE => "no value",
+            BasicType::LUA_TNIL => "nil",
+            BasicType::LUA_TBOOLEAN => "boolean",
+            BasicType::LUA_TNUMBER => "number",
+            BasicType::LUA_TSTRING => "string",
+            BasicType::LUA_TTABLE => "table",
+            BasicType::LUA_TFUNCTION => "function",
+            BasicType::LUA_TTHREAD => "thread",
+            _ => "userdata",
+        }
+    }
+}
+
 basic! {
     #[derive(Debug, Clone, PartialEq)]
      pub enum Arithmetic {
@@ -72,7 +88,23 @@ basic! {
     }
 }
 
+pub const LUA_VERSION: &str = "Lua 5.3";
+
+/* thread status */
+pub const LUA_OK: u8 = 0;
+pub const LUA_YIELD: u8 = 1;
+pub const LUA_ERRRUN: u8 = 2;
+pub const LUA_ERRSYNTAX: u8 = 3;
+pub const LUA_ERRMEM: u8 = 4;
+// 5 is for errors in __gc metamethods, which this VM never runs
+pub const LUA_ERRERR: u8 = 6;
+
+/* option for multiple returns in 'pcall' and 'call' */
+pub const LUA_MULTRET: isize = -1;
+
 pub const LUA_MINSTACK: usize = 20;
 pub const LUAI_MAXSTACK: usize = 1000000;
+pub const LUAI_MAXCCALLS: usize = 200; // maximum depth for nested Rust calls and syntactical structures
 pub const LUA_REGISTRYINDEX: isize = -(LUAI_MAXSTACK as isize) - 1000;
+pub const LUA_RIDX_MAINTHREAD: isize = 1;
 pub const LUA_RIDX_GLOBALS: isize = 2;
@@ -1,6 +1,8 @@
+use crate::state::LuaError;
+
 use super::{
     basic::{Arithmetic, BasicType, Comparison},
-    lua_vm::RustFn,
+    lua_vm::{RustFunction, RustKFn},
 };
 
 pub trait LuaState {
@@ -36,36 +38,75 @@ pub trait LuaState {
     fn to_numberx(&self, idx: isize) -> Option<f64>;
     fn to_string(&self, idx: isize) -> String;
     fn to_stringx(&self, idx: isize) -> Option<String>;
-    fn to_rust_function(&self, idx: isize) -> Option<RustFn>;
+    fn to_bytes(&self, idx: isize) -> Vec<u8>;
+    fn to_bytesx(&self, idx: isize) -> Option<Vec<u8>>;
+    fn to_rust_function(&self, idx: isize) -> Option<RustFunction>;
+    fn to_pointer(&self, idx: isize) -> usize;
+    fn raw_len(&self, idx: isize) -> usize;
     /* push functions (rust -> stack) */
     fn push_nil(&mut self);
     fn push_boolean(&mut self, b: bool);
     fn push_integer(&mut self, n: i64);
     fn push_number(&mut self, n: f64);
     fn push_string(&mut self, s: String);
-    fn push_rust_fn(&mut self, f: RustFn);
+    fn push_bytes(&mut self, s: Vec<u8>);
+    fn push_rust_fn(&mut self, f: RustFunction);
     fn push_global_table(&mut self);
-    fn push_rust_closure(&mut self, f: RustFn, n: isize);
+    fn push_rust_closure(&mut self, f: RustFunction, n: isize);
     /* comparison and arithmetic functions */
-    fn arith(&mut self, op: Arithmetic);
-    fn compare(&self, idx1: isize, idx2: isize, op: Comparison) -> bool;
+    fn arith(&mut self, op: Arithmetic) -> Result<(), LuaError>;
+    fn compare(&mut self, idx1: isize, idx2: isize, op: Comparison) -> Result<bool, LuaError>;
+    fn raw_equal(&self, idx1: isize, idx2: isize) -> bool;
     /* miscellaneous functions */
-    fn len(&mut self, idx: isize);
-    fn concat(&mut self, n: isize);
+    fn len(&mut self, idx: isize) -> Result<(), LuaError>;
+    fn concat(&mut self, n: isize) -> Result<(), LuaError>;
+    fn next(&mut self, idx: isize) -> Result<bool, LuaError>;
+    fn string_to_number(&mut self, s: &str) -> bool;
     /* ger functions (rust -> stack) */
     fn new_table(&mut self);
     fn create_table(&mut self, n_arr: usize, n_rec: usize);
-    fn table(&mut self, idx: isize) -> BasicType;
-    fn field(&mut self, idx: isize, k: &str) -> BasicType;
-    fn i(&mut self, idx: isize, i: i64) -> BasicType;
-    fn global(&mut self, name: &str) -> BasicType;
+    fn table(&mut self, idx: isize) -> Result<BasicType, LuaError>;
+    fn field(&mut self, idx: isize, k: &str) -> Result<BasicType, LuaError>;
+    fn i(&mut self, idx: isize, i: i64) -> Result<BasicType, LuaError>;
+    fn global(&mut self, name: &str) -> Result<BasicType, LuaError>;
+    fn raw_get(&mut self, idx: isize) -> Result<BasicType, LuaError>;
+    fn raw_get_i(&mut self, idx: isize, i: i64) -> Result<BasicType, LuaError>;
+    fn get_metatable(&mut self, idx: isize) -> bool;
+    fn get_metafield(&mut self, idx: isize, e: &str) -> BasicType;
     /* set functions (stack -> Lua) */
-    fn set_table(&mut self, idx: isize);
-    fn set_field(&mut self, idx: isize, k: &str);
-    fn set_i(&mut self, idx: isize, i: i64);
-    fn set_global(&mut self, name: &str);
-    fn register(&mut self, name: &str, f: RustFn);
+    fn set_table(&mut self, idx: isize) -> Result<(), LuaError>;
+    fn set_field(&mut self, idx: isize, k: &str) -> Result<(), LuaError>;
+    fn set_i(&mut self, idx: isize, i: i64) -> Result<(), LuaError>;
+    fn set_global(&mut self, name: &str) -> Result<(), LuaError>;
+    fn raw_set(&mut self, idx: isize) -> Result<(), LuaError>;
+    fn raw_set_i(&mut self, idx: isize, i: i64) -> Result<(), LuaError>;
+    fn set_metatable(&mut self, idx: isize) -> Result<(), LuaError>;
+    fn register(&mut self, name: &str, f: RustFunction);
     /* 'load' and 'call' functions (load and run Lua code) */
     fn load(&mut self, chunk: Vec<u8>, chunk_name: &str, mode: &str) -> u8;
-    fn call(&mut self, n_args: usize, n_results: isize);
+    fn call(&mut self, n_args: usize, n_results: isize) -> Result<(), LuaError>;
+    fn pcall(&mut self, n_args: usize, n_results: isize, msgh: isize) -> u8;
+    fn pcall_k(
+        &mut self,
+        n_args: usize,
+        n_results: isize,
+        msgh: isize,
+        ctx: isize,
+        k: RustKFn,
+    ) -> Result<u8, LuaError>;
+    fn dump(&self, strip: bool) -> Option<Vec<u8>>;
+    /* error functions */
+    fn error(&mut self) -> LuaError;
+    fn position(&self, level: usize) -> String;
+    fn traceback(&mut self, msg: Option<&str>, level: usize);
+    /* coroutine functions */
+    fn new_thread(&mut self);
+    fn push_thread(&mut self) -> bool;
+    fn xmove(&mut self, idx: isize, n: usize) -> Result<(), LuaError>;
+    fn resume(&mut self, idx: isize, n_args: usize) -> u8;
+    fn yield_k(&mut self, n_results: usize, ctx: isize, k: Option<RustKFn>) -> LuaError;
+    fn is_yieldable(&self) -> bool;
+    fn thread_status(&self, idx: isize) -> Result<&'static str, LuaError>;
+    /* standard libraries */
+    fn open_libs(&mut self) -> Result<(), LuaError>;
 }
@@ -1,9 +1,84 @@
+use std::{cell::RefCell, fmt, rc::Rc};
+
+use crate::state::LuaError;
+
 use super::basic::LUA_REGISTRYINDEX;
 pub use super::lua_api::LuaState as LuaAPI;
 
-pub type RustFn = fn(&dyn super::lua_api::LuaState) -> usize;
+pub type RustFn = fn(&mut dyn LuaAPI) -> Result<usize, LuaError>;
+// continuation of a Rust function whose callee yielded, called with the status
+// of that call and the context the function passed along
+pub type RustKFn = fn(&mut dyn LuaAPI, u8, isize) -> Result<usize, LuaError>;
+type DynFn = dyn Fn(&mut dyn LuaAPI) -> Result<usize, LuaError>;
+type DynFnMut = dyn FnMut(&mut dyn LuaAPI) -> Result<usize, LuaError>;
+pub type BoxedFn = Box<DynFn>;
+pub type BoxedFnMut = Box<DynFnMut>;
+
+// a function callable from Lua: a plain pointer, or a closure with captured state
+#[derive(Clone)]
+pub enum RustFunction {
+    Ptr(RustFn),
+    Fn(Rc<DynFn>),
+    FnMut(Rc<RefCell<DynFnMut>>),
+}
+
+impl RustFunction {
+    pub fn new_fn(f: impl Fn(&mut dyn LuaAPI) -> Result<usize, LuaError> + 'static) -> Self {
+        Self::Fn(Rc::new(f))
+    }
+
+    pub fn new_fn_mut(f: impl FnMut(&mut dyn LuaAPI) -> Result<usize, LuaError> + 'static) -> Self {
+        Self::FnMut(Rc::new(RefCell::new(f)))
+    }
+
+    pub fn call(&self, ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+        match self {
+            Self::Ptr(f) => f(ls),
+            Self::Fn(f) => f(ls),
+            // an FnMut can't run again while it is running
+            Self::FnMut(f) => match f.try_borrow_mut() {
+                Ok(mut f) => f(ls),
+                Err(_) => {
+                    let pos = ls.position(1);
+                    ls.push_string(format!("{}attempt to re-enter a running Rust closure", pos));
+                    Err(ls.error())
+                }
+            },
+        }
+    }
+}
+
+impl From<RustFn> for RustFunction {
+    fn from(f: RustFn) -> Self {
+        Self::Ptr(f)
+    }
+}
+
+impl From<BoxedFn> for RustFunction {
+    fn from(f: BoxedFn) -> Self {
+        Self::Fn(Rc::from(f))
+    }
+}
+
+impl From<BoxedFnMut> for RustFunction {
+    fn from(f: BoxedFnMut) -> Self {
+        Self::FnMut(Rc::new(RefCell::new(f)))
+    }
+}
+
+impl fmt::Debug for RustFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ptr(p) => write!(f, "Ptr({:p})", *p as *const ()),
+            Self::Fn(c) => write!(f, "Fn({:p})", Rc::as_ptr(c) as *const ()),
+            Self::FnMut(c) => write!(f, "FnMut({:p})", Rc::as_ptr(c) as *const ()),
+        }
+    }
+}
 
 pub trait LuaVM: LuaAPI {
+    fn pre_call(&mut self, n_args: usize, n_results: isize) -> Result<bool, LuaError>;
+    fn pre_tail_call(&mut self, n_args: usize) -> Result<bool, LuaError>;
     fn pc(&self) -> isize;
     fn add_pc(&mut self, n: isize);
     fn fetch(&mut self) -> u32;
@@ -18,5 +93,5 @@ pub trait LuaVM: LuaAPI {
 }
 
 pub const fn lua_upvalue_index(i: isize) -> isize {
-    LUA_REGISTRYINDEX - 1
+    LUA_REGISTRYINDEX - i
 }
@@ -15,6 +15,9 @@ pub const LUA_NUMBER_SIZE: u8 = 8;
 pub const LUAC_INT: i64 = 0x5678;
 pub const LUAC_NUM: f64 = 370.5;
 
+// the source of a main function loaded without one, shown as "?"
+pub const NO_SOURCE: &str = "=?";
+
 pub const TAG_NIL: u8 = 0x00;
 pub const TAG_BOOLEAN: u8 = 0x01;
 pub const TAG_NUMBER: u8 = 0x03;
@@ -28,7 +31,7 @@ pub enum ConstantType {
     Boolean(bool),
     Integer(i64),
     Number(f64),
-    String(String),
+    String(Vec<u8>),
 }
 
 #[derive(Debug)]
@@ -0,0 +1,78 @@
+use std::fmt;
+
+use super::chunk;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChunkError {
+    BadSignature,
+    VersionMismatch(u8),
+    FormatMismatch(u8),
+    Corrupted,
+    SizeMismatch {
+        what: &'static str,
+        expected: u8,
+        found: u8,
+    },
+    EndiannessMismatch,
+    FloatFormatMismatch,
+    Truncated(usize), // offset of the read that ran out of data
+    BadConstantTag {
+        tag: u8,
+        offset: usize,
+    },
+    TooManyLevels,
+    BadInstruction {
+        pc: usize, // 1-based, as in listings
+        reason: &'static str,
+    },
+}
+
+impl fmt::Display for ChunkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ChunkError::BadSignature => write!(f, "not a precompiled chunk"),
+            ChunkError::VersionMismatch(found) => write!(
+                f,
+                "version mismatch in precompiled chunk (expected 0x{:02x}, found 0x{:02x})",
+                chunk::LUAC_VERSION,
+                found
+            ),
+            ChunkError::FormatMismatch(found) => write!(
+                f,
+                "format mismatch in precompiled chunk (expected {}, found {})",
+                chunk::LUAC_FORMAT,
+                found
+            ),
+            ChunkError::Corrupted => write!(f, "corrupted precompiled chunk"),
+            ChunkError::SizeMismatch {
+                what,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{} size mismatch in precompiled chunk (expected {}, found {})",
+                what, expected, found
+            ),
+            ChunkError::EndiannessMismatch => write!(f, "endianness mismatch in precompiled chunk"),
+            ChunkError::FloatFormatMismatch => {
+                write!(f, "float format mismatch in precompiled chunk")
+            }
+            ChunkError::Truncated(offset) => {
+                write!(f, "truncated precompiled chunk at offset {}", offset)
+            }
+            ChunkError::BadConstantTag { tag, offset } => write!(
+                f,
+                "bad constant tag 0x{:02x} at offset {} in precompiled chunk",
+                tag, offset
+            ),
+            ChunkError::TooManyLevels => {
+                write!(f, "too many nested functions in precompiled chunk")
+            }
+            ChunkError::BadInstruction { pc, reason } => {
+                write!(f, "{} in instruction {} of precompiled chunk", reason, pc)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ChunkError {}
@@ -0,0 +1,285 @@
+use std::fmt::Write;
+
+use crate::{
+    math::number::float_to_str,
+    vm::{instruction::Instruction, opcode::*},
+};
+
+use super::chunk::{ConstantType, Prototype, LUA_SIGNATURE};
+
+const BITRK: isize = 1 << 8; // this bit set means the operand is a constant
+
+fn is_k(x: isize) -> bool {
+    x & BITRK != 0
+}
+
+fn index_k(x: isize) -> usize {
+    (x & !BITRK) as usize
+}
+
+// constants are shown as negative numbers
+fn my_k(x: usize) -> isize {
+    -1 - x as isize
+}
+
+fn plural(n: usize) -> &'static str {
+    if n == 1 {
+        ""
+    } else {
+        "s"
+    }
+}
+
+// lists proto and its nested functions like `luac -l`, or `luac -l -l` if full is set
+pub fn list(proto: &Prototype, full: bool) -> String {
+    let mut out = String::new();
+    list_function(&mut out, proto, full);
+    out
+}
+
+fn list_function(out: &mut String, f: &Prototype, full: bool) {
+    list_header(out, f);
+    list_code(out, f);
+    if full {
+        list_debug(out, f);
+    }
+    for p in f.protos() {
+        list_function(out, p, full);
+    }
+}
+
+fn list_header(out: &mut String, f: &Prototype) {
+    let source = f.source();
+    let source = if let Some(s) = source.strip_prefix(['@', '=']) {
+        s
+    } else if source.as_bytes().first() == Some(&LUA_SIGNATURE[0]) {
+        "(bstring)"
+    } else {
+        "(string)"
+    };
+
+    let kind = if f.line_defined() == 0 {
+        "main"
+    } else {
+        "function"
+    };
+    let n_code = f.code().len();
+    let _ = writeln!(
+        out,
+        "\n{} <{}:{},{}> ({} instruction{} at {:p})",
+        kind,
+        source,
+        f.line_defined(),
+        f.last_line_defined(),
+        n_code,
+        plural(n_code),
+        f
+    );
+
+    let n_params = f.num_params() as usize;
+    let n_slots = f.max_stack_size() as usize;
+    let n_upvals = f.upvalues().len();
+    let n_locals = f.locvars().len();
+    let n_consts = f.constants().len();
+    let n_protos = f.protos().len();
+    let _ = writeln!(
+        out,
+        "{}{} param{}, {} slot{}, {} upvalue{}, {} local{}, {} constant{}, {} function{}",
+        n_params,
+        if f.is_vararg() != 0 { "+" } else { "" },
+        plural(n_params),
+        n_slots,
+        plural(n_slots),
+        n_upvals,
+        plural(n_upvals),
+        n_locals,
+        plural(n_locals),
+        n_consts,
+        plural(n_consts),
+        n_protos,
+        plural(n_protos)
+    );
+}
+
+fn list_code(out: &mut String, f: &Prototype) {
+    let code = f.code();
+    let mut pc = 0;
+    while pc < code.len() {
+        let i = code[pc];
+        let op = i.opcode();
+        let (a, b, c) = i.abc();
+        let (_, bx) = i.a_bx();
+        let (_, sbx) = i.a_sbx();
+        let ax = i.ax();
+
+        let _ = write!(out, "\t{}\t", pc + 1);
+        match f.line_info().get(pc) {
+            Some(&line) if line > 0 => {
+                let _ = write!(out, "[{}]\t", line);
+            }
+            _ => out.push_str("[-]\t"),
+        }
+        let _ = write!(out, "{:<9}\t", i.opname().trim_end());
+
+        match i.opmode() {
+            OP_MODE_ABC => {
+                let _ = write!(out, "{}", a);
+                if i.b_mode() != OP_ARG_N {
+                    let b = if is_k(b) { my_k(index_k(b)) } else { b };
+                    let _ = write!(out, " {}", b);
+                }
+                if i.c_mode() != OP_ARG_N {
+                    let c = if is_k(c) { my_k(index_k(c)) } else { c };
+                    let _ = write!(out, " {}", c);
+                }
+            }
+            OP_MODE_ABX => {
+                let _ = write!(out, "{}", a);
+                if i.b_mode() == OP_ARG_K {
+                    let _ = write!(out, " {}", my_k(bx as usize));
+                }
+                if i.b_mode() == OP_ARG_U {
+                    let _ = write!(out, " {}", bx);
+                }
+            }
+            OP_MODE_ASBX => {
+                let _ = write!(out, "{} {}", a, sbx);
+            }
+            _ => {
+                let _ = write!(out, "{}", my_k(ax as usize));
+            }
+        }
+
+        match op {
+            OP_LOADK => {
+                let _ = write!(out, "\t; {}", constant(f, bx as usize));
+            }
+            OP_GETUPVAL | OP_SETUPVAL => {
+                let _ = write!(out, "\t; {}", upval_name(f, b as usize));
+            }
+            OP_GETTABUP => {
+                let _ = write!(out, "\t; {}", upval_name(f, b as usize));
+                if is_k(c) {
+                    let _ = write!(out, " {}", constant(f, index_k(c)));
+                }
+            }
+            OP_SETTABUP => {
+                let _ = write!(out, "\t; {}", upval_name(f, a as usize));
+                if is_k(b) {
+                    let _ = write!(out, " {}", constant(f, index_k(b)));
+                }
+                if is_k(c) {
+                    let _ = write!(out, " {}", constant(f, index_k(c)));
+                }
+            }
+            OP_GETTABLE | OP_SELF if is_k(c) => {
+                let _ = write!(out, "\t; {}", constant(f, index_k(c)));
+            }
+            OP_SETTABLE | OP_ADD | OP_SUB | OP_MUL | OP_MOD | OP_POW | OP_DIV | OP_IDIV
+            | OP_BAND | OP_BOR | OP_BXOR | OP_SHL | OP_SHR | OP_EQ | OP_LT | OP_LE
+                if is_k(b) || is_k(c) =>
+            {
+                let rk = |x| {
+                    if is_k(x) {
+                        constant(f, index_k(x))
+                    } else {
+                        "-".to_string()
+                    }
+                };
+                let _ = write!(out, "\t; {} {}", rk(b), rk(c));
+            }
+            OP_JMP | OP_FORLOOP | OP_FORPREP | OP_TFORLOOP => {
+                let _ = write!(out, "\t; to {}", sbx + pc as isize + 2);
+            }
+            OP_CLOSURE => {
+                let _ = write!(out, "\t; {:p}", &*f.protos()[bx as usize]);
+            }
+            OP_SETLIST => {
+                if c == 0 {
+                    pc += 1;
+                    let _ = write!(out, "\t; {}", code[pc]);
+                } else {
+                    let _ = write!(out, "\t; {}", c);
+                }
+            }
+            OP_EXTRAARG => {
+                let _ = write!(out, "\t; {}", constant(f, ax as usize));
+            }
+            _ => {}
+        }
+        out.push('\n');
+        pc += 1;
+    }
+}
+
+fn list_debug(out: &mut String, f: &Prototype) {
+    let _ = writeln!(out, "constants ({}) for {:p}:", f.constants().len(), f);
+    for i in 0..f.constants().len() {
+        let _ = writeln!(out, "\t{}\t{}", i + 1, constant(f, i));
+    }
+
+    let _ = writeln!(out, "locals ({}) for {:p}:", f.locvars().len(), f);
+    for (i, var) in f.locvars().iter().enumerate() {
+        let _ = writeln!(
+            out,
+            "\t{}\t{}\t{}\t{}",
+            i,
+            var.var_name,
+            var.start_pc + 1,
+            var.end_pc + 1
+        );
+    }
+
+    let _ = writeln!(out, "upvalues ({}) for {:p}:", f.upvalues().len(), f);
+    for (i, uv) in f.upvalues().iter().enumerate() {
+        let _ = writeln!(
+            out,
+            "\t{}\t{}\t{}\t{}",
+            i,
+            upval_name(f, i),
+            uv.instack,
+            uv.idx
+        );
+    }
+}
+
+fn upval_name(f: &Prototype, idx: usize) -> &str {
+    match f.upvalue_names().get(idx) {
+        Some(name) if !name.is_empty() => name,
+        _ => "-",
+    }
+}
+
+fn constant(f: &Prototype, idx: usize) -> String {
+    match f.constants().get(idx) {
+        Some(ConstantType::Nil) => "nil".to_string(),
+        Some(ConstantType::Boolean(b)) => b.to_string(),
+        Some(ConstantType::Integer(n)) => n.to_string(),
+        Some(ConstantType::Number(n)) => float_to_str(*n),
+        Some(ConstantType::String(s)) => quote_string(s),
+        None => format!("? k={}", idx),
+    }
+}
+
+fn quote_string(s: &[u8]) -> String {
+    let mut out = String::from("\"");
+    for &c in s {
+        match c {
+            b'"' => out.push_str("\\\""),
+            b'\\' => out.push_str("\\\\"),
+            0x07 => out.push_str("\\a"),
+            0x08 => out.push_str("\\b"),
+            0x0c => out.push_str("\\f"),
+            b'\n' => out.push_str("\\n"),
+            b'\r' => out.push_str("\\r"),
+            b'\t' => out.push_str("\\t"),
+            0x0b => out.push_str("\\v"),
+            c if c == b' ' || c.is_ascii_graphic() => out.push(c as char),
+            c => {
+                let _ = write!(out, "\\{:03}", c);
+            }
+        }
+    }
+    out.push('"');
+    out
+}
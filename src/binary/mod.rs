@@ -1,11 +1,26 @@
 use std::rc::Rc;
 
 pub mod chunk;
+mod error;
+pub mod lister;
 mod reader;
+mod writer;
 
-pub fn un_dump(data: Vec<u8>) -> Rc<chunk::Prototype> {
+pub use self::error::ChunkError;
+
+// like lundump, a stripped main function has no source
+pub fn un_dump(data: &[u8]) -> Result<Rc<chunk::Prototype>, ChunkError> {
     let mut reader = reader::Reader::new(data);
-    reader.check_header();
-    reader.read_byte();
-    reader.read_proto("")
+    reader.check_header()?;
+    reader.read_byte()?; // size_upvalues
+    reader.read_proto(chunk::NO_SOURCE)
+}
+
+// serializes proto in the format of luac 5.3, without debug information if strip is set
+pub fn dump(proto: &chunk::Prototype, strip: bool) -> Vec<u8> {
+    let mut writer = writer::Writer::new(strip);
+    writer.write_header();
+    writer.write_byte(proto.upvalues().len() as u8); // size_upvalues
+    writer.write_proto(proto, Some(chunk::NO_SOURCE));
+    writer.into_bytes()
 }
@@ -1,161 +1,324 @@
 use std::rc::Rc;
 
-use super::chunk::{self, LocVar, Prototype, Upvalue};
+use crate::{
+    api::basic::LUAI_MAXCCALLS,
+    vm::{instruction::Instruction, opcode::*},
+};
 
+use super::{
+    chunk::{self, LocVar, Prototype, Upvalue},
+    error::ChunkError,
+};
+
+// a cursor over the bytes of a binary chunk
 #[derive(Debug)]
-pub struct Reader {
-    data: Vec<u8>,
+pub struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    level: usize, // nesting of the function being read
 }
 
-impl Reader {
-    pub fn new(data: Vec<u8>) -> Self {
-        Reader { data }
-    }
-
-    pub fn check_header(&mut self) {
-        assert_eq!(
-            self.read_bytes(4),
-            chunk::LUA_SIGNATURE,
-            "not a precompiled chunk!"
-        );
-        assert_eq!(self.read_byte(), chunk::LUAC_VERSION, "version mismatch!");
-        assert_eq!(self.read_byte(), chunk::LUAC_FORMAT, "format mismatch!");
-        assert_eq!(self.read_bytes(6), chunk::LUAC_DATA, "corrupted!");
-        assert_eq!(self.read_byte(), chunk::INT_SIZE, "int size mismatch!");
-        assert_eq!(self.read_byte(), chunk::SIZET_SIZE, "size_t size mismatch!");
-        assert_eq!(
-            self.read_byte(),
-            chunk::INSTRUCTION_SIZE,
-            "instruction size mismatch!"
-        );
-        assert_eq!(
-            self.read_byte(),
-            chunk::LUA_INTEGER_SIZE,
-            "lua_Integer size mismatch!"
-        );
-        assert_eq!(
-            self.read_byte(),
-            chunk::LUA_NUMBER_SIZE,
-            "lua_Number size mismatch!"
-        );
-        assert_eq!(
-            self.read_lua_integer(),
-            chunk::LUAC_INT,
-            "endianness mismatch!"
-        );
-        assert_eq!(
-            self.read_lua_number(),
-            chunk::LUAC_NUM,
-            "float format mismatch!"
-        );
-    }
-
-    pub fn read_byte(&mut self) -> u8 {
-        self.data.remove(0)
-    }
-
-    fn read_u32(&mut self) -> u32 {
-        let result = u32::from_le_bytes(self.data[..4].try_into().unwrap());
-        self.data.drain(..4);
-        result
-    }
-
-    fn read_u64(&mut self) -> u64 {
-        let result = u64::from_le_bytes(self.data[..8].try_into().unwrap());
-        self.data.drain(..8);
-        result
-    }
-
-    fn read_lua_integer(&mut self) -> i64 {
-        self.read_u64() as i64
-    }
-
-    fn read_lua_number(&mut self) -> f64 {
-        let bit = self.read_u64();
-        f64::from_bits(bit)
-    }
-
-    fn read_string(&mut self) -> String {
-        let mut size = self.read_byte() as usize;
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Reader {
+            data,
+            pos: 0,
+            level: 0,
+        }
+    }
+
+    pub fn check_header(&mut self) -> Result<(), ChunkError> {
+        if self.read_bytes(4)? != chunk::LUA_SIGNATURE {
+            return Err(ChunkError::BadSignature);
+        }
+        let version = self.read_byte()?;
+        if version != chunk::LUAC_VERSION {
+            return Err(ChunkError::VersionMismatch(version));
+        }
+        let format = self.read_byte()?;
+        if format != chunk::LUAC_FORMAT {
+            return Err(ChunkError::FormatMismatch(format));
+        }
+        if self.read_bytes(6)? != chunk::LUAC_DATA {
+            return Err(ChunkError::Corrupted);
+        }
+        self.check_size("int", chunk::INT_SIZE)?;
+        self.check_size("size_t", chunk::SIZET_SIZE)?;
+        self.check_size("Instruction", chunk::INSTRUCTION_SIZE)?;
+        self.check_size("lua_Integer", chunk::LUA_INTEGER_SIZE)?;
+        self.check_size("lua_Number", chunk::LUA_NUMBER_SIZE)?;
+        if self.read_lua_integer()? != chunk::LUAC_INT {
+            return Err(ChunkError::EndiannessMismatch);
+        }
+        if self.read_lua_number()? != chunk::LUAC_NUM {
+            return Err(ChunkError::FloatFormatMismatch);
+        }
+        Ok(())
+    }
+
+    fn check_size(&mut self, what: &'static str, expected: u8) -> Result<(), ChunkError> {
+        let found = self.read_byte()?;
+        if found != expected {
+            return Err(ChunkError::SizeMismatch {
+                what,
+                expected,
+                found,
+            });
+        }
+        Ok(())
+    }
+
+    // number of bytes left to read
+    fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+
+    pub fn read_byte(&mut self) -> Result<u8, ChunkError> {
+        let b = *self
+            .data
+            .get(self.pos)
+            .ok_or(ChunkError::Truncated(self.pos))?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    fn read_u32(&mut self) -> Result<u32, ChunkError> {
+        let bytes = self.read_bytes(4)?;
+        Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn read_u64(&mut self) -> Result<u64, ChunkError> {
+        let bytes = self.read_bytes(8)?;
+        Ok(u64::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn read_lua_integer(&mut self) -> Result<i64, ChunkError> {
+        Ok(self.read_u64()? as i64)
+    }
+
+    fn read_lua_number(&mut self) -> Result<f64, ChunkError> {
+        let bit = self.read_u64()?;
+        Ok(f64::from_bits(bit))
+    }
+
+    fn read_string(&mut self) -> Result<String, ChunkError> {
+        let bytes = self.read_lstring()?;
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    fn read_lstring(&mut self) -> Result<Vec<u8>, ChunkError> {
+        let mut size = self.read_byte()? as usize;
         if size == 0 {
-            return String::new();
+            return Ok(Vec::new());
         }
 
         if size == 0xFF {
-            size = self.read_u64() as usize;
+            size = self.read_u64()? as usize;
         }
 
-        let bytes = self.read_bytes(size - 1);
-        String::from_utf8_lossy(&bytes).to_string()
+        Ok(self.read_bytes(size.saturating_sub(1))?.to_vec())
+    }
+
+    fn read_bytes(&mut self, n: usize) -> Result<&'a [u8], ChunkError> {
+        if n > self.remaining() {
+            return Err(ChunkError::Truncated(self.pos));
+        }
+        let bytes = &self.data[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(bytes)
     }
 
-    fn read_bytes(&mut self, n: usize) -> Vec<u8> {
-        let bytes = self.data[..n].into();
-        self.data.drain(..n);
-        bytes
+    pub fn read_proto(&mut self, parent_source: &str) -> Result<Rc<Prototype>, ChunkError> {
+        if self.level >= LUAI_MAXCCALLS {
+            return Err(ChunkError::TooManyLevels);
+        }
+        self.level += 1;
+        let proto = self.read_proto_body(parent_source)?;
+        self.level -= 1;
+        check_code(&proto)?;
+        Ok(Rc::new(proto))
     }
 
-    pub fn read_proto(&mut self, parent_source: &str) -> Rc<Prototype> {
-        let mut source: String = self.read_string();
+    fn read_proto_body(&mut self, parent_source: &str) -> Result<Prototype, ChunkError> {
+        let mut source: String = self.read_string()?;
         if source.is_empty() {
             source = parent_source.to_string();
         }
-        Rc::new(
-            Prototype::new()
-                .set_source(source.clone())
-                .set_line_defined(self.read_u32())
-                .set_last_line_defined(self.read_u32())
-                .set_num_params(self.read_byte())
-                .set_is_vararg(self.read_byte())
-                .set_max_stack_size(self.read_byte())
-                .set_code(self.read_func(|r| r.read_u32()))
-                .set_constants(self.read_func(|r| r.read_constant()))
-                .set_upvalues(self.read_func(|r| r.read_upvalue()))
-                .set_protos(self.read_func(|r| r.read_proto(source.as_str())))
-                .set_line_info(self.read_func(|r| r.read_u32()))
-                .set_locvars(self.read_func(|r| r.read_locvar()))
-                .set_upvalue_names(self.read_func(|r| r.read_string())),
-        )
-    }
-
-    fn read_func<T, F>(&mut self, func: F) -> Vec<T>
+        Ok(Prototype::new()
+            .set_source(source.clone())
+            .set_line_defined(self.read_u32()?)
+            .set_last_line_defined(self.read_u32()?)
+            .set_num_params(self.read_byte()?)
+            .set_is_vararg(self.read_byte()?)
+            .set_max_stack_size(self.read_byte()?)
+            .set_code(self.read_func(|r| r.read_u32())?)
+            .set_constants(self.read_func(|r| r.read_constant())?)
+            .set_upvalues(self.read_func(|r| r.read_upvalue())?)
+            .set_protos(self.read_func(|r| r.read_proto(source.as_str()))?)
+            .set_line_info(self.read_func(|r| r.read_u32())?)
+            .set_locvars(self.read_func(|r| r.read_locvar())?)
+            .set_upvalue_names(self.read_func(|r| r.read_string())?))
+    }
+
+    fn read_func<T, F>(&mut self, func: F) -> Result<Vec<T>, ChunkError>
     where
-        F: Fn(&mut Reader) -> T,
+        F: Fn(&mut Reader<'a>) -> Result<T, ChunkError>,
     {
-        let size: usize = self.read_u32().try_into().unwrap();
-        let mut vec = Vec::with_capacity(size);
+        let size = self.read_u32()? as usize;
+        // every element takes at least one byte, don't trust larger sizes
+        let mut vec = Vec::with_capacity(size.min(self.remaining()));
         for _ in 0..size {
-            vec.push(func(self));
+            vec.push(func(self)?);
         }
-        vec
+        Ok(vec)
     }
 
-    fn read_constant(&mut self) -> chunk::ConstantType {
-        let b = self.read_byte();
-        match b {
+    fn read_constant(&mut self) -> Result<chunk::ConstantType, ChunkError> {
+        let offset = self.pos;
+        let b = self.read_byte()?;
+        let k = match b {
             chunk::TAG_NIL => chunk::ConstantType::Nil,
-            chunk::TAG_BOOLEAN => chunk::ConstantType::Boolean(self.read_byte() != 0),
-            chunk::TAG_INTEGER => chunk::ConstantType::Integer(self.read_lua_integer()),
-            chunk::TAG_NUMBER => chunk::ConstantType::Number(self.read_lua_number()),
+            chunk::TAG_BOOLEAN => chunk::ConstantType::Boolean(self.read_byte()? != 0),
+            chunk::TAG_INTEGER => chunk::ConstantType::Integer(self.read_lua_integer()?),
+            chunk::TAG_NUMBER => chunk::ConstantType::Number(self.read_lua_number()?),
             chunk::TAG_SHORT_STR | chunk::TAG_LONG_STR => {
-                chunk::ConstantType::String(self.read_string())
+                chunk::ConstantType::String(self.read_lstring()?)
             }
-            _ => panic!("corrupted!"),
-        }
+            _ => return Err(ChunkError::BadConstantTag { tag: b, offset }),
+        };
+        Ok(k)
     }
 
-    fn read_upvalue(&mut self) -> Upvalue {
-        Upvalue {
-            instack: self.read_byte(),
-            idx: self.read_byte(),
-        }
+    fn read_upvalue(&mut self) -> Result<Upvalue, ChunkError> {
+        Ok(Upvalue {
+            instack: self.read_byte()?,
+            idx: self.read_byte()?,
+        })
     }
 
-    fn read_locvar(&mut self) -> LocVar {
-        LocVar {
-            var_name: self.read_string(),
-            start_pc: self.read_u32(),
-            end_pc: self.read_u32(),
+    fn read_locvar(&mut self) -> Result<LocVar, ChunkError> {
+        Ok(LocVar {
+            var_name: self.read_string()?,
+            start_pc: self.read_u32()?,
+            end_pc: self.read_u32()?,
+        })
+    }
+}
+
+// checks that the operands of every instruction stay within the registers,
+// constants, upvalues and functions of proto, so the VM never indexes past them
+fn check_code(proto: &Prototype) -> Result<(), ChunkError> {
+    let code = proto.code();
+    let max_stack = proto.max_stack_size() as isize;
+    let n_k = proto.constants().len() as isize;
+    let n_upvals = proto.upvalues().len() as isize;
+    let reg = |r: isize| (0..max_stack).contains(&r);
+    let rk = |x: isize| {
+        if x & 0x100 != 0 {
+            (x & 0xFF) < n_k
+        } else {
+            reg(x)
+        }
+    };
+    let upval = |u: isize| u < n_upvals;
+    let target =
+        |pc: usize, offset: isize| (0..code.len() as isize).contains(&(pc as isize + 1 + offset));
+    let extra_arg = |pc: usize| code.get(pc + 1).map(|i| (i.opcode(), i.ax()));
+
+    if code.last().map(|i| i.opcode()) != Some(OP_RETURN) {
+        return Err(ChunkError::BadInstruction {
+            pc: code.len(),
+            reason: "missing final RETURN",
+        });
+    }
+    let mut pc = 0;
+    while pc < code.len() {
+        let i = code[pc];
+        let op = i.opcode();
+        let (a, b, c) = i.abc();
+        let (_, bx) = i.a_bx();
+        let (_, sbx) = i.a_sbx();
+        let mut skip = false; // the instruction may skip the next one
+        let reason = match op {
+            OP_MOVE | OP_UNM | OP_BNOT | OP_NOT | OP_LEN if !reg(a) || !reg(b) => {
+                Some("register out of range")
+            }
+            OP_LOADK if !reg(a) || bx >= n_k => Some("constant out of range"),
+            OP_LOADKX => match extra_arg(pc) {
+                Some((OP_EXTRAARG, ax)) if reg(a) && ax < n_k => {
+                    pc += 1;
+                    None
+                }
+                _ => Some("bad LOADKX"),
+            },
+            OP_LOADBOOL => {
+                skip = c != 0;
+                (!reg(a)).then_some("register out of range")
+            }
+            OP_LOADNIL if !reg(a + b) => Some("register out of range"),
+            OP_GETUPVAL | OP_SETUPVAL if !reg(a) || !upval(b) => Some("upvalue out of range"),
+            OP_GETTABUP if !reg(a) || !upval(b) || !rk(c) => Some("operand out of range"),
+            OP_SETTABUP if !upval(a) || !rk(b) || !rk(c) => Some("operand out of range"),
+            OP_GETTABLE if !reg(a) || !reg(b) || !rk(c) => Some("operand out of range"),
+            OP_SETTABLE if !reg(a) || !rk(b) || !rk(c) => Some("operand out of range"),
+            OP_NEWTABLE if !reg(a) => Some("register out of range"),
+            OP_SELF if !reg(a + 1) || !reg(b) || !rk(c) => Some("operand out of range"),
+            OP_ADD..=OP_SHR if !reg(a) || !rk(b) || !rk(c) => Some("operand out of range"),
+            OP_CONCAT if !reg(a) || b > c || !reg(b) || !reg(c) => Some("register out of range"),
+            OP_JMP if !target(pc, sbx) => Some("jump out of range"),
+            OP_JMP if a > 0 && !reg(a - 1) => Some("register out of range"),
+            OP_EQ | OP_LT | OP_LE => {
+                skip = true;
+                (!rk(b) || !rk(c)).then_some("operand out of range")
+            }
+            OP_TEST | OP_TESTSET => {
+                skip = true;
+                (!reg(a) || (op == OP_TESTSET && !reg(b))).then_some("register out of range")
+            }
+            OP_CALL if !reg(a) || (b > 0 && !reg(a + b - 1)) || (c > 1 && !reg(a + c - 2)) => {
+                Some("register out of range")
+            }
+            OP_TAILCALL if !reg(a) || (b > 0 && !reg(a + b - 1)) => Some("register out of range"),
+            OP_RETURN | OP_VARARG if b > 1 && !reg(a + b - 2) => Some("register out of range"),
+            OP_VARARG if !reg(a) => Some("register out of range"),
+            OP_FORLOOP | OP_FORPREP if !reg(a + 3) => Some("register out of range"),
+            OP_TFORCALL if !reg(a + 2 + c.max(1)) => Some("register out of range"),
+            OP_TFORLOOP if !reg(a + 1) => Some("register out of range"),
+            OP_FORLOOP | OP_FORPREP | OP_TFORLOOP if !target(pc, sbx) => Some("jump out of range"),
+            OP_SETLIST if !reg(a + b) => Some("register out of range"),
+            OP_SETLIST if c == 0 => match extra_arg(pc) {
+                Some((OP_EXTRAARG, _)) => {
+                    pc += 1;
+                    None
+                }
+                _ => Some("bad SETLIST"),
+            },
+            OP_CLOSURE if !reg(a) => Some("register out of range"),
+            OP_CLOSURE => match proto.protos().get(bx as usize) {
+                // the new closure captures registers or upvalues of this function
+                Some(f) => {
+                    let captured = |uv: &Upvalue| match uv.instack {
+                        0 => upval(uv.idx as isize),
+                        _ => reg(uv.idx as isize),
+                    };
+                    (!f.upvalues().iter().all(captured)).then_some("upvalue out of range")
+                }
+                None => Some("function out of range"),
+            },
+            OP_EXTRAARG => Some("unexpected EXTRAARG"),
+            op if op > OP_EXTRAARG => Some("bad opcode"),
+            _ => None,
+        };
+        if reason.is_none() && skip && pc + 2 >= code.len() {
+            return Err(ChunkError::BadInstruction {
+                pc: pc + 1,
+                reason: "skip out of range",
+            });
+        }
+        if let Some(reason) = reason {
+            return Err(ChunkError::BadInstruction { pc: pc + 1, reason });
         }
+        pc += 1;
     }
+    Ok(())
 }
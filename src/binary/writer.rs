@@ -0,0 +1,155 @@
+use super::chunk::{self, ConstantType, LocVar, Prototype, Upvalue};
+
+const LUAI_MAXSHORTLEN: usize = 40; // longer strings are dumped as long strings
+
+#[derive(Debug)]
+pub struct Writer {
+    data: Vec<u8>,
+    strip: bool, // leave out debug information
+}
+
+impl Writer {
+    pub fn new(strip: bool) -> Self {
+        Writer {
+            data: vec![],
+            strip,
+        }
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+
+    pub fn write_header(&mut self) {
+        self.write_bytes(chunk::LUA_SIGNATURE);
+        self.write_byte(chunk::LUAC_VERSION);
+        self.write_byte(chunk::LUAC_FORMAT);
+        self.write_bytes(chunk::LUAC_DATA);
+        self.write_byte(chunk::INT_SIZE);
+        self.write_byte(chunk::SIZET_SIZE);
+        self.write_byte(chunk::INSTRUCTION_SIZE);
+        self.write_byte(chunk::LUA_INTEGER_SIZE);
+        self.write_byte(chunk::LUA_NUMBER_SIZE);
+        self.write_lua_integer(chunk::LUAC_INT);
+        self.write_lua_number(chunk::LUAC_NUM);
+    }
+
+    pub fn write_byte(&mut self, b: u8) {
+        self.data.push(b);
+    }
+
+    fn write_u32(&mut self, n: u32) {
+        self.write_bytes(&n.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.write_bytes(&n.to_le_bytes());
+    }
+
+    fn write_lua_integer(&mut self, n: i64) {
+        self.write_u64(n as u64);
+    }
+
+    fn write_lua_number(&mut self, n: f64) {
+        self.write_u64(n.to_bits());
+    }
+
+    // None is dumped as an empty string
+    fn write_string(&mut self, s: Option<&[u8]>) {
+        let s = match s {
+            Some(s) => s,
+            None => return self.write_byte(0),
+        };
+
+        let size = s.len() + 1;
+        if size < 0xFF {
+            self.write_byte(size as u8);
+        } else {
+            self.write_byte(0xFF);
+            self.write_u64(size as u64);
+        }
+        self.write_bytes(s);
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        self.data.extend_from_slice(bytes);
+    }
+
+    pub fn write_proto(&mut self, proto: &Prototype, parent_source: Option<&str>) {
+        // functions share the source of their parent
+        if self.strip || parent_source == Some(proto.source()) {
+            self.write_string(None);
+        } else {
+            self.write_string(Some(proto.source().as_bytes()));
+        }
+        self.write_u32(proto.line_defined());
+        self.write_u32(proto.last_line_defined());
+        self.write_byte(proto.num_params());
+        self.write_byte(proto.is_vararg());
+        self.write_byte(proto.max_stack_size());
+        self.write_func(proto.code(), |w, i| w.write_u32(*i));
+        self.write_func(proto.constants(), |w, k| w.write_constant(k));
+        self.write_func(proto.upvalues(), |w, uv| w.write_upvalue(uv));
+        self.write_func(proto.protos(), |w, p| w.write_proto(p, Some(proto.source())));
+        self.write_debug(proto);
+    }
+
+    fn write_debug(&mut self, proto: &Prototype) {
+        if self.strip {
+            self.write_u32(0); // line_info
+            self.write_u32(0); // locvars
+            self.write_u32(0); // upvalue_names
+        } else {
+            self.write_func(proto.line_info(), |w, line| w.write_u32(*line));
+            self.write_func(proto.locvars(), |w, var| w.write_locvar(var));
+            self.write_func(proto.upvalue_names(), |w, name| w.write_string(Some(name.as_bytes())));
+        }
+    }
+
+    fn write_func<T, F>(&mut self, items: &[T], func: F)
+    where
+        F: Fn(&mut Writer, &T),
+    {
+        self.write_u32(items.len() as u32);
+        for item in items {
+            func(self, item);
+        }
+    }
+
+    fn write_constant(&mut self, k: &ConstantType) {
+        match k {
+            ConstantType::Nil => self.write_byte(chunk::TAG_NIL),
+            ConstantType::Boolean(b) => {
+                self.write_byte(chunk::TAG_BOOLEAN);
+                self.write_byte(*b as u8);
+            }
+            ConstantType::Integer(n) => {
+                self.write_byte(chunk::TAG_INTEGER);
+                self.write_lua_integer(*n);
+            }
+            ConstantType::Number(n) => {
+                self.write_byte(chunk::TAG_NUMBER);
+                self.write_lua_number(*n);
+            }
+            ConstantType::String(s) => {
+                if s.len() <= LUAI_MAXSHORTLEN {
+                    self.write_byte(chunk::TAG_SHORT_STR);
+                } else {
+                    self.write_byte(chunk::TAG_LONG_STR);
+                }
+                self.write_string(Some(s));
+            }
+        }
+    }
+
+    fn write_upvalue(&mut self, uv: &Upvalue) {
+        self.write_byte(uv.instack);
+        self.write_byte(uv.idx);
+    }
+
+    fn write_locvar(&mut self, var: &LocVar) {
+        self.write_string(Some(var.var_name.as_bytes()));
+        self.write_u32(var.start_pc);
+        self.write_u32(var.end_pc);
+    }
+}
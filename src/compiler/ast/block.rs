@@ -0,0 +1,10 @@
+use super::{exp::Exp, stat::Stat};
+
+// block ::= {stat} [retstat]
+// retstat ::= return [explist] [';']
+#[derive(Debug)]
+pub struct Block {
+    pub last_line: usize,
+    pub stats: Vec<Stat>,
+    pub ret_exps: Option<Vec<Exp>>,
+}
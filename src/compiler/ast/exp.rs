@@ -0,0 +1,146 @@
+use super::block::Block;
+
+/*
+exp ::=  nil | false | true | Numeral | LiteralString | '...' | functiondef |
+     prefixexp | tableconstructor | exp binop exp | unop exp
+
+prefixexp ::= var | functioncall | '(' exp ')'
+
+var ::=  Name | prefixexp '[' exp ']' | prefixexp '.' Name
+
+functioncall ::=  prefixexp args | prefixexp ':' Name args
+*/
+#[derive(Debug)]
+pub enum Exp {
+    Nil {
+        line: usize,
+    },
+    True {
+        line: usize,
+    },
+    False {
+        line: usize,
+    },
+    Vararg {
+        line: usize,
+    },
+    Integer {
+        line: usize,
+        val: i64,
+    },
+    Float {
+        line: usize,
+        val: f64,
+    },
+    String {
+        line: usize,
+        val: Vec<u8>,
+    },
+    Unop {
+        line: usize, // line of operator
+        op: u8,      // operator
+        exp: Box<Exp>,
+    },
+    Binop {
+        line: usize, // line of operator
+        op: u8,      // operator
+        exp1: Box<Exp>,
+        exp2: Box<Exp>,
+    },
+    Concat {
+        line: usize, // line of last operator
+        exps: Vec<Exp>,
+    },
+    // tableconstructor ::= '{' [fieldlist] '}'
+    // fieldlist ::= field {fieldsep field} [fieldsep]
+    // field ::= '[' exp ']' '=' exp | Name '=' exp | exp
+    // fieldsep ::= ',' | ';'
+    TableConstructor {
+        line: usize,      // line of '{'
+        last_line: usize, // line of '}'
+        key_exps: Vec<Option<Exp>>,
+        val_exps: Vec<Exp>,
+    },
+    FuncDef(FuncDefExp),
+    Name {
+        line: usize,
+        name: String,
+    },
+    Parens(Box<Exp>),
+    TableAccess {
+        last_line: usize, // line of ']'
+        prefix_exp: Box<Exp>,
+        key_exp: Box<Exp>,
+    },
+    FuncCall(FuncCallExp),
+}
+
+// functiondef ::= function funcbody
+// funcbody ::= '(' [parlist] ')' block end
+// parlist ::= namelist [',' '...'] | '...'
+// namelist ::= Name {',' Name}
+#[derive(Debug)]
+pub struct FuncDefExp {
+    pub line: usize,
+    pub last_line: usize, // line of `end`
+    pub par_list: Vec<String>,
+    pub is_vararg: bool,
+    pub block: Box<Block>,
+}
+
+#[derive(Debug)]
+pub struct FuncCallExp {
+    pub line: usize,      // line of prefixexp
+    pub last_line: usize, // line of ')'
+    pub prefix_exp: Box<Exp>,
+    pub name_exp: Option<Box<Exp>>,
+    pub args: Vec<Exp>,
+}
+
+impl Exp {
+    pub fn is_vararg_or_func_call(&self) -> bool {
+        matches!(self, Exp::Vararg { .. } | Exp::FuncCall(_))
+    }
+
+    pub fn line(&self) -> usize {
+        match self {
+            Exp::Nil { line }
+            | Exp::True { line }
+            | Exp::False { line }
+            | Exp::Vararg { line }
+            | Exp::Integer { line, .. }
+            | Exp::Float { line, .. }
+            | Exp::String { line, .. }
+            | Exp::Unop { line, .. }
+            | Exp::Name { line, .. }
+            | Exp::TableConstructor { line, .. } => *line,
+            Exp::Binop { exp1, .. } => exp1.line(),
+            Exp::Concat { exps, .. } => exps[0].line(),
+            Exp::FuncDef(f) => f.line,
+            Exp::Parens(exp) => exp.line(),
+            Exp::TableAccess { prefix_exp, .. } => prefix_exp.line(),
+            Exp::FuncCall(f) => f.prefix_exp.line(),
+        }
+    }
+
+    pub fn last_line(&self) -> usize {
+        match self {
+            Exp::Nil { line }
+            | Exp::True { line }
+            | Exp::False { line }
+            | Exp::Vararg { line }
+            | Exp::Integer { line, .. }
+            | Exp::Float { line, .. }
+            | Exp::String { line, .. }
+            | Exp::Name { line, .. } => *line,
+            Exp::Unop { exp, .. } => exp.last_line(),
+            Exp::Binop { exp2, .. } => exp2.last_line(),
+            Exp::Concat { exps, .. } => exps[exps.len() - 1].last_line(),
+            Exp::TableConstructor { last_line, .. } => *last_line,
+            Exp::FuncDef(f) => f.last_line,
+            Exp::Parens(exp) => exp.last_line(),
+            Exp::TableAccess { last_line, .. } => *last_line,
+            Exp::FuncCall(f) => f.last_line,
+        }
+    }
+}
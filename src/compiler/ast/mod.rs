@@ -0,0 +1,5 @@
+mod block;
+mod exp;
+mod stat;
+
+pub use self::{block::*, exp::*, stat::*};
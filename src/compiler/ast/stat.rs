@@ -0,0 +1,83 @@
+use super::{
+    block::Block,
+    exp::{Exp, FuncCallExp, FuncDefExp},
+};
+
+/*
+stat ::=  ';' |
+     varlist '=' explist |
+     functioncall |
+     label |
+     break |
+     goto Name |
+     do block end |
+     while exp do block end |
+     repeat block until exp |
+     if exp then block {elseif exp then block} [else block] end |
+     for Name '=' exp ',' exp [',' exp] do block end |
+     for namelist in explist do block end |
+     function funcname funcbody |
+     local function Name funcbody |
+     local namelist ['=' explist]
+*/
+#[derive(Debug)]
+pub enum Stat {
+    Empty,
+    Break {
+        line: usize,
+    },
+    Label {
+        line: usize,
+        name: String,
+    },
+    Goto {
+        line: usize,
+        name: String,
+    },
+    Do {
+        block: Box<Block>,
+    },
+    FuncCall(FuncCallExp),
+    While {
+        exp: Exp,
+        block: Box<Block>,
+    },
+    Repeat {
+        block: Box<Block>,
+        exp: Exp,
+    },
+    If {
+        exps: Vec<Exp>,
+        blocks: Vec<Block>,
+    },
+    ForNum {
+        line_of_for: usize,
+        line_of_do: usize,
+        var_name: String,
+        init_exp: Exp,
+        limit_exp: Exp,
+        step_exp: Exp,
+        block: Box<Block>,
+    },
+    ForIn {
+        line_of_do: usize,
+        name_list: Vec<String>,
+        exp_list: Vec<Exp>,
+        block: Box<Block>,
+    },
+    LocalVarDecl {
+        line: usize, // line of the first name
+        last_line: usize,
+        name_list: Vec<String>,
+        exp_list: Vec<Exp>,
+    },
+    Assign {
+        last_line: usize,
+        var_list: Vec<Exp>,
+        exp_list: Vec<Exp>,
+    },
+    LocalFuncDef {
+        name: String,
+        exp: FuncDefExp,
+    },
+}
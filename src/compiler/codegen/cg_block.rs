@@ -0,0 +1,87 @@
+use crate::compiler::ast::{Block, Exp, Stat};
+
+use super::{
+    cg_exp::{cg_exp, cg_tail_call_exp},
+    cg_stat::cg_stat,
+    func_info::FuncInfo,
+};
+
+pub fn cg_block(fi: &mut FuncInfo, node: &Block) -> Result<(), String> {
+    cg_stats(fi, node, false)
+}
+
+// generates the statements of a block; `with_until` is set for the body of
+// a repeat loop, whose locals are still visible in the condition
+pub fn cg_stats(fi: &mut FuncInfo, node: &Block, with_until: bool) -> Result<(), String> {
+    for (i, stat) in node.stats.iter().enumerate() {
+        if let Stat::Label { line, name } = stat {
+            // labels at the end of a block are outside the scope of its locals
+            let is_last = !with_until
+                && node.ret_exps.is_none()
+                && node.stats[i + 1..]
+                    .iter()
+                    .all(|s| matches!(s, Stat::Label { .. }));
+            fi.add_label(name, *line, is_last)?;
+        } else {
+            cg_stat(fi, stat)?;
+        }
+    }
+
+    if let Some(ret_exps) = &node.ret_exps {
+        cg_ret_stat(fi, ret_exps, node.last_line)?;
+    }
+    Ok(())
+}
+
+// generates a block in a scope of its own
+pub fn cg_scoped_block(fi: &mut FuncInfo, node: &Block) -> Result<(), String> {
+    fi.enter_scope(false);
+    cg_block(fi, node)?;
+    fi.exit_scope(node.last_line)
+}
+
+fn cg_ret_stat(fi: &mut FuncInfo, exps: &[Exp], line: usize) -> Result<(), String> {
+    let n_exps = exps.len();
+    if n_exps == 0 {
+        fi.emit_return(line, 0, 0);
+        return Ok(());
+    }
+
+    if n_exps == 1 {
+        match &exps[0] {
+            Exp::Name { name, .. } => {
+                if let Some(r) = fi.slot_of_loc_var(name) {
+                    fi.emit_return(line, r, 1);
+                    return Ok(());
+                }
+            }
+            Exp::FuncCall(fc) => {
+                let r = fi.alloc_reg()?;
+                cg_tail_call_exp(fi, fc, r)?;
+                fi.free_reg();
+                fi.emit_return(line, r, -1);
+                return Ok(());
+            }
+            _ => {}
+        }
+    }
+
+    let mult_ret = exps[n_exps - 1].is_vararg_or_func_call();
+    for (i, exp) in exps.iter().enumerate() {
+        let r = fi.alloc_reg()?;
+        if i == n_exps - 1 && mult_ret {
+            cg_exp(fi, exp, r, -1)?;
+        } else {
+            cg_exp(fi, exp, r, 1)?;
+        }
+    }
+    fi.free_regs(n_exps);
+
+    let a = fi.used_regs();
+    if mult_ret {
+        fi.emit_return(line, a, -1);
+    } else {
+        fi.emit_return(line, a, n_exps as isize);
+    }
+    Ok(())
+}
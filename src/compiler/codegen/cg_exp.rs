@@ -0,0 +1,350 @@
+use crate::{
+    binary::chunk::ConstantType,
+    compiler::{
+        ast::{Exp, FuncCallExp, FuncDefExp},
+        lexer::token::*,
+    },
+};
+
+use super::{cg_block::cg_block, func_info::FuncInfo};
+
+// kinds of operands an expression can be turned into
+pub const ARG_CONST: u8 = 1; // const index
+pub const ARG_REG: u8 = 2; // register index
+pub const ARG_UPVAL: u8 = 4; // upvalue index
+pub const ARG_RK: u8 = ARG_REG | ARG_CONST;
+pub const ARG_RU: u8 = ARG_REG | ARG_UPVAL;
+const ARG_LOCAL: u8 = 8; // register of a local variable, nothing to free
+
+const LFIELDS_PER_FLUSH: usize = 50; // number of list items to accumulate before a SETLIST
+
+// evaluates exp into registers a, a+1, ..., a+n-1 (n = -1 means all values)
+pub fn cg_exp(fi: &mut FuncInfo, node: &Exp, a: usize, n: isize) -> Result<(), String> {
+    match node {
+        Exp::Nil { line } => fi.emit_load_nil(*line, a, n as usize),
+        Exp::False { line } => fi.emit_load_bool(*line, a, 0, 0),
+        Exp::True { line } => fi.emit_load_bool(*line, a, 1, 0),
+        Exp::Integer { line, val } => fi.emit_load_k(*line, a, ConstantType::Integer(*val))?,
+        Exp::Float { line, val } => fi.emit_load_k(*line, a, ConstantType::Number(*val))?,
+        Exp::String { line, val } => fi.emit_load_k(*line, a, ConstantType::String(val.clone()))?,
+        Exp::Parens(exp) => cg_exp(fi, exp, a, 1)?,
+        Exp::Vararg { line } => cg_vararg_exp(fi, *line, a, n)?,
+        Exp::FuncDef(fd) => cg_func_def_exp(fi, fd, a)?,
+        Exp::TableConstructor {
+            line,
+            last_line,
+            key_exps,
+            val_exps,
+        } => cg_table_constructor_exp(fi, *line, *last_line, key_exps, val_exps, a)?,
+        Exp::Unop { line, op, exp } => cg_unop_exp(fi, *line, *op, exp, a)?,
+        Exp::Binop {
+            line,
+            op,
+            exp1,
+            exp2,
+        } => cg_binop_exp(fi, *line, *op, exp1, exp2, a)?,
+        Exp::Concat { line, exps } => cg_concat_exp(fi, *line, exps, a)?,
+        Exp::Name { line, name } => cg_name_exp(fi, *line, name, a)?,
+        Exp::TableAccess {
+            last_line,
+            prefix_exp,
+            key_exp,
+        } => cg_table_access_exp(fi, *last_line, prefix_exp, key_exp, a)?,
+        Exp::FuncCall(fc) => cg_func_call_exp(fi, fc, a, n)?,
+    }
+    Ok(())
+}
+
+fn cg_vararg_exp(fi: &mut FuncInfo, line: usize, a: usize, n: isize) -> Result<(), String> {
+    if !fi.is_vararg {
+        return fi.error(
+            line,
+            "cannot use '...' outside a vararg function near '...'",
+        );
+    }
+    fi.emit_vararg(line, a, n);
+    Ok(())
+}
+
+// f[a] := function(args) body end
+pub fn cg_func_def_exp(fi: &mut FuncInfo, node: &FuncDefExp, a: usize) -> Result<(), String> {
+    let source = fi.source().to_string();
+    let parent = std::mem::take(fi);
+    let mut sub = FuncInfo::new(Some(Box::new(parent)), node, &source);
+
+    let result = cg_func_body(&mut sub, node);
+    *fi = *sub.parent.take().unwrap();
+    result?;
+
+    fi.sub_protos.push(sub.into_proto());
+    let bx = fi.sub_protos.len() - 1;
+    fi.emit_closure(node.last_line, a, bx);
+    Ok(())
+}
+
+fn cg_func_body(fi: &mut FuncInfo, node: &FuncDefExp) -> Result<(), String> {
+    fi.enter_scope(false);
+    for param in &node.par_list {
+        fi.add_loc_var(param, node.line, 0)?;
+    }
+    cg_block(fi, &node.block)?;
+    fi.emit_return(node.last_line, 0, 0);
+    fi.exit_scope(node.last_line)
+}
+
+fn cg_table_constructor_exp(
+    fi: &mut FuncInfo,
+    line: usize,
+    last_line: usize,
+    key_exps: &[Option<Exp>],
+    val_exps: &[Exp],
+    a: usize,
+) -> Result<(), String> {
+    let n_exps = val_exps.len();
+    let mult_ret = n_exps > 0
+        && key_exps[n_exps - 1].is_none()
+        && val_exps[n_exps - 1].is_vararg_or_func_call();
+    let n_list = key_exps.iter().filter(|k| k.is_none()).count();
+    fi.emit_new_table(line, a, n_list - mult_ret as usize, n_exps - n_list);
+
+    let mut pending = 0; // list items waiting in registers for a SETLIST
+    let mut flushed = 0; // number of SETLISTs emitted so far
+    for (i, (key_exp, val_exp)) in key_exps.iter().zip(val_exps).enumerate() {
+        match key_exp {
+            None => {
+                let tmp = fi.alloc_reg()?;
+                if i == n_exps - 1 && mult_ret {
+                    cg_exp(fi, val_exp, tmp, -1)?;
+                } else {
+                    cg_exp(fi, val_exp, tmp, 1)?;
+                }
+                pending += 1;
+
+                if pending == LFIELDS_PER_FLUSH {
+                    flushed += 1;
+                    fi.emit_set_list(val_exp.last_line(), a, pending, flushed)?;
+                    fi.free_regs(pending);
+                    pending = 0;
+                }
+            }
+            Some(key_exp) => {
+                let (b, kb) = exp_to_op_arg(fi, key_exp, ARG_RK)?;
+                let (c, kc) = exp_to_op_arg(fi, val_exp, ARG_RK)?;
+                fi.emit_set_table(val_exp.last_line(), a, b, c);
+                free_op_arg(fi, kc);
+                free_op_arg(fi, kb);
+            }
+        }
+    }
+
+    if pending > 0 {
+        flushed += 1;
+        if mult_ret {
+            fi.emit_set_list(last_line, a, 0, flushed)?;
+        } else {
+            fi.emit_set_list(last_line, a, pending, flushed)?;
+        }
+        fi.free_regs(pending);
+    }
+    Ok(())
+}
+
+// r[a] := op exp
+fn cg_unop_exp(fi: &mut FuncInfo, line: usize, op: u8, exp: &Exp, a: usize) -> Result<(), String> {
+    let (b, kind) = exp_to_op_arg(fi, exp, ARG_REG)?;
+    fi.emit_unary_op(line, op, a, b);
+    free_op_arg(fi, kind);
+    Ok(())
+}
+
+// r[a] := exp1 op exp2
+fn cg_binop_exp(
+    fi: &mut FuncInfo,
+    line: usize,
+    op: u8,
+    exp1: &Exp,
+    exp2: &Exp,
+    a: usize,
+) -> Result<(), String> {
+    match op {
+        TOKEN_OP_AND | TOKEN_OP_OR => {
+            let (b, kind) = exp_to_op_arg(fi, exp1, ARG_REG)?;
+            free_op_arg(fi, kind);
+            // and: keep exp1 if it is false, or: keep it if it is true
+            fi.emit_test_set(line, a, b, (op == TOKEN_OP_OR) as usize);
+            let pc_of_jmp = fi.emit_jmp(line, 0, 0);
+
+            let (b, kind) = exp_to_op_arg(fi, exp2, ARG_REG)?;
+            free_op_arg(fi, kind);
+            fi.emit_move(line, a, b);
+            fi.patch_to_here(pc_of_jmp);
+        }
+        TOKEN_OP_EQ | TOKEN_OP_NE | TOKEN_OP_LT | TOKEN_OP_GT | TOKEN_OP_LE | TOKEN_OP_GE => {
+            let (b, kb) = exp_to_op_arg(fi, exp1, ARG_RK)?;
+            let (c, kc) = exp_to_op_arg(fi, exp2, ARG_RK)?;
+            fi.emit_compare(line, op, true, b, c);
+            fi.emit_jmp(line, 0, 1);
+            fi.emit_load_bool(line, a, 0, 1);
+            fi.emit_load_bool(line, a, 1, 0);
+            free_op_arg(fi, kc);
+            free_op_arg(fi, kb);
+        }
+        _ => {
+            let (b, kb) = exp_to_op_arg(fi, exp1, ARG_RK)?;
+            let (c, kc) = exp_to_op_arg(fi, exp2, ARG_RK)?;
+            fi.emit_arith_op(line, op, a, b, c);
+            free_op_arg(fi, kc);
+            free_op_arg(fi, kb);
+        }
+    }
+    Ok(())
+}
+
+// r[a] := exp1 .. exp2 .. ... .. expn
+fn cg_concat_exp(fi: &mut FuncInfo, line: usize, exps: &[Exp], a: usize) -> Result<(), String> {
+    let b = fi.used_regs();
+    for exp in exps {
+        let tmp = fi.alloc_reg()?;
+        cg_exp(fi, exp, tmp, 1)?;
+    }
+
+    let c = fi.used_regs() - 1;
+    fi.free_regs(exps.len());
+    fi.emit_concat(line, a, b, c);
+    Ok(())
+}
+
+// r[a] := name
+fn cg_name_exp(fi: &mut FuncInfo, line: usize, name: &str, a: usize) -> Result<(), String> {
+    if let Some(r) = fi.slot_of_loc_var(name) {
+        if r != a {
+            fi.emit_move(line, a, r);
+        }
+    } else if let Some(idx) = fi.index_of_upval(name)? {
+        fi.emit_get_upval(line, a, idx);
+    } else {
+        // x => _ENV['x']
+        let env = Exp::Name {
+            line,
+            name: "_ENV".to_string(),
+        };
+        let key = Exp::String {
+            line,
+            val: name.as_bytes().to_vec(),
+        };
+        cg_table_access_exp(fi, line, &env, &key, a)?;
+    }
+    Ok(())
+}
+
+// r[a] := prefix[key]
+fn cg_table_access_exp(
+    fi: &mut FuncInfo,
+    line: usize,
+    prefix_exp: &Exp,
+    key_exp: &Exp,
+    a: usize,
+) -> Result<(), String> {
+    let (b, kb) = exp_to_op_arg(fi, prefix_exp, ARG_RU)?;
+    let (c, kc) = exp_to_op_arg(fi, key_exp, ARG_RK)?;
+    if kb == ARG_UPVAL {
+        fi.emit_get_tabup(line, a, b, c);
+    } else {
+        fi.emit_get_table(line, a, b, c);
+    }
+    free_op_arg(fi, kc);
+    free_op_arg(fi, kb);
+    Ok(())
+}
+
+// r[a], r[a+1], ..., r[a+n-1] := f(args)
+pub fn cg_func_call_exp(
+    fi: &mut FuncInfo,
+    node: &FuncCallExp,
+    a: usize,
+    n: isize,
+) -> Result<(), String> {
+    let n_args = prep_func_call(fi, node, a)?;
+    fi.emit_call(node.line, a, n_args, n);
+    Ok(())
+}
+
+// return f(args)
+pub fn cg_tail_call_exp(fi: &mut FuncInfo, node: &FuncCallExp, a: usize) -> Result<(), String> {
+    let n_args = prep_func_call(fi, node, a)?;
+    fi.emit_tail_call(node.line, a, n_args);
+    Ok(())
+}
+
+// puts the function and its arguments in r[a], r[a+1], ... and returns the
+// number of arguments (-1 if the last argument is multi-valued)
+fn prep_func_call(fi: &mut FuncInfo, node: &FuncCallExp, a: usize) -> Result<isize, String> {
+    let mut n_args = node.args.len() as isize;
+    let mut last_arg_is_vararg_or_func_call = false;
+
+    cg_exp(fi, &node.prefix_exp, a, 1)?;
+    if let Some(name_exp) = &node.name_exp {
+        fi.alloc_reg()?;
+        let (c, kind) = exp_to_op_arg(fi, name_exp, ARG_RK)?;
+        fi.emit_self(node.line, a, a, c);
+        free_op_arg(fi, kind);
+        n_args += 1;
+    }
+
+    for (i, arg) in node.args.iter().enumerate() {
+        let tmp = fi.alloc_reg()?;
+        if i == node.args.len() - 1 && arg.is_vararg_or_func_call() {
+            last_arg_is_vararg_or_func_call = true;
+            cg_exp(fi, arg, tmp, -1)?;
+        } else {
+            cg_exp(fi, arg, tmp, 1)?;
+        }
+    }
+    fi.free_regs(n_args as usize);
+
+    if last_arg_is_vararg_or_func_call {
+        n_args = -1;
+    }
+    Ok(n_args)
+}
+
+// turns exp into an operand of one of the given kinds, returning the
+// operand and its actual kind; a register operand of kind ARG_REG that was
+// allocated for the purpose must be released with free_op_arg
+pub fn exp_to_op_arg(fi: &mut FuncInfo, node: &Exp, arg_kinds: u8) -> Result<(usize, u8), String> {
+    if arg_kinds & ARG_CONST != 0 {
+        let k = match node {
+            Exp::Nil { .. } => Some(ConstantType::Nil),
+            Exp::False { .. } => Some(ConstantType::Boolean(false)),
+            Exp::True { .. } => Some(ConstantType::Boolean(true)),
+            Exp::Integer { val, .. } => Some(ConstantType::Integer(*val)),
+            Exp::Float { val, .. } => Some(ConstantType::Number(*val)),
+            Exp::String { val, .. } => Some(ConstantType::String(val.clone())),
+            _ => None,
+        };
+        if let Some(rk) = k.and_then(|k| fi.rk_of_constant(k)) {
+            return Ok((rk, ARG_CONST));
+        }
+    }
+
+    if let Exp::Name { name, .. } = node {
+        if let Some(r) = fi.slot_of_loc_var(name) {
+            return Ok((r, ARG_LOCAL));
+        }
+        if arg_kinds & ARG_UPVAL != 0 {
+            if let Some(idx) = fi.index_of_upval(name)? {
+                return Ok((idx, ARG_UPVAL));
+            }
+        }
+    }
+
+    let a = fi.alloc_reg()?;
+    cg_exp(fi, node, a, 1)?;
+    Ok((a, ARG_REG))
+}
+
+pub fn free_op_arg(fi: &mut FuncInfo, kind: u8) {
+    if kind == ARG_REG {
+        fi.free_reg();
+    }
+}
@@ -0,0 +1,427 @@
+use crate::compiler::{
+    ast::{Block, Exp, FuncCallExp, FuncDefExp, Stat},
+    lexer::token::*,
+};
+
+use super::{
+    cg_block::{cg_scoped_block, cg_stats},
+    cg_exp::*,
+    func_info::FuncInfo,
+};
+
+pub fn cg_stat(fi: &mut FuncInfo, node: &Stat) -> Result<(), String> {
+    match node {
+        Stat::Empty | Stat::Label { .. } => Ok(()),
+        Stat::FuncCall(fc) => cg_func_call_stat(fi, fc),
+        Stat::Break { line } => cg_goto_stat(fi, *line, "break"),
+        Stat::Goto { line, name } => cg_goto_stat(fi, *line, name),
+        Stat::Do { block } => cg_scoped_block(fi, block),
+        Stat::While { exp, block } => cg_while_stat(fi, exp, block),
+        Stat::Repeat { block, exp } => cg_repeat_stat(fi, block, exp),
+        Stat::If { exps, blocks } => cg_if_stat(fi, exps, blocks),
+        Stat::ForNum {
+            line_of_for,
+            line_of_do,
+            var_name,
+            init_exp,
+            limit_exp,
+            step_exp,
+            block,
+        } => {
+            let exps = [init_exp, limit_exp, step_exp];
+            cg_for_num_stat(fi, *line_of_for, *line_of_do, var_name, exps, block)
+        }
+        Stat::ForIn {
+            line_of_do,
+            name_list,
+            exp_list,
+            block,
+        } => cg_for_in_stat(fi, *line_of_do, name_list, exp_list, block),
+        Stat::LocalVarDecl {
+            line,
+            last_line,
+            name_list,
+            exp_list,
+        } => cg_local_var_decl_stat(fi, *line, *last_line, name_list, exp_list),
+        Stat::Assign {
+            last_line,
+            var_list,
+            exp_list,
+        } => cg_assign_stat(fi, *last_line, var_list, exp_list),
+        Stat::LocalFuncDef { name, exp } => cg_local_func_def_stat(fi, name, exp),
+    }
+}
+
+fn cg_func_call_stat(fi: &mut FuncInfo, node: &FuncCallExp) -> Result<(), String> {
+    let r = fi.alloc_reg()?;
+    cg_func_call_exp(fi, node, r, 0)?;
+    fi.free_reg();
+    Ok(())
+}
+
+fn cg_goto_stat(fi: &mut FuncInfo, line: usize, name: &str) -> Result<(), String> {
+    let pc = fi.emit_jmp(line, 0, 0);
+    fi.add_goto(name, line, pc)
+}
+
+// emits code that jumps when exp is false and returns the pc of that jump,
+// or None if exp is a constant that is always true
+fn cg_cond(fi: &mut FuncInfo, exp: &Exp) -> Result<Option<usize>, String> {
+    match exp {
+        Exp::True { .. } | Exp::Integer { .. } | Exp::Float { .. } | Exp::String { .. } => Ok(None),
+        Exp::Nil { line } | Exp::False { line } => Ok(Some(fi.emit_jmp(*line, 0, 0))),
+        Exp::Binop {
+            line,
+            op:
+                op @ (TOKEN_OP_EQ | TOKEN_OP_NE | TOKEN_OP_LT | TOKEN_OP_GT | TOKEN_OP_LE | TOKEN_OP_GE),
+            exp1,
+            exp2,
+        } => {
+            let (b, kb) = exp_to_op_arg(fi, exp1, ARG_RK)?;
+            let (c, kc) = exp_to_op_arg(fi, exp2, ARG_RK)?;
+            free_op_arg(fi, kc);
+            free_op_arg(fi, kb);
+            fi.emit_compare(*line, *op, false, b, c);
+            Ok(Some(fi.emit_jmp(*line, 0, 0)))
+        }
+        _ => {
+            let line = exp.last_line();
+            let (a, kind) = exp_to_op_arg(fi, exp, ARG_REG)?;
+            free_op_arg(fi, kind);
+            fi.emit_test(line, a, 0);
+            Ok(Some(fi.emit_jmp(line, 0, 0)))
+        }
+    }
+}
+
+/*
+           ______________
+          /  false? jmp  |
+         /               |
+while exp do block end <-'
+      ^           \
+      |___________/
+           jmp
+*/
+fn cg_while_stat(fi: &mut FuncInfo, exp: &Exp, block: &Block) -> Result<(), String> {
+    let pc_before_exp = fi.pc();
+    let pc_jmp_to_end = cg_cond(fi, exp)?;
+
+    fi.enter_scope(true);
+    cg_scoped_block(fi, block)?;
+    let pc = fi.emit_jmp(block.last_line, 0, 0);
+    fi.patch_to(pc, pc_before_exp);
+    fi.exit_scope(block.last_line)?;
+
+    if let Some(pc) = pc_jmp_to_end {
+        fi.patch_to_here(pc);
+    }
+    Ok(())
+}
+
+/*
+        ______________
+       |  false? jmp  |
+       V              /
+repeat block until exp
+*/
+fn cg_repeat_stat(fi: &mut FuncInfo, block: &Block, exp: &Exp) -> Result<(), String> {
+    let pc_before_block = fi.pc();
+
+    fi.enter_scope(true);
+    fi.enter_scope(false);
+    cg_stats(fi, block, true)?;
+    // the condition can see the locals of the block
+    let pc_jmp_back = cg_cond(fi, exp)?;
+    if let Some(pc) = pc_jmp_back {
+        fi.close_block_upvals_on(pc);
+    }
+    fi.exit_scope(exp.last_line())?;
+    if let Some(pc) = pc_jmp_back {
+        fi.patch_to(pc, pc_before_block);
+    }
+    fi.exit_scope(exp.last_line())
+}
+
+/*
+         _________________       _________________       _____________
+        / false? jmp      |     / false? jmp      |     / false? jmp  |
+       /                  V    /                  V    /              V
+if exp1 then block1 elseif exp2 then block2 elseif true then block3 end <-.
+                   \                       \                       \      |
+                    \_______________________\_______________________\_____|
+                    jmp                     jmp                     jmp
+*/
+fn cg_if_stat(fi: &mut FuncInfo, exps: &[Exp], blocks: &[Block]) -> Result<(), String> {
+    let mut pc_jmp_to_ends = vec![];
+
+    for (i, (exp, block)) in exps.iter().zip(blocks).enumerate() {
+        let pc_jmp_to_next = cg_cond(fi, exp)?;
+        cg_scoped_block(fi, block)?;
+        if i < exps.len() - 1 {
+            pc_jmp_to_ends.push(fi.emit_jmp(block.last_line, 0, 0));
+        }
+        if let Some(pc) = pc_jmp_to_next {
+            fi.patch_to_here(pc);
+        }
+    }
+
+    for pc in pc_jmp_to_ends {
+        fi.patch_to_here(pc);
+    }
+    Ok(())
+}
+
+fn cg_for_num_stat(
+    fi: &mut FuncInfo,
+    line_of_for: usize,
+    line_of_do: usize,
+    var_name: &str,
+    exps: [&Exp; 3],
+    block: &Block,
+) -> Result<(), String> {
+    fi.enter_scope(true);
+
+    let a = fi.used_regs();
+    for (name, exp) in ["(for index)", "(for limit)", "(for step)"]
+        .iter()
+        .zip(exps)
+    {
+        let r = fi.alloc_reg()?;
+        cg_exp(fi, exp, r, 1)?;
+        fi.free_reg();
+        fi.add_loc_var(name, line_of_for, fi.pc())?;
+    }
+
+    let pc_for_prep = fi.emit_for_prep(line_of_do, a);
+    fi.enter_scope(false);
+    fi.add_loc_var(var_name, line_of_for, fi.pc())?;
+    cg_scoped_block(fi, block)?;
+    fi.exit_scope(block.last_line)?;
+
+    fi.patch_to_here(pc_for_prep);
+    let pc_for_loop = fi.emit_for_loop(line_of_for, a);
+    fi.patch_to(pc_for_loop, pc_for_prep + 1);
+
+    fi.exit_scope(line_of_for)
+}
+
+fn cg_for_in_stat(
+    fi: &mut FuncInfo,
+    line_of_do: usize,
+    name_list: &[String],
+    exp_list: &[Exp],
+    block: &Block,
+) -> Result<(), String> {
+    let line = exp_list[0].line();
+    fi.enter_scope(true);
+
+    let a = fi.used_regs();
+    let hidden = ["(for generator)", "(for state)", "(for control)"].map(String::from);
+    cg_local_var_decl_stat(fi, line, line, &hidden, exp_list)?;
+    fi.check_stack(3)?;
+
+    let pc_jmp_to_tfc = fi.emit_jmp(line_of_do, 0, 0);
+    fi.enter_scope(false);
+    for name in name_list {
+        fi.add_loc_var(name, line_of_do, fi.pc())?;
+    }
+    cg_scoped_block(fi, block)?;
+    fi.exit_scope(block.last_line)?;
+
+    fi.patch_to_here(pc_jmp_to_tfc);
+    fi.emit_tfor_call(line, a, name_list.len());
+    let pc_tfor_loop = fi.emit_tfor_loop(line, a + 2);
+    fi.patch_to(pc_tfor_loop, pc_jmp_to_tfc + 1);
+
+    fi.exit_scope(line)
+}
+
+fn cg_local_var_decl_stat(
+    fi: &mut FuncInfo,
+    line: usize,
+    last_line: usize,
+    name_list: &[String],
+    exp_list: &[Exp],
+) -> Result<(), String> {
+    // like luac, the limit is checked where the names are declared
+    fi.check_loc_vars(line, name_list.len())?;
+    let old_regs = fi.used_regs();
+    cg_adjusted_exps(fi, last_line, exp_list, name_list.len())?;
+    fi.set_used_regs(old_regs);
+
+    // the new locals come into scope after the declaration
+    let start_pc = fi.pc();
+    for name in name_list {
+        fi.add_loc_var(name, line, start_pc)?;
+    }
+    Ok(())
+}
+
+// evaluates exps into n consecutive new registers, dropping extra values
+// and filling missing ones with nil
+fn cg_adjusted_exps(fi: &mut FuncInfo, line: usize, exps: &[Exp], n: usize) -> Result<(), String> {
+    let n_exps = exps.len();
+    let mult_ret = n_exps > 0 && exps[n_exps - 1].is_vararg_or_func_call();
+
+    for (i, exp) in exps.iter().enumerate() {
+        let a = fi.alloc_reg()?;
+        if i == n_exps - 1 && mult_ret {
+            // the extra results land above a, so they are only reserved
+            // once the call or vararg has been generated
+            let wanted = n.saturating_sub(i);
+            cg_exp(fi, exp, a, wanted as isize)?;
+            if wanted > 1 {
+                fi.alloc_regs(wanted - 1)?;
+            }
+        } else {
+            cg_exp(fi, exp, a, 1)?;
+        }
+    }
+
+    if n_exps < n && !mult_ret {
+        let a = fi.alloc_regs(n - n_exps)?;
+        fi.emit_load_nil(line, a, n - n_exps);
+    }
+    Ok(())
+}
+
+// the place an assignment stores into
+enum Target {
+    Local(usize),
+    Upval(usize),
+    Indexed {
+        t: usize,
+        t_is_upval: bool,
+        key: usize,
+    },
+}
+
+fn cg_assign_stat(
+    fi: &mut FuncInfo,
+    last_line: usize,
+    var_list: &[Exp],
+    exp_list: &[Exp],
+) -> Result<(), String> {
+    let old_regs = fi.used_regs();
+
+    // locals assigned by this statement; tables and keys taken from them
+    // must be copied before any store happens
+    let assigned: Vec<usize> = var_list
+        .iter()
+        .filter_map(|var| match var {
+            Exp::Name { name, .. } => fi.slot_of_loc_var(name),
+            _ => None,
+        })
+        .collect();
+
+    let mut targets = Vec::with_capacity(var_list.len());
+    for var in var_list {
+        let target = match var {
+            Exp::Name { line, name } => {
+                if let Some(r) = fi.slot_of_loc_var(name) {
+                    Target::Local(r)
+                } else if let Some(idx) = fi.index_of_upval(name)? {
+                    Target::Upval(idx)
+                } else {
+                    let env = Exp::Name {
+                        line: *line,
+                        name: "_ENV".to_string(),
+                    };
+                    let key = Exp::String {
+                        line: *line,
+                        val: name.as_bytes().to_vec(),
+                    };
+                    cg_indexed_target(fi, &env, &key, &assigned)?
+                }
+            }
+            Exp::TableAccess {
+                prefix_exp,
+                key_exp,
+                ..
+            } => cg_indexed_target(fi, prefix_exp, key_exp, &assigned)?,
+            _ => unreachable!(),
+        };
+        targets.push(target);
+    }
+
+    // a single value stored into a table can be a constant
+    if let ([Target::Indexed { t, t_is_upval, key }], [exp]) = (&targets[..], exp_list) {
+        let (v, _) = exp_to_op_arg(fi, exp, ARG_RK)?;
+        if *t_is_upval {
+            fi.emit_set_tabup(last_line, *t, *key, v);
+        } else {
+            fi.emit_set_table(last_line, *t, *key, v);
+        }
+        fi.set_used_regs(old_regs);
+        return Ok(());
+    }
+
+    let first = fi.used_regs();
+    cg_adjusted_exps(fi, last_line, exp_list, var_list.len())?;
+
+    for (i, target) in targets.iter().enumerate().rev() {
+        let v = first + i;
+        match *target {
+            Target::Local(r) => fi.emit_move(last_line, r, v),
+            Target::Upval(idx) => fi.emit_set_upval(last_line, v, idx),
+            Target::Indexed {
+                t,
+                t_is_upval: true,
+                key,
+            } => fi.emit_set_tabup(last_line, t, key, v),
+            Target::Indexed {
+                t,
+                t_is_upval: false,
+                key,
+            } => fi.emit_set_table(last_line, t, key, v),
+        }
+    }
+
+    fi.set_used_regs(old_regs);
+    Ok(())
+}
+
+fn cg_indexed_target(
+    fi: &mut FuncInfo,
+    prefix_exp: &Exp,
+    key_exp: &Exp,
+    assigned: &[usize],
+) -> Result<Target, String> {
+    let (t, t_kind) = if is_assigned_local(fi, prefix_exp, assigned) {
+        let r = fi.alloc_reg()?;
+        cg_exp(fi, prefix_exp, r, 1)?;
+        (r, ARG_REG)
+    } else {
+        exp_to_op_arg(fi, prefix_exp, ARG_RU)?
+    };
+
+    let key = if is_assigned_local(fi, key_exp, assigned) {
+        let r = fi.alloc_reg()?;
+        cg_exp(fi, key_exp, r, 1)?;
+        r
+    } else {
+        exp_to_op_arg(fi, key_exp, ARG_RK)?.0
+    };
+
+    Ok(Target::Indexed {
+        t,
+        t_is_upval: t_kind == ARG_UPVAL,
+        key,
+    })
+}
+
+fn is_assigned_local(fi: &FuncInfo, exp: &Exp, assigned: &[usize]) -> bool {
+    match exp {
+        Exp::Name { name, .. } => fi
+            .slot_of_loc_var(name)
+            .is_some_and(|r| assigned.contains(&r)),
+        _ => false,
+    }
+}
+
+fn cg_local_func_def_stat(fi: &mut FuncInfo, name: &str, exp: &FuncDefExp) -> Result<(), String> {
+    // the function can refer to itself, so its local is in scope from the start
+    let r = fi.add_loc_var(name, exp.line, fi.pc() + 1)?;
+    cg_func_def_exp(fi, exp, r)
+}
@@ -0,0 +1,737 @@
+use std::{collections::HashMap, rc::Rc};
+
+use crate::{
+    binary::chunk::{ConstantType, LocVar, Prototype, Upvalue},
+    compiler::{
+        ast::FuncDefExp,
+        lexer::{chunk_id, token::*},
+    },
+    vm::{instruction::*, opcode::*},
+};
+
+const MAXREGS: usize = 255; // maximum number of registers in a Lua function
+const MAXVARS: usize = 200; // maximum number of local variables per function
+const MAXUPVAL: usize = 255; // maximum number of upvalues per function
+
+// floats are keyed by their bits so that constants can be hashed
+#[derive(PartialEq, Eq, Hash)]
+enum ConstantKey {
+    Nil,
+    Boolean(bool),
+    Integer(i64),
+    Number(u64),
+    String(Vec<u8>),
+}
+
+impl ConstantKey {
+    fn of(k: &ConstantType) -> Self {
+        match k {
+            ConstantType::Nil => ConstantKey::Nil,
+            ConstantType::Boolean(b) => ConstantKey::Boolean(*b),
+            ConstantType::Integer(i) => ConstantKey::Integer(*i),
+            ConstantType::Number(n) => ConstantKey::Number(n.to_bits()),
+            ConstantType::String(s) => ConstantKey::String(s.clone()),
+        }
+    }
+}
+
+#[derive(Default)]
+struct LocVarInfo {
+    name: String,
+    start_pc: usize,
+    end_pc: usize,
+    captured: bool,
+}
+
+#[derive(Default)]
+struct UpvalInfo {
+    name: String,
+    instack: bool,
+    idx: usize,
+}
+
+// a pending goto or an active label
+#[derive(Default)]
+struct LabelDesc {
+    name: String,
+    pc: usize,
+    line: usize,
+    n_active: usize, // number of active locals at that position
+}
+
+#[derive(Default)]
+struct BlockInfo {
+    n_active: usize,    // number of active locals outside the block
+    first_label: usize, // index of first label in this block
+    first_goto: usize,  // index of first pending goto in this block
+    is_loop: bool,
+}
+
+#[derive(Default)]
+pub struct FuncInfo {
+    pub parent: Option<Box<FuncInfo>>,
+    pub sub_protos: Vec<Rc<Prototype>>,
+    source: String,
+    used_regs: usize,
+    max_regs: usize,
+    active_vars: Vec<usize>, // indices into loc_vars, one per register in use by a local
+    loc_vars: Vec<LocVarInfo>,
+    blocks: Vec<BlockInfo>,
+    labels: Vec<LabelDesc>,
+    gotos: Vec<LabelDesc>,
+    upvalues: Vec<UpvalInfo>,
+    constant_idx: HashMap<ConstantKey, usize>,
+    constants: Vec<ConstantType>,
+    insts: Vec<u32>,
+    line_nums: Vec<u32>,
+    line: usize,
+    last_line: usize,
+    num_params: usize,
+    pub is_vararg: bool,
+}
+
+impl FuncInfo {
+    pub fn new(parent: Option<Box<FuncInfo>>, fd: &FuncDefExp, source: &str) -> Self {
+        FuncInfo {
+            parent,
+            source: source.to_string(),
+            line: fd.line,
+            last_line: fd.last_line,
+            num_params: fd.par_list.len(),
+            is_vararg: fd.is_vararg,
+            ..Default::default()
+        }
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /* errors */
+
+    pub fn error<T>(&self, line: usize, msg: &str) -> Result<T, String> {
+        Err(format!("{}:{}: {}", chunk_id(&self.source), line, msg))
+    }
+
+    /* constants */
+
+    pub fn index_of_constant(&mut self, k: ConstantType) -> usize {
+        let key = ConstantKey::of(&k);
+        if let Some(idx) = self.constant_idx.get(&key) {
+            return *idx;
+        }
+
+        let idx = self.constants.len();
+        self.constants.push(k);
+        self.constant_idx.insert(key, idx);
+        idx
+    }
+
+    /* registers */
+
+    pub fn used_regs(&self) -> usize {
+        self.used_regs
+    }
+
+    pub fn alloc_reg(&mut self) -> Result<usize, String> {
+        self.alloc_regs(1)
+    }
+
+    // allocates n consecutive registers and returns the first one
+    pub fn alloc_regs(&mut self, n: usize) -> Result<usize, String> {
+        let first = self.used_regs;
+        self.check_stack(n)?;
+        self.used_regs += n;
+        Ok(first)
+    }
+
+    pub fn free_reg(&mut self) {
+        self.free_regs(1)
+    }
+
+    pub fn free_regs(&mut self, n: usize) {
+        debug_assert!(self.used_regs >= n + self.active_vars.len());
+        self.used_regs -= n;
+    }
+
+    pub fn set_used_regs(&mut self, n: usize) {
+        self.used_regs = n;
+    }
+
+    // makes sure that n more registers are available
+    pub fn check_stack(&mut self, n: usize) -> Result<(), String> {
+        let new_stack = self.used_regs + n;
+        if new_stack > MAXREGS {
+            return self.error(
+                self.last_line,
+                "function or expression needs too many registers",
+            );
+        }
+        if new_stack > self.max_regs {
+            self.max_regs = new_stack;
+        }
+        Ok(())
+    }
+
+    /* lexical scope */
+
+    pub fn enter_scope(&mut self, is_loop: bool) {
+        self.blocks.push(BlockInfo {
+            n_active: self.active_vars.len(),
+            first_label: self.labels.len(),
+            first_goto: self.gotos.len(),
+            is_loop,
+        });
+    }
+
+    pub fn exit_scope(&mut self, line: usize) -> Result<(), String> {
+        let bl = self.blocks.last().unwrap();
+        let (n_active, is_loop) = (bl.n_active, bl.is_loop);
+        let has_upval = self.block_has_upval(n_active);
+        let is_inner = self.blocks.len() > 1;
+
+        if is_inner && has_upval {
+            // close the upvalues of the block's locals when falling off its end
+            self.emit_jmp(line, n_active + 1, 0);
+        }
+        if is_loop {
+            // a loop block has an implicit label for `break`
+            self.create_label("break", 0, n_active, self.pc())?;
+        }
+
+        let end_pc = self.pc();
+        for idx in self.active_vars.drain(n_active..) {
+            self.loc_vars[idx].end_pc = end_pc;
+        }
+        self.used_regs = n_active;
+
+        let bl = self.blocks.pop().unwrap();
+        self.labels.truncate(bl.first_label);
+        if is_inner {
+            self.move_gotos_out(&bl, has_upval)
+        } else if let Some(gt) = self.gotos.get(bl.first_goto) {
+            let msg = if gt.name == "break" {
+                format!("<break> at line {} not inside a loop", gt.line)
+            } else {
+                format!(
+                    "no visible label '{}' for <goto> at line {}",
+                    gt.name, gt.line
+                )
+            };
+            self.error(gt.line, &msg)
+        } else {
+            Ok(())
+        }
+    }
+
+    // makes the jump at pc close the captured locals of the current block
+    pub fn close_block_upvals_on(&mut self, pc: usize) {
+        let n_active = self.blocks.last().unwrap().n_active;
+        if self.block_has_upval(n_active) {
+            self.patch_close(pc, n_active);
+        }
+    }
+
+    fn block_has_upval(&self, n_active: usize) -> bool {
+        self.active_vars[n_active..]
+            .iter()
+            .any(|&idx| self.loc_vars[idx].captured)
+    }
+
+    /* local variables */
+
+    // fails if n more locals would go over the limit, reporting the line
+    // they are declared on
+    pub fn check_loc_vars(&self, line: usize, n: usize) -> Result<(), String> {
+        if self.active_vars.len() + n > MAXVARS {
+            let msg = format!(
+                "too many local variables (limit is {}) in {}",
+                MAXVARS,
+                self.func_what()
+            );
+            return self.error(line, &msg);
+        }
+        Ok(())
+    }
+
+    pub fn add_loc_var(&mut self, name: &str, line: usize, start_pc: usize) -> Result<usize, String> {
+        self.check_loc_vars(line, 1)?;
+        let slot = self.alloc_reg()?;
+        self.loc_vars.push(LocVarInfo {
+            name: name.to_string(),
+            start_pc,
+            ..Default::default()
+        });
+        self.active_vars.push(self.loc_vars.len() - 1);
+        debug_assert_eq!(slot + 1, self.active_vars.len());
+        Ok(slot)
+    }
+
+    pub fn slot_of_loc_var(&self, name: &str) -> Option<usize> {
+        self.active_vars
+            .iter()
+            .rposition(|&idx| self.loc_vars[idx].name == name)
+    }
+
+    /* upvalues */
+
+    pub fn index_of_upval(&mut self, name: &str) -> Result<Option<usize>, String> {
+        if let Some(idx) = self.upvalues.iter().position(|uv| uv.name == name) {
+            return Ok(Some(idx));
+        }
+
+        let parent = match self.parent.as_mut() {
+            Some(parent) => parent,
+            None => return Ok(None),
+        };
+        let (instack, idx) = if let Some(slot) = parent.slot_of_loc_var(name) {
+            let var = parent.active_vars[slot];
+            parent.loc_vars[var].captured = true;
+            (true, slot)
+        } else if let Some(idx) = parent.index_of_upval(name)? {
+            (false, idx)
+        } else {
+            return Ok(None);
+        };
+
+        if self.upvalues.len() >= MAXUPVAL {
+            let msg = format!(
+                "too many upvalues (limit is {}) in {}",
+                MAXUPVAL,
+                self.func_what()
+            );
+            return self.error(self.last_line, &msg);
+        }
+        self.upvalues.push(UpvalInfo {
+            name: name.to_string(),
+            instack,
+            idx,
+        });
+        Ok(Some(self.upvalues.len() - 1))
+    }
+
+    fn func_what(&self) -> String {
+        if self.line == 0 {
+            "main function".to_string()
+        } else {
+            format!("function at line {}", self.line)
+        }
+    }
+
+    /* labels and gotos */
+
+    pub fn add_label(&mut self, name: &str, line: usize, is_last: bool) -> Result<(), String> {
+        let first_label = self.blocks.last().unwrap().first_label;
+        if let Some(l) = self.labels[first_label..].iter().find(|l| l.name == name) {
+            let msg = format!("label '{}' already defined on line {}", name, l.line);
+            return self.error(line, &msg);
+        }
+
+        // a label at the end of a block is considered outside the scope of its locals
+        let n_active = if is_last {
+            self.blocks.last().unwrap().n_active
+        } else {
+            self.active_vars.len()
+        };
+        self.create_label(name, line, n_active, self.pc())
+    }
+
+    pub fn add_goto(&mut self, name: &str, line: usize, pc: usize) -> Result<(), String> {
+        self.gotos.push(LabelDesc {
+            name: name.to_string(),
+            pc,
+            line,
+            n_active: self.active_vars.len(),
+        });
+        self.find_label(self.gotos.len() - 1)?;
+        Ok(())
+    }
+
+    fn create_label(
+        &mut self,
+        name: &str,
+        line: usize,
+        n_active: usize,
+        pc: usize,
+    ) -> Result<(), String> {
+        self.labels.push(LabelDesc {
+            name: name.to_string(),
+            pc,
+            line,
+            n_active,
+        });
+
+        // solve pending gotos of the current block
+        let label = self.labels.len() - 1;
+        let mut i = self.blocks.last().unwrap().first_goto;
+        while i < self.gotos.len() {
+            if self.gotos[i].name == name {
+                self.close_goto(i, label)?;
+            } else {
+                i += 1;
+            }
+        }
+        Ok(())
+    }
+
+    // tries to solve a pending goto with the labels visible in the current block
+    fn find_label(&mut self, g: usize) -> Result<bool, String> {
+        let first_label = self.blocks.last().unwrap().first_label;
+        let found = self.labels[first_label..]
+            .iter()
+            .position(|l| l.name == self.gotos[g].name);
+        match found {
+            Some(i) => {
+                let label = first_label + i;
+                if self.gotos[g].n_active > self.labels[label].n_active {
+                    self.patch_close(self.gotos[g].pc, self.labels[label].n_active);
+                }
+                self.close_goto(g, label)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn close_goto(&mut self, g: usize, label: usize) -> Result<(), String> {
+        let gt = &self.gotos[g];
+        let lb = &self.labels[label];
+        if gt.n_active < lb.n_active {
+            let var = &self.loc_vars[self.active_vars[gt.n_active]].name;
+            let msg = format!(
+                "<goto {}> at line {} jumps into the scope of local '{}'",
+                gt.name, gt.line, var
+            );
+            return self.error(gt.line, &msg);
+        }
+
+        let (pc, target) = (gt.pc, lb.pc);
+        self.patch_to(pc, target);
+        self.gotos.remove(g);
+        Ok(())
+    }
+
+    // moves the pending gotos of a closing block to the enclosing one
+    fn move_gotos_out(&mut self, bl: &BlockInfo, has_upval: bool) -> Result<(), String> {
+        let mut i = bl.first_goto;
+        while i < self.gotos.len() {
+            if self.gotos[i].n_active > bl.n_active {
+                if has_upval {
+                    self.patch_close(self.gotos[i].pc, bl.n_active);
+                }
+                self.gotos[i].n_active = bl.n_active;
+            }
+            if !self.find_label(i)? {
+                i += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /* code */
+
+    // index of the next instruction to be emitted
+    pub fn pc(&self) -> usize {
+        self.insts.len()
+    }
+
+    // makes the jump at pc go to target
+    pub fn patch_to(&mut self, pc: usize, target: usize) {
+        let sbx = target as isize - (pc as isize + 1);
+        let (a, _) = self.insts[pc].a_sbx();
+        self.insts[pc] =
+            (((sbx + MAXARG_S_BX) as u32) << 14) | ((a as u32) << 6) | (self.insts[pc] & 0x3f);
+    }
+
+    pub fn patch_to_here(&mut self, pc: usize) {
+        self.patch_to(pc, self.pc());
+    }
+
+    // makes the jump at pc close the upvalues from register `level` up
+    fn patch_close(&mut self, pc: usize, level: usize) {
+        let i = self.insts[pc];
+        self.insts[pc] = (i & !(0xff << 6)) | (((level + 1) as u32) << 6);
+    }
+
+    fn emit(&mut self, line: usize, i: u32) -> usize {
+        self.insts.push(i);
+        self.line_nums.push(line as u32);
+        self.insts.len() - 1
+    }
+
+    fn emit_abc(&mut self, line: usize, opcode: u8, a: usize, b: usize, c: usize) -> usize {
+        let i = (b as u32) << 23 | (c as u32) << 14 | (a as u32) << 6 | opcode as u32;
+        self.emit(line, i)
+    }
+
+    fn emit_abx(&mut self, line: usize, opcode: u8, a: usize, bx: usize) -> usize {
+        let i = (bx as u32) << 14 | (a as u32) << 6 | opcode as u32;
+        self.emit(line, i)
+    }
+
+    fn emit_asbx(&mut self, line: usize, opcode: u8, a: usize, sbx: isize) -> usize {
+        let i = ((sbx + MAXARG_S_BX) as u32) << 14 | (a as u32) << 6 | opcode as u32;
+        self.emit(line, i)
+    }
+
+    fn emit_ax(&mut self, line: usize, opcode: u8, ax: usize) -> usize {
+        let i = (ax as u32) << 6 | opcode as u32;
+        self.emit(line, i)
+    }
+
+    // r[a] = r[b]
+    pub fn emit_move(&mut self, line: usize, a: usize, b: usize) {
+        self.emit_abc(line, OP_MOVE, a, b, 0);
+    }
+
+    // r[a], r[a+1], ..., r[a+n-1] = nil
+    pub fn emit_load_nil(&mut self, line: usize, a: usize, n: usize) {
+        self.emit_abc(line, OP_LOADNIL, a, n - 1, 0);
+    }
+
+    // r[a] = (bool)b; if c then pc++
+    pub fn emit_load_bool(&mut self, line: usize, a: usize, b: usize, c: usize) {
+        self.emit_abc(line, OP_LOADBOOL, a, b, c);
+    }
+
+    // r[a] = kst[bx]
+    pub fn emit_load_k(&mut self, line: usize, a: usize, k: ConstantType) -> Result<(), String> {
+        let idx = self.index_of_constant(k);
+        if idx <= MAXARG_BX as usize {
+            self.emit_abx(line, OP_LOADK, a, idx);
+        } else if idx <= MAXARG_AX as usize {
+            self.emit_abx(line, OP_LOADKX, a, 0);
+            self.emit_ax(line, OP_EXTRAARG, idx);
+        } else {
+            return self.error(line, "constant table overflow");
+        }
+        Ok(())
+    }
+
+    // rk operand of a constant, None if it does not fit into B or C
+    pub fn rk_of_constant(&mut self, k: ConstantType) -> Option<usize> {
+        let idx = self.index_of_constant(k);
+        if idx <= 0xff {
+            Some(0x100 + idx)
+        } else {
+            None
+        }
+    }
+
+    // r[a], r[a+1], ..., r[a+n-2] = vararg
+    pub fn emit_vararg(&mut self, line: usize, a: usize, n: isize) {
+        self.emit_abc(line, OP_VARARG, a, (n + 1) as usize, 0);
+    }
+
+    // r[a] = closure(proto[bx])
+    pub fn emit_closure(&mut self, line: usize, a: usize, bx: usize) {
+        self.emit_abx(line, OP_CLOSURE, a, bx);
+    }
+
+    // r[a] = {}
+    pub fn emit_new_table(&mut self, line: usize, a: usize, n_arr: usize, n_rec: usize) {
+        use crate::vm::fpd::int2fb;
+        self.emit_abc(line, OP_NEWTABLE, a, int2fb(n_arr), int2fb(n_rec));
+    }
+
+    // r[a][(c-1)*FPF+i] = r[a+i], 1 <= i <= b
+    pub fn emit_set_list(
+        &mut self,
+        line: usize,
+        a: usize,
+        b: usize,
+        c: usize,
+    ) -> Result<(), String> {
+        if c <= MAXARG_C as usize {
+            self.emit_abc(line, OP_SETLIST, a, b, c);
+        } else if c <= MAXARG_AX as usize {
+            self.emit_abc(line, OP_SETLIST, a, b, 0);
+            self.emit_ax(line, OP_EXTRAARG, c);
+        } else {
+            return self.error(line, "constructor too long");
+        }
+        Ok(())
+    }
+
+    // r[a] = r[b][rk(c)]
+    pub fn emit_get_table(&mut self, line: usize, a: usize, b: usize, c: usize) {
+        self.emit_abc(line, OP_GETTABLE, a, b, c);
+    }
+
+    // r[a][rk(b)] = rk(c)
+    pub fn emit_set_table(&mut self, line: usize, a: usize, b: usize, c: usize) {
+        self.emit_abc(line, OP_SETTABLE, a, b, c);
+    }
+
+    // r[a] = upval[b]
+    pub fn emit_get_upval(&mut self, line: usize, a: usize, b: usize) {
+        self.emit_abc(line, OP_GETUPVAL, a, b, 0);
+    }
+
+    // upval[b] = r[a]
+    pub fn emit_set_upval(&mut self, line: usize, a: usize, b: usize) {
+        self.emit_abc(line, OP_SETUPVAL, a, b, 0);
+    }
+
+    // r[a] = upval[b][rk(c)]
+    pub fn emit_get_tabup(&mut self, line: usize, a: usize, b: usize, c: usize) {
+        self.emit_abc(line, OP_GETTABUP, a, b, c);
+    }
+
+    // upval[a][rk(b)] = rk(c)
+    pub fn emit_set_tabup(&mut self, line: usize, a: usize, b: usize, c: usize) {
+        self.emit_abc(line, OP_SETTABUP, a, b, c);
+    }
+
+    // r[a], ..., r[a+c-2] = r[a](r[a+1], ..., r[a+b-1])
+    pub fn emit_call(&mut self, line: usize, a: usize, n_args: isize, n_ret: isize) {
+        self.emit_abc(
+            line,
+            OP_CALL,
+            a,
+            (n_args + 1) as usize,
+            (n_ret + 1) as usize,
+        );
+    }
+
+    // return r[a](r[a+1], ..., r[a+b-1])
+    pub fn emit_tail_call(&mut self, line: usize, a: usize, n_args: isize) {
+        self.emit_abc(line, OP_TAILCALL, a, (n_args + 1) as usize, 0);
+    }
+
+    // return r[a], ..., r[a+b-2]
+    pub fn emit_return(&mut self, line: usize, a: usize, n: isize) {
+        self.emit_abc(line, OP_RETURN, a, (n + 1) as usize, 0);
+    }
+
+    // r[a+1] = r[b]; r[a] = r[b][rk(c)]
+    pub fn emit_self(&mut self, line: usize, a: usize, b: usize, c: usize) {
+        self.emit_abc(line, OP_SELF, a, b, c);
+    }
+
+    // pc += sbx; if a then close all upvalues >= r[a-1]
+    pub fn emit_jmp(&mut self, line: usize, a: usize, sbx: isize) -> usize {
+        self.emit_asbx(line, OP_JMP, a, sbx)
+    }
+
+    // if not (r[a] <=> c) then pc++
+    pub fn emit_test(&mut self, line: usize, a: usize, c: usize) {
+        self.emit_abc(line, OP_TEST, a, 0, c);
+    }
+
+    // if (r[b] <=> c) then r[a] = r[b] else pc++
+    pub fn emit_test_set(&mut self, line: usize, a: usize, b: usize, c: usize) {
+        self.emit_abc(line, OP_TESTSET, a, b, c);
+    }
+
+    pub fn emit_for_prep(&mut self, line: usize, a: usize) -> usize {
+        self.emit_asbx(line, OP_FORPREP, a, 0)
+    }
+
+    pub fn emit_for_loop(&mut self, line: usize, a: usize) -> usize {
+        self.emit_asbx(line, OP_FORLOOP, a, 0)
+    }
+
+    pub fn emit_tfor_call(&mut self, line: usize, a: usize, c: usize) {
+        self.emit_abc(line, OP_TFORCALL, a, 0, c);
+    }
+
+    pub fn emit_tfor_loop(&mut self, line: usize, a: usize) -> usize {
+        self.emit_asbx(line, OP_TFORLOOP, a, 0)
+    }
+
+    // r[a] = op r[b]
+    pub fn emit_unary_op(&mut self, line: usize, op: u8, a: usize, b: usize) {
+        let opcode = match op {
+            TOKEN_OP_NOT => OP_NOT,
+            TOKEN_OP_BNOT => OP_BNOT,
+            TOKEN_OP_LEN => OP_LEN,
+            TOKEN_OP_UNM => OP_UNM,
+            _ => unreachable!(),
+        };
+        self.emit_abc(line, opcode, a, b, 0);
+    }
+
+    // r[a] = rk(b) op rk(c)
+    pub fn emit_arith_op(&mut self, line: usize, op: u8, a: usize, b: usize, c: usize) {
+        let opcode = match op {
+            TOKEN_OP_ADD => OP_ADD,
+            TOKEN_OP_SUB => OP_SUB,
+            TOKEN_OP_MUL => OP_MUL,
+            TOKEN_OP_MOD => OP_MOD,
+            TOKEN_OP_POW => OP_POW,
+            TOKEN_OP_DIV => OP_DIV,
+            TOKEN_OP_IDIV => OP_IDIV,
+            TOKEN_OP_BAND => OP_BAND,
+            TOKEN_OP_BOR => OP_BOR,
+            TOKEN_OP_BXOR => OP_BXOR,
+            TOKEN_OP_SHL => OP_SHL,
+            TOKEN_OP_SHR => OP_SHR,
+            _ => unreachable!(),
+        };
+        self.emit_abc(line, opcode, a, b, c);
+    }
+
+    // if ((rk(b) op rk(c)) ~= cond) then pc++
+    pub fn emit_compare(&mut self, line: usize, op: u8, cond: bool, b: usize, c: usize) {
+        let cond = cond as usize;
+        match op {
+            TOKEN_OP_EQ => self.emit_abc(line, OP_EQ, cond, b, c),
+            TOKEN_OP_NE => self.emit_abc(line, OP_EQ, 1 - cond, b, c),
+            TOKEN_OP_LT => self.emit_abc(line, OP_LT, cond, b, c),
+            TOKEN_OP_GT => self.emit_abc(line, OP_LT, cond, c, b),
+            TOKEN_OP_LE => self.emit_abc(line, OP_LE, cond, b, c),
+            TOKEN_OP_GE => self.emit_abc(line, OP_LE, cond, c, b),
+            _ => unreachable!(),
+        };
+    }
+
+    // r[a] = r[b] .. ... .. r[c]
+    pub fn emit_concat(&mut self, line: usize, a: usize, b: usize, c: usize) {
+        self.emit_abc(line, OP_CONCAT, a, b, c);
+    }
+
+    /* prototype */
+
+    pub fn into_proto(self) -> Rc<Prototype> {
+        let loc_vars = self
+            .loc_vars
+            .into_iter()
+            .map(|v| LocVar {
+                var_name: v.name,
+                start_pc: v.start_pc as u32,
+                end_pc: v.end_pc as u32,
+            })
+            .collect();
+        let (upvalues, upvalue_names) = self
+            .upvalues
+            .into_iter()
+            .map(|uv| {
+                let info = Upvalue {
+                    instack: uv.instack as u8,
+                    idx: uv.idx as u8,
+                };
+                (info, uv.name)
+            })
+            .unzip();
+
+        // the main function has no definition lines
+        let last_line = if self.line == 0 { 0 } else { self.last_line };
+        Rc::new(
+            Prototype::new()
+                .set_source(self.source)
+                .set_line_defined(self.line as u32)
+                .set_last_line_defined(last_line as u32)
+                .set_num_params(self.num_params as u8)
+                .set_is_vararg(self.is_vararg as u8)
+                .set_max_stack_size(self.max_regs.max(2) as u8)
+                .set_code(self.insts)
+                .set_constants(self.constants)
+                .set_upvalues(upvalues)
+                .set_protos(self.sub_protos)
+                .set_line_info(self.line_nums)
+                .set_locvars(loc_vars)
+                .set_upvalue_names(upvalue_names),
+        )
+    }
+}
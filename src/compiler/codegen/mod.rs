@@ -0,0 +1,31 @@
+mod cg_block;
+mod cg_exp;
+mod cg_stat;
+mod func_info;
+
+use std::rc::Rc;
+
+use crate::binary::chunk::Prototype;
+
+use self::{cg_exp::cg_func_def_exp, func_info::FuncInfo};
+
+use super::ast::{Block, FuncDefExp};
+
+pub fn gen_proto(chunk: Block, source: &str) -> Result<Rc<Prototype>, String> {
+    let last_line = chunk.last_line;
+    let fd = FuncDefExp {
+        line: 0,
+        last_line,
+        par_list: vec![],
+        is_vararg: true,
+        block: Box::new(chunk),
+    };
+
+    // the main function gets _ENV as its only upvalue, taken from a
+    // pseudo enclosing function where it is the first local
+    let mut fi = FuncInfo::new(None, &fd, source);
+    fi.enter_scope(false);
+    fi.add_loc_var("_ENV", 0, 0)?;
+    cg_func_def_exp(&mut fi, &fd, 1)?;
+    Ok(fi.sub_protos.pop().unwrap())
+}
@@ -0,0 +1,627 @@
+pub mod token;
+
+use crate::api::basic::LUAI_MAXCCALLS;
+
+use self::token::*;
+
+const LUA_IDSIZE: usize = 60;
+
+// printable name of a chunk for messages, as luaO_chunkid does it:
+// "=name" -> name, "@file" -> file, anything else -> [string "..."]
+pub fn chunk_id(source: &str) -> String {
+    if let Some(name) = source.strip_prefix('=') {
+        name.chars().take(LUA_IDSIZE - 1).collect()
+    } else if let Some(name) = source.strip_prefix('@') {
+        let n = name.chars().count();
+        if n < LUA_IDSIZE {
+            name.to_string()
+        } else {
+            let tail: String = name.chars().skip(n - (LUA_IDSIZE - 4)).collect();
+            format!("...{tail}")
+        }
+    } else {
+        const MAX: usize = LUA_IDSIZE - 15;
+        let first_line = source.split(['\n', '\r']).next().unwrap_or_default();
+        if first_line.len() == source.len() && source.chars().count() < MAX {
+            format!("[string \"{source}\"]")
+        } else {
+            let line: String = first_line.chars().take(MAX).collect();
+            format!("[string \"{line}...\"]")
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Token {
+    line: usize,
+    kind: u8,
+    value: Vec<u8>, // the bytes of a string literal, the source text otherwise
+    start: usize,
+    end: usize,
+}
+
+#[derive(Debug)]
+pub struct Lexer<'a> {
+    chunk: &'a [u8],    // source code
+    chunk_name: String, // source name
+    line: usize,        // current line
+    last_line: usize,   // line of the last token consumed
+    pos: usize,         // current position in chunk
+    level: usize,       // nesting of the syntactical structures being parsed
+    ahead: Option<Token>,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(chunk: &'a [u8], chunk_name: &str) -> Self {
+        let mut this = Self {
+            chunk,
+            chunk_name: chunk_id(chunk_name),
+            line: 1,
+            last_line: 1,
+            pos: 0,
+            level: 0,
+            ahead: None,
+        };
+        this.skip_shebang();
+        this
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn last_line(&self) -> usize {
+        self.last_line
+    }
+
+    pub fn look_ahead(&mut self) -> Result<u8, String> {
+        if self.ahead.is_none() {
+            let token = self.scan()?;
+            self.ahead = Some(token);
+        }
+        Ok(self.ahead.as_ref().unwrap().kind)
+    }
+
+    pub fn next_token(&mut self) -> Result<(usize, u8, String), String> {
+        let token = match self.ahead.take() {
+            Some(token) => token,
+            None => self.scan()?,
+        };
+        self.last_line = token.line;
+        Ok((token.line, token.kind, String::from_utf8_lossy(&token.value).into_owned()))
+    }
+
+    // the next token, which must be a string literal, as the bytes it stands for
+    pub fn next_string(&mut self) -> Result<(usize, Vec<u8>), String> {
+        if self.look_ahead()? != TOKEN_STRING {
+            return self.error_near(&format!("{} expected", token_str(TOKEN_STRING)));
+        }
+        let token = self.ahead.take().unwrap();
+        self.last_line = token.line;
+        Ok((token.line, token.value))
+    }
+
+    pub fn next_identifier(&mut self) -> Result<(usize, String), String> {
+        self.next_token_of_kind(TOKEN_IDENTIFIER)
+    }
+
+    pub fn next_token_of_kind(&mut self, kind: u8) -> Result<(usize, String), String> {
+        if self.look_ahead()? != kind {
+            return self.error_near(&format!("{} expected", token_str(kind)));
+        }
+        let (line, _, token) = self.next_token()?;
+        Ok((line, token))
+    }
+
+    // like next_token_of_kind, but mentions the opening token when it is on another line
+    pub fn check_match(&mut self, what: u8, who: u8, line: usize) -> Result<usize, String> {
+        if self.look_ahead()? == what {
+            return Ok(self.next_token()?.0);
+        }
+        if line == self.line {
+            self.error_near(&format!("{} expected", token_str(what)))
+        } else {
+            self.error_near(&format!(
+                "{} expected (to close {} at line {})",
+                token_str(what),
+                token_str(who),
+                line
+            ))
+        }
+    }
+
+    // the parser enters a level for each nested statement or expression
+    pub fn enter_level(&mut self) -> Result<(), String> {
+        self.level += 1;
+        if self.level > LUAI_MAXCCALLS {
+            return self.error("chunk has too many syntax levels");
+        }
+        Ok(())
+    }
+
+    pub fn leave_level(&mut self) {
+        self.level -= 1;
+    }
+
+    pub fn error<T>(&self, msg: &str) -> Result<T, String> {
+        Err(format!("{}:{}: {}", self.chunk_name, self.line, msg))
+    }
+
+    // error that points at the next token, e.g. "unexpected symbol near 'x'"
+    pub fn error_near<T>(&mut self, msg: &str) -> Result<T, String> {
+        self.look_ahead()?;
+        let token = self.ahead.as_ref().unwrap();
+        let near = match token.kind {
+            TOKEN_EOF => "<eof>".to_string(),
+            _ => format!("'{}'", self.raw(token.start, token.end)),
+        };
+        self.error(&format!("{msg} near {near}"))
+    }
+
+    fn raw(&self, start: usize, end: usize) -> String {
+        String::from_utf8_lossy(&self.chunk[start..end]).into_owned()
+    }
+
+    fn skip_shebang(&mut self) {
+        if self.chunk.starts_with(b"#") {
+            while let Some(c) = self.current() {
+                if c == b'\n' || c == b'\r' {
+                    break;
+                }
+                self.pos += 1;
+            }
+        }
+    }
+
+    fn current(&self) -> Option<u8> {
+        self.chunk.get(self.pos).copied()
+    }
+
+    fn peek(&self, n: usize) -> Option<u8> {
+        self.chunk.get(self.pos + n).copied()
+    }
+
+    fn test(&self, s: &[u8]) -> bool {
+        self.chunk[self.pos..].starts_with(s)
+    }
+
+    fn is_newline(c: u8) -> bool {
+        c == b'\n' || c == b'\r'
+    }
+
+    // skips '\n', '\r', '\n\r' or '\r\n' and counts a single line
+    fn skip_newline(&mut self) {
+        let c = self.chunk[self.pos];
+        self.pos += 1;
+        if let Some(next) = self.current() {
+            if Self::is_newline(next) && next != c {
+                self.pos += 1;
+            }
+        }
+        self.line += 1;
+    }
+
+    fn skip_whitespaces(&mut self) -> Result<(), String> {
+        while let Some(c) = self.current() {
+            if self.test(b"--") {
+                self.skip_comment()?;
+            } else if Self::is_newline(c) {
+                self.skip_newline();
+            } else if matches!(c, b' ' | b'\t' | b'\x0b' | b'\x0c') {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn skip_comment(&mut self) -> Result<(), String> {
+        self.pos += 2; // --
+
+        // long comment ?
+        if self.current() == Some(b'[') {
+            if let Some(level) = self.long_bracket_level() {
+                self.read_long_string(level, "comment")?;
+                return Ok(());
+            }
+        }
+
+        // short comment
+        while let Some(c) = self.current() {
+            if Self::is_newline(c) {
+                break;
+            }
+            self.pos += 1;
+        }
+        Ok(())
+    }
+
+    // level of an opening long bracket '[==[' at the current position
+    fn long_bracket_level(&self) -> Option<usize> {
+        let mut n = 1;
+        while self.peek(n) == Some(b'=') {
+            n += 1;
+        }
+        if self.peek(n) == Some(b'[') {
+            Some(n - 1)
+        } else {
+            None
+        }
+    }
+
+    fn scan(&mut self) -> Result<Token, String> {
+        self.skip_whitespaces()?;
+
+        let start = self.pos;
+        let line = self.line;
+        let c = match self.current() {
+            Some(c) => c,
+            None => return Ok(self.token(TOKEN_EOF, start, String::from("<eof>"))),
+        };
+
+        let kind = match c {
+            b';' => TOKEN_SEP_SEMI,
+            b',' => TOKEN_SEP_COMMA,
+            b'(' => TOKEN_SEP_LPAREN,
+            b')' => TOKEN_SEP_RPAREN,
+            b']' => TOKEN_SEP_RBRACK,
+            b'{' => TOKEN_SEP_LCURLY,
+            b'}' => TOKEN_SEP_RCURLY,
+            b'+' => TOKEN_OP_ADD,
+            b'-' => TOKEN_OP_MINUS,
+            b'*' => TOKEN_OP_MUL,
+            b'^' => TOKEN_OP_POW,
+            b'%' => TOKEN_OP_MOD,
+            b'&' => TOKEN_OP_BAND,
+            b'|' => TOKEN_OP_BOR,
+            b'#' => TOKEN_OP_LEN,
+            b':' => self.either(b':', TOKEN_SEP_LABEL, TOKEN_SEP_COLON),
+            b'/' => self.either(b'/', TOKEN_OP_IDIV, TOKEN_OP_DIV),
+            b'~' => self.either(b'=', TOKEN_OP_NE, TOKEN_OP_WAVE),
+            b'=' => self.either(b'=', TOKEN_OP_EQ, TOKEN_OP_ASSIGN),
+            b'<' => match self.peek(1) {
+                Some(b'<') => self.longer(TOKEN_OP_SHL),
+                Some(b'=') => self.longer(TOKEN_OP_LE),
+                _ => TOKEN_OP_LT,
+            },
+            b'>' => match self.peek(1) {
+                Some(b'>') => self.longer(TOKEN_OP_SHR),
+                Some(b'=') => self.longer(TOKEN_OP_GE),
+                _ => TOKEN_OP_GT,
+            },
+            b'.' => {
+                if self.test(b"...") {
+                    self.pos += 2;
+                    TOKEN_VARARG
+                } else if self.test(b"..") {
+                    self.longer(TOKEN_OP_CONCAT)
+                } else if self.peek(1).is_some_and(|c| c.is_ascii_digit()) {
+                    return self.scan_number(start, line);
+                } else {
+                    TOKEN_SEP_DOT
+                }
+            }
+            b'[' => match self.long_bracket_level() {
+                Some(level) => {
+                    let s = self.read_long_string(level, "string")?;
+                    return Ok(Token {
+                        line,
+                        kind: TOKEN_STRING,
+                        value: s,
+                        start,
+                        end: self.pos,
+                    });
+                }
+                None => {
+                    if self.peek(1) == Some(b'=') {
+                        self.pos += 1;
+                        return self.error("invalid long string delimiter near '[='");
+                    }
+                    TOKEN_SEP_LBRACK
+                }
+            },
+            b'\'' | b'"' => {
+                let s = self.scan_short_string(c)?;
+                return Ok(Token {
+                    line,
+                    kind: TOKEN_STRING,
+                    value: s,
+                    start,
+                    end: self.pos,
+                });
+            }
+            _ if c.is_ascii_digit() => return self.scan_number(start, line),
+            _ if c == b'_' || c.is_ascii_alphabetic() => {
+                while self
+                    .current()
+                    .is_some_and(|c| c == b'_' || c.is_ascii_alphanumeric())
+                {
+                    self.pos += 1;
+                }
+                let name = self.raw(start, self.pos);
+                let kind = keyword(&name).unwrap_or(TOKEN_IDENTIFIER);
+                return Ok(self.token(kind, start, name));
+            }
+            _ => {
+                self.pos += 1;
+                let near = if c.is_ascii_graphic() {
+                    format!("'{}'", c as char)
+                } else {
+                    format!("'<\\{}>'", c)
+                };
+                return self.error(&format!("unexpected symbol near {near}"));
+            }
+        };
+
+        self.pos += 1;
+        let value = self.raw(start, self.pos);
+        Ok(self.token(kind, start, value))
+    }
+
+    fn token(&self, kind: u8, start: usize, value: String) -> Token {
+        Token {
+            line: self.line,
+            kind,
+            value: value.into_bytes(),
+            start,
+            end: self.pos,
+        }
+    }
+
+    // two-character token if the next char is 'c', one-character token otherwise
+    fn either(&mut self, c: u8, long: u8, short: u8) -> u8 {
+        if self.peek(1) == Some(c) {
+            self.longer(long)
+        } else {
+            short
+        }
+    }
+
+    fn longer(&mut self, kind: u8) -> u8 {
+        self.pos += 1;
+        kind
+    }
+
+    fn scan_number(&mut self, start: usize, line: usize) -> Result<Token, String> {
+        let mut exp = [b'E', b'e'];
+        if self.test(b"0x") || self.test(b"0X") {
+            exp = [b'P', b'p'];
+            self.pos += 2;
+        }
+        loop {
+            match self.current() {
+                Some(c) if exp.contains(&c) => {
+                    self.pos += 1;
+                    if let Some(b'+' | b'-') = self.current() {
+                        self.pos += 1;
+                    }
+                }
+                Some(c) if c.is_ascii_hexdigit() || c == b'.' => self.pos += 1,
+                _ => break,
+            }
+        }
+        // a numeral touching a letter is malformed, like '3x'
+        while self
+            .current()
+            .is_some_and(|c| c == b'_' || c.is_ascii_alphanumeric())
+        {
+            self.pos += 1;
+        }
+
+        let num = self.raw(start, self.pos);
+        if crate::math::parser::parse_integer(&num).is_none()
+            && crate::math::parser::parse_float(&num).is_none()
+        {
+            return self.error(&format!("malformed number near '{num}'"));
+        }
+        Ok(Token {
+            line,
+            kind: TOKEN_NUMBER,
+            value: num.into_bytes(),
+            start,
+            end: self.pos,
+        })
+    }
+
+    fn read_long_string(&mut self, level: usize, what: &str) -> Result<Vec<u8>, String> {
+        self.pos += level + 2; // [==[
+
+        // skip first newline
+        if self.current().is_some_and(Self::is_newline) {
+            self.skip_newline();
+        }
+
+        let mut buf = Vec::new();
+        loop {
+            match self.current() {
+                None => {
+                    return self.error(&format!("unfinished long {what} near '<eof>'"));
+                }
+                Some(b']') => {
+                    let mut n = 1;
+                    while self.peek(n) == Some(b'=') {
+                        n += 1;
+                    }
+                    if n - 1 == level && self.peek(n) == Some(b']') {
+                        self.pos += n + 1;
+                        break;
+                    }
+                    buf.extend_from_slice(&self.chunk[self.pos..self.pos + n]);
+                    self.pos += n;
+                }
+                Some(c) if Self::is_newline(c) => {
+                    self.skip_newline();
+                    buf.push(b'\n');
+                }
+                Some(c) => {
+                    buf.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+        Ok(buf)
+    }
+
+    fn scan_short_string(&mut self, delimiter: u8) -> Result<Vec<u8>, String> {
+        let start = self.pos;
+        self.pos += 1;
+
+        let mut buf = Vec::new();
+        loop {
+            let c = match self.current() {
+                Some(c) if c == delimiter => break,
+                Some(c) if !Self::is_newline(c) => c,
+                _ => {
+                    return self.error(&format!(
+                        "unfinished string near '{}'",
+                        self.raw(start, self.pos)
+                    ))
+                }
+            };
+
+            if c != b'\\' {
+                buf.push(c);
+                self.pos += 1;
+                continue;
+            }
+
+            self.pos += 1; // '\\'
+            match self.current() {
+                Some(b'a') => self.escape(&mut buf, b'\x07'),
+                Some(b'b') => self.escape(&mut buf, b'\x08'),
+                Some(b'f') => self.escape(&mut buf, b'\x0c'),
+                Some(b'n') => self.escape(&mut buf, b'\n'),
+                Some(b'r') => self.escape(&mut buf, b'\r'),
+                Some(b't') => self.escape(&mut buf, b'\t'),
+                Some(b'v') => self.escape(&mut buf, b'\x0b'),
+                Some(b'\\') => self.escape(&mut buf, b'\\'),
+                Some(b'"') => self.escape(&mut buf, b'"'),
+                Some(b'\'') => self.escape(&mut buf, b'\''),
+                Some(c) if Self::is_newline(c) => {
+                    self.skip_newline();
+                    buf.push(b'\n');
+                }
+                Some(b'x') => {
+                    let mut v = 0;
+                    for i in 1..=2 {
+                        match self.peek(i).and_then(|c| (c as char).to_digit(16)) {
+                            Some(d) => v = v * 16 + d,
+                            None => {
+                                return self.escape_error(
+                                    start,
+                                    self.pos + i + 1,
+                                    "hexadecimal digit expected",
+                                )
+                            }
+                        }
+                    }
+                    self.pos += 3;
+                    buf.push(v as u8);
+                }
+                Some(b'z') => {
+                    self.pos += 1;
+                    while let Some(c) = self.current() {
+                        if Self::is_newline(c) {
+                            self.skip_newline();
+                        } else if c.is_ascii_whitespace() || c == b'\x0b' {
+                            self.pos += 1;
+                        } else {
+                            break;
+                        }
+                    }
+                }
+                Some(b'u') => {
+                    if self.peek(1) != Some(b'{') {
+                        return self.escape_error(start, self.pos + 2, "missing '{'");
+                    }
+                    let mut n = 2;
+                    let mut v: u32 = 0;
+                    while let Some(d) = self.peek(n).and_then(|c| (c as char).to_digit(16)) {
+                        v = match v.checked_mul(16) {
+                            Some(x) if x + d <= 0x7FFFFFFF => x + d,
+                            _ => {
+                                return self.escape_error(
+                                    start,
+                                    self.pos + n + 1,
+                                    "UTF-8 value too large",
+                                )
+                            }
+                        };
+                        n += 1;
+                    }
+                    if n == 2 {
+                        return self.escape_error(
+                            start,
+                            self.pos + n + 1,
+                            "hexadecimal digit expected",
+                        );
+                    }
+                    if self.peek(n) != Some(b'}') {
+                        return self.escape_error(start, self.pos + n + 1, "missing '}'");
+                    }
+                    self.pos += n + 1;
+                    utf8_encode(&mut buf, v);
+                }
+                Some(c) if c.is_ascii_digit() => {
+                    let mut v: u32 = 0;
+                    let mut n = 0;
+                    while n < 3 {
+                        match self.peek(n).filter(|c| c.is_ascii_digit()) {
+                            Some(d) => v = v * 10 + (d - b'0') as u32,
+                            None => break,
+                        }
+                        n += 1;
+                    }
+                    if v > 0xFF {
+                        return self.escape_error(start, self.pos + n, "decimal escape too large");
+                    }
+                    self.pos += n;
+                    buf.push(v as u8);
+                }
+                None => {
+                    return self.error(&format!(
+                        "unfinished string near '{}'",
+                        self.raw(start, self.pos)
+                    ))
+                }
+                Some(_) => {
+                    return self.escape_error(start, self.pos + 1, "invalid escape sequence")
+                }
+            }
+        }
+
+        self.pos += 1; // closing delimiter
+        Ok(buf)
+    }
+
+    fn escape(&mut self, buf: &mut Vec<u8>, c: u8) {
+        buf.push(c);
+        self.pos += 1;
+    }
+
+    fn escape_error<T>(&self, start: usize, end: usize, msg: &str) -> Result<T, String> {
+        let end = end.min(self.chunk.len());
+        self.error(&format!("{msg} near '{}'", self.raw(start, end)))
+    }
+}
+
+// encodes a code point the way Lua does, accepting values up to 2^31
+fn utf8_encode(buf: &mut Vec<u8>, mut x: u32) {
+    if x < 0x80 {
+        buf.push(x as u8);
+        return;
+    }
+    let mut bytes = Vec::new();
+    let mut mfb: u32 = 0x3f; // maximum that fits in first byte
+    loop {
+        bytes.push(0x80 | (x & 0x3f) as u8);
+        x >>= 6;
+        mfb >>= 1;
+        if x <= mfb {
+            break;
+        }
+    }
+    bytes.push(((!mfb << 1) | x) as u8);
+    buf.extend(bytes.iter().rev());
+}
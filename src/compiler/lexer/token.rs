@@ -0,0 +1,150 @@
+pub const TOKEN_EOF: u8 = 0x00; // end-of-file
+pub const TOKEN_VARARG: u8 = 0x01; // ...
+pub const TOKEN_SEP_SEMI: u8 = 0x02; // ;
+pub const TOKEN_SEP_COMMA: u8 = 0x03; // ,
+pub const TOKEN_SEP_DOT: u8 = 0x04; // .
+pub const TOKEN_SEP_COLON: u8 = 0x05; // :
+pub const TOKEN_SEP_LABEL: u8 = 0x06; // ::
+pub const TOKEN_SEP_LPAREN: u8 = 0x07; // (
+pub const TOKEN_SEP_RPAREN: u8 = 0x08; // )
+pub const TOKEN_SEP_LBRACK: u8 = 0x09; // [
+pub const TOKEN_SEP_RBRACK: u8 = 0x0a; // ]
+pub const TOKEN_SEP_LCURLY: u8 = 0x0b; // {
+pub const TOKEN_SEP_RCURLY: u8 = 0x0c; // }
+pub const TOKEN_OP_ASSIGN: u8 = 0x0d; // =
+pub const TOKEN_OP_MINUS: u8 = 0x0e; // - (sub or unm)
+pub const TOKEN_OP_WAVE: u8 = 0x0f; // ~ (bnot or bxor)
+pub const TOKEN_OP_ADD: u8 = 0x10; // +
+pub const TOKEN_OP_MUL: u8 = 0x11; // *
+pub const TOKEN_OP_DIV: u8 = 0x12; // /
+pub const TOKEN_OP_IDIV: u8 = 0x13; // //
+pub const TOKEN_OP_POW: u8 = 0x14; // ^
+pub const TOKEN_OP_MOD: u8 = 0x15; // %
+pub const TOKEN_OP_BAND: u8 = 0x16; // &
+pub const TOKEN_OP_BOR: u8 = 0x17; // |
+pub const TOKEN_OP_SHR: u8 = 0x18; // >>
+pub const TOKEN_OP_SHL: u8 = 0x19; // <<
+pub const TOKEN_OP_CONCAT: u8 = 0x1a; // ..
+pub const TOKEN_OP_LT: u8 = 0x1b; // <
+pub const TOKEN_OP_LE: u8 = 0x1c; // <=
+pub const TOKEN_OP_GT: u8 = 0x1d; // >
+pub const TOKEN_OP_GE: u8 = 0x1e; // >=
+pub const TOKEN_OP_EQ: u8 = 0x1f; // ==
+pub const TOKEN_OP_NE: u8 = 0x20; // ~=
+pub const TOKEN_OP_LEN: u8 = 0x21; // #
+pub const TOKEN_OP_AND: u8 = 0x22; // and
+pub const TOKEN_OP_OR: u8 = 0x23; // or
+pub const TOKEN_OP_NOT: u8 = 0x24; // not
+pub const TOKEN_KW_BREAK: u8 = 0x25; // break
+pub const TOKEN_KW_DO: u8 = 0x26; // do
+pub const TOKEN_KW_ELSE: u8 = 0x27; // else
+pub const TOKEN_KW_ELSEIF: u8 = 0x28; // elseif
+pub const TOKEN_KW_END: u8 = 0x29; // end
+pub const TOKEN_KW_FALSE: u8 = 0x2a; // false
+pub const TOKEN_KW_FOR: u8 = 0x2b; // for
+pub const TOKEN_KW_FUNCTION: u8 = 0x2c; // function
+pub const TOKEN_KW_GOTO: u8 = 0x2d; // goto
+pub const TOKEN_KW_IF: u8 = 0x2e; // if
+pub const TOKEN_KW_IN: u8 = 0x2f; // in
+pub const TOKEN_KW_LOCAL: u8 = 0x30; // local
+pub const TOKEN_KW_NIL: u8 = 0x31; // nil
+pub const TOKEN_KW_REPEAT: u8 = 0x32; // repeat
+pub const TOKEN_KW_RETURN: u8 = 0x33; // return
+pub const TOKEN_KW_THEN: u8 = 0x34; // then
+pub const TOKEN_KW_TRUE: u8 = 0x35; // true
+pub const TOKEN_KW_UNTIL: u8 = 0x36; // until
+pub const TOKEN_KW_WHILE: u8 = 0x37; // while
+pub const TOKEN_IDENTIFIER: u8 = 0x38; // identifier
+pub const TOKEN_NUMBER: u8 = 0x39; // number literal
+pub const TOKEN_STRING: u8 = 0x3a; // string literal
+pub const TOKEN_OP_UNM: u8 = TOKEN_OP_MINUS; // unary minus
+pub const TOKEN_OP_SUB: u8 = TOKEN_OP_MINUS;
+pub const TOKEN_OP_BNOT: u8 = TOKEN_OP_WAVE;
+pub const TOKEN_OP_BXOR: u8 = TOKEN_OP_WAVE;
+
+const KEYWORDS: [(&str, u8); 22] = [
+    ("and", TOKEN_OP_AND),
+    ("break", TOKEN_KW_BREAK),
+    ("do", TOKEN_KW_DO),
+    ("else", TOKEN_KW_ELSE),
+    ("elseif", TOKEN_KW_ELSEIF),
+    ("end", TOKEN_KW_END),
+    ("false", TOKEN_KW_FALSE),
+    ("for", TOKEN_KW_FOR),
+    ("function", TOKEN_KW_FUNCTION),
+    ("goto", TOKEN_KW_GOTO),
+    ("if", TOKEN_KW_IF),
+    ("in", TOKEN_KW_IN),
+    ("local", TOKEN_KW_LOCAL),
+    ("nil", TOKEN_KW_NIL),
+    ("not", TOKEN_OP_NOT),
+    ("or", TOKEN_OP_OR),
+    ("repeat", TOKEN_KW_REPEAT),
+    ("return", TOKEN_KW_RETURN),
+    ("then", TOKEN_KW_THEN),
+    ("true", TOKEN_KW_TRUE),
+    ("until", TOKEN_KW_UNTIL),
+    ("while", TOKEN_KW_WHILE),
+];
+
+pub fn keyword(name: &str) -> Option<u8> {
+    KEYWORDS
+        .iter()
+        .find(|(k, _)| *k == name)
+        .map(|(_, kind)| *kind)
+}
+
+// printable form of a token kind, used in "'x' expected" messages
+pub fn token_name(kind: u8) -> &'static str {
+    match kind {
+        TOKEN_EOF => "<eof>",
+        TOKEN_VARARG => "...",
+        TOKEN_SEP_SEMI => ";",
+        TOKEN_SEP_COMMA => ",",
+        TOKEN_SEP_DOT => ".",
+        TOKEN_SEP_COLON => ":",
+        TOKEN_SEP_LABEL => "::",
+        TOKEN_SEP_LPAREN => "(",
+        TOKEN_SEP_RPAREN => ")",
+        TOKEN_SEP_LBRACK => "[",
+        TOKEN_SEP_RBRACK => "]",
+        TOKEN_SEP_LCURLY => "{",
+        TOKEN_SEP_RCURLY => "}",
+        TOKEN_OP_ASSIGN => "=",
+        TOKEN_OP_MINUS => "-",
+        TOKEN_OP_WAVE => "~",
+        TOKEN_OP_ADD => "+",
+        TOKEN_OP_MUL => "*",
+        TOKEN_OP_DIV => "/",
+        TOKEN_OP_IDIV => "//",
+        TOKEN_OP_POW => "^",
+        TOKEN_OP_MOD => "%",
+        TOKEN_OP_BAND => "&",
+        TOKEN_OP_BOR => "|",
+        TOKEN_OP_SHR => ">>",
+        TOKEN_OP_SHL => "<<",
+        TOKEN_OP_CONCAT => "..",
+        TOKEN_OP_LT => "<",
+        TOKEN_OP_LE => "<=",
+        TOKEN_OP_GT => ">",
+        TOKEN_OP_GE => ">=",
+        TOKEN_OP_EQ => "==",
+        TOKEN_OP_NE => "~=",
+        TOKEN_OP_LEN => "#",
+        TOKEN_IDENTIFIER => "<name>",
+        TOKEN_NUMBER => "<number>",
+        TOKEN_STRING => "<string>",
+        _ => KEYWORDS
+            .iter()
+            .find(|(_, k)| *k == kind)
+            .map_or("?", |(name, _)| name),
+    }
+}
+
+// symbols and reserved words are quoted, names and literals are not
+pub fn token_str(kind: u8) -> String {
+    match kind {
+        TOKEN_EOF | TOKEN_IDENTIFIER | TOKEN_NUMBER | TOKEN_STRING => token_name(kind).to_string(),
+        _ => format!("'{}'", token_name(kind)),
+    }
+}
@@ -0,0 +1,13 @@
+mod ast;
+mod codegen;
+pub mod lexer;
+mod parser;
+
+use std::rc::Rc;
+
+use crate::binary::chunk::Prototype;
+
+pub fn compile(chunk: &[u8], chunk_name: &str) -> Result<Rc<Prototype>, String> {
+    let ast = parser::parse(chunk, chunk_name)?;
+    codegen::gen_proto(ast, chunk_name)
+}
@@ -0,0 +1,17 @@
+mod optimizer;
+mod parse_block;
+mod parse_exp;
+mod parse_prefix_exp;
+mod parse_stat;
+
+use super::{
+    ast::Block,
+    lexer::{token::TOKEN_EOF, Lexer},
+};
+
+pub fn parse(chunk: &[u8], chunk_name: &str) -> Result<Block, String> {
+    let mut lexer = Lexer::new(chunk, chunk_name);
+    let block = parse_block::parse_block(&mut lexer)?;
+    lexer.next_token_of_kind(TOKEN_EOF)?;
+    Ok(block)
+}
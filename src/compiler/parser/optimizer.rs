@@ -0,0 +1,192 @@
+use crate::{
+    compiler::{ast::Exp, lexer::token::*},
+    math::number::{
+        f_floor_div, f_mod, float_to_integer, i_floor_div, i_mod, shift_left, shift_right,
+    },
+};
+
+// true or x => true
+// false or x => x
+pub fn optimize_logical_or(exp: Exp) -> Exp {
+    match exp {
+        Exp::Binop { exp1, .. } if is_true(&exp1) => *exp1,
+        Exp::Binop { exp1, exp2, .. } if is_false(&exp1) && !exp2.is_vararg_or_func_call() => *exp2,
+        _ => exp,
+    }
+}
+
+// false and x => false
+// true and x => x
+pub fn optimize_logical_and(exp: Exp) -> Exp {
+    match exp {
+        Exp::Binop { exp1, .. } if is_false(&exp1) => *exp1,
+        Exp::Binop { exp1, exp2, .. } if is_true(&exp1) && !exp2.is_vararg_or_func_call() => *exp2,
+        _ => exp,
+    }
+}
+
+pub fn optimize_bitwise_binary_op(exp: Exp) -> Exp {
+    if let Exp::Binop {
+        line,
+        op,
+        exp1,
+        exp2,
+    } = &exp
+    {
+        if let (Some(i), Some(j)) = (cast_to_int(exp1), cast_to_int(exp2)) {
+            let val = match *op {
+                TOKEN_OP_BAND => i & j,
+                TOKEN_OP_BOR => i | j,
+                TOKEN_OP_BXOR => i ^ j,
+                TOKEN_OP_SHL => shift_left(i, j),
+                TOKEN_OP_SHR => shift_right(i, j),
+                _ => return exp,
+            };
+            return Exp::Integer { line: *line, val };
+        }
+    }
+    exp
+}
+
+pub fn optimize_arith_binary_op(exp: Exp) -> Exp {
+    if let Exp::Binop {
+        line,
+        op,
+        exp1,
+        exp2,
+    } = &exp
+    {
+        if let (Exp::Integer { val: x, .. }, Exp::Integer { val: y, .. }) = (&**exp1, &**exp2) {
+            let (x, y) = (*x, *y);
+            let val = match *op {
+                TOKEN_OP_ADD => Some(x.wrapping_add(y)),
+                TOKEN_OP_SUB => Some(x.wrapping_sub(y)),
+                TOKEN_OP_MUL => Some(x.wrapping_mul(y)),
+                TOKEN_OP_IDIV if y != 0 => Some(i_floor_div(x, y)),
+                TOKEN_OP_MOD if y != 0 => Some(i_mod(x, y)),
+                _ => None,
+            };
+            if let Some(val) = val {
+                return Exp::Integer { line: *line, val };
+            }
+        }
+
+        if let (Some(f), Some(g)) = (cast_to_float(exp1), cast_to_float(exp2)) {
+            let val = match *op {
+                TOKEN_OP_ADD => f + g,
+                TOKEN_OP_SUB => f - g,
+                TOKEN_OP_MUL => f * g,
+                TOKEN_OP_DIV if g != 0.0 => f / g,
+                TOKEN_OP_IDIV if g != 0.0 => f_floor_div(f, g),
+                TOKEN_OP_MOD if g != 0.0 => f_mod(f, g),
+                TOKEN_OP_POW => f.powf(g),
+                _ => return exp,
+            };
+            return fold_float(exp, val);
+        }
+    }
+    exp
+}
+
+pub fn optimize_pow(exp: Exp) -> Exp {
+    optimize_arith_binary_op(exp)
+}
+
+pub fn optimize_unary_op(exp: Exp) -> Exp {
+    if let Exp::Unop {
+        line,
+        op,
+        exp: inner,
+    } = exp
+    {
+        return match op {
+            TOKEN_OP_UNM => optimize_unm(line, inner),
+            TOKEN_OP_NOT => optimize_not(line, inner),
+            TOKEN_OP_BNOT => optimize_bnot(line, inner),
+            _ => Exp::Unop {
+                line,
+                op,
+                exp: inner,
+            },
+        };
+    }
+    exp
+}
+
+fn optimize_unm(line: usize, exp: Box<Exp>) -> Exp {
+    match *exp {
+        Exp::Integer { line, val } => Exp::Integer {
+            line,
+            val: val.wrapping_neg(),
+        },
+        Exp::Float { line, val } if val != 0.0 => Exp::Float { line, val: -val },
+        _ => Exp::Unop {
+            line,
+            op: TOKEN_OP_UNM,
+            exp,
+        },
+    }
+}
+
+fn optimize_not(line: usize, exp: Box<Exp>) -> Exp {
+    match *exp {
+        Exp::Nil { .. } | Exp::False { .. } => Exp::True { line },
+        Exp::True { .. } | Exp::Integer { .. } | Exp::Float { .. } | Exp::String { .. } => {
+            Exp::False { line }
+        }
+        _ => Exp::Unop {
+            line,
+            op: TOKEN_OP_NOT,
+            exp,
+        },
+    }
+}
+
+fn optimize_bnot(line: usize, exp: Box<Exp>) -> Exp {
+    match cast_to_int(&exp) {
+        Some(i) => Exp::Integer { line, val: !i },
+        None => Exp::Unop {
+            line,
+            op: TOKEN_OP_BNOT,
+            exp,
+        },
+    }
+}
+
+// NaN and zero results are left to the VM, just like luac
+fn fold_float(exp: Exp, val: f64) -> Exp {
+    if val.is_nan() || val == 0.0 {
+        return exp;
+    }
+    Exp::Float {
+        line: exp.line(),
+        val,
+    }
+}
+
+fn is_false(exp: &Exp) -> bool {
+    matches!(exp, Exp::False { .. } | Exp::Nil { .. })
+}
+
+fn is_true(exp: &Exp) -> bool {
+    matches!(
+        exp,
+        Exp::True { .. } | Exp::Integer { .. } | Exp::Float { .. } | Exp::String { .. }
+    )
+}
+
+fn cast_to_int(exp: &Exp) -> Option<i64> {
+    match exp {
+        Exp::Integer { val, .. } => Some(*val),
+        Exp::Float { val, .. } => float_to_integer(*val),
+        _ => None,
+    }
+}
+
+fn cast_to_float(exp: &Exp) -> Option<f64> {
+    match exp {
+        Exp::Integer { val, .. } => Some(*val as f64),
+        Exp::Float { val, .. } => Some(*val),
+        _ => None,
+    }
+}
@@ -0,0 +1,64 @@
+use crate::compiler::{
+    ast::{Block, Exp, Stat},
+    lexer::{token::*, Lexer},
+};
+
+use super::{parse_exp::parse_exp_list, parse_stat::parse_stat};
+
+// block ::= {stat} [retstat]
+pub fn parse_block(lexer: &mut Lexer) -> Result<Block, String> {
+    Ok(Block {
+        stats: parse_stats(lexer)?,
+        ret_exps: parse_ret_exps(lexer)?,
+        last_line: lexer.last_line(),
+    })
+}
+
+fn parse_stats(lexer: &mut Lexer) -> Result<Vec<Stat>, String> {
+    let mut stats = Vec::with_capacity(8);
+    while !is_return_or_block_end(lexer.look_ahead()?) {
+        let stat = parse_stat(lexer)?;
+        if !matches!(stat, Stat::Empty) {
+            stats.push(stat);
+        }
+    }
+    Ok(stats)
+}
+
+fn is_return_or_block_end(token_kind: u8) -> bool {
+    matches!(
+        token_kind,
+        TOKEN_KW_RETURN
+            | TOKEN_EOF
+            | TOKEN_KW_END
+            | TOKEN_KW_ELSE
+            | TOKEN_KW_ELSEIF
+            | TOKEN_KW_UNTIL
+    )
+}
+
+// retstat ::= return [explist] [';']
+// explist ::= exp {',' exp}
+fn parse_ret_exps(lexer: &mut Lexer) -> Result<Option<Vec<Exp>>, String> {
+    if lexer.look_ahead()? != TOKEN_KW_RETURN {
+        return Ok(None);
+    }
+
+    lexer.next_token()?;
+    match lexer.look_ahead()? {
+        TOKEN_EOF | TOKEN_KW_END | TOKEN_KW_ELSE | TOKEN_KW_ELSEIF | TOKEN_KW_UNTIL => {
+            Ok(Some(vec![]))
+        }
+        TOKEN_SEP_SEMI => {
+            lexer.next_token()?;
+            Ok(Some(vec![]))
+        }
+        _ => {
+            let exps = parse_exp_list(lexer)?;
+            if lexer.look_ahead()? == TOKEN_SEP_SEMI {
+                lexer.next_token()?;
+            }
+            Ok(Some(exps))
+        }
+    }
+}
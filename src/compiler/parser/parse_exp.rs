@@ -0,0 +1,394 @@
+use crate::{
+    compiler::{
+        ast::{Exp, FuncDefExp},
+        lexer::{token::*, Lexer},
+    },
+    math::parser::{parse_float, parse_integer},
+};
+
+use super::{
+    optimizer::{
+        optimize_arith_binary_op, optimize_bitwise_binary_op, optimize_logical_and,
+        optimize_logical_or, optimize_pow, optimize_unary_op,
+    },
+    parse_block::parse_block,
+    parse_prefix_exp::parse_prefix_exp,
+};
+
+// explist ::= exp {',' exp}
+pub fn parse_exp_list(lexer: &mut Lexer) -> Result<Vec<Exp>, String> {
+    let mut exps = Vec::with_capacity(4);
+    exps.push(parse_exp(lexer)?);
+    while lexer.look_ahead()? == TOKEN_SEP_COMMA {
+        lexer.next_token()?;
+        exps.push(parse_exp(lexer)?);
+    }
+    Ok(exps)
+}
+
+/*
+exp ::=  nil | false | true | Numeral | LiteralString | '...' | functiondef |
+     prefixexp | tableconstructor | exp binop exp | unop exp
+*/
+/*
+exp   ::= exp12
+exp12 ::= exp11 {or exp11}
+exp11 ::= exp10 {and exp10}
+exp10 ::= exp9 {('<' | '>' | '<=' | '>=' | '~=' | '==') exp9}
+exp9  ::= exp8 {'|' exp8}
+exp8  ::= exp7 {'~' exp7}
+exp7  ::= exp6 {'&' exp6}
+exp6  ::= exp5 {('<<' | '>>') exp5}
+exp5  ::= exp4 {'..' exp4}
+exp4  ::= exp3 {('+' | '-') exp3}
+exp3  ::= exp2 {('*' | '/' | '//' | '%') exp2}
+exp2  ::= {('not' | '#' | '-' | '~')} exp1
+exp1  ::= exp0 {'^' exp2}
+exp0  ::= nil | false | true | Numeral | LiteralString
+        | '...' | functiondef | prefixexp | tableconstructor
+*/
+pub fn parse_exp(lexer: &mut Lexer) -> Result<Exp, String> {
+    lexer.enter_level()?;
+    let exp = parse_exp12(lexer)?;
+    lexer.leave_level();
+    Ok(exp)
+}
+
+fn binop(line: usize, op: u8, exp1: Exp, exp2: Exp) -> Exp {
+    Exp::Binop {
+        line,
+        op,
+        exp1: Box::new(exp1),
+        exp2: Box::new(exp2),
+    }
+}
+
+// x or y
+fn parse_exp12(lexer: &mut Lexer) -> Result<Exp, String> {
+    let mut exp = parse_exp11(lexer)?;
+    while lexer.look_ahead()? == TOKEN_OP_OR {
+        let (line, op, _) = lexer.next_token()?;
+        let lor = binop(line, op, exp, parse_exp11(lexer)?);
+        exp = optimize_logical_or(lor);
+    }
+    Ok(exp)
+}
+
+// x and y
+fn parse_exp11(lexer: &mut Lexer) -> Result<Exp, String> {
+    let mut exp = parse_exp10(lexer)?;
+    while lexer.look_ahead()? == TOKEN_OP_AND {
+        let (line, op, _) = lexer.next_token()?;
+        let land = binop(line, op, exp, parse_exp10(lexer)?);
+        exp = optimize_logical_and(land);
+    }
+    Ok(exp)
+}
+
+// compare
+fn parse_exp10(lexer: &mut Lexer) -> Result<Exp, String> {
+    let mut exp = parse_exp9(lexer)?;
+    loop {
+        match lexer.look_ahead()? {
+            TOKEN_OP_LT | TOKEN_OP_GT | TOKEN_OP_NE | TOKEN_OP_LE | TOKEN_OP_GE | TOKEN_OP_EQ => {
+                let (line, op, _) = lexer.next_token()?;
+                exp = binop(line, op, exp, parse_exp9(lexer)?);
+            }
+            _ => return Ok(exp),
+        }
+    }
+}
+
+// x | y
+fn parse_exp9(lexer: &mut Lexer) -> Result<Exp, String> {
+    let mut exp = parse_exp8(lexer)?;
+    while lexer.look_ahead()? == TOKEN_OP_BOR {
+        let (line, op, _) = lexer.next_token()?;
+        let bor = binop(line, op, exp, parse_exp8(lexer)?);
+        exp = optimize_bitwise_binary_op(bor);
+    }
+    Ok(exp)
+}
+
+// x ~ y
+fn parse_exp8(lexer: &mut Lexer) -> Result<Exp, String> {
+    let mut exp = parse_exp7(lexer)?;
+    while lexer.look_ahead()? == TOKEN_OP_BXOR {
+        let (line, op, _) = lexer.next_token()?;
+        let bxor = binop(line, op, exp, parse_exp7(lexer)?);
+        exp = optimize_bitwise_binary_op(bxor);
+    }
+    Ok(exp)
+}
+
+// x & y
+fn parse_exp7(lexer: &mut Lexer) -> Result<Exp, String> {
+    let mut exp = parse_exp6(lexer)?;
+    while lexer.look_ahead()? == TOKEN_OP_BAND {
+        let (line, op, _) = lexer.next_token()?;
+        let band = binop(line, op, exp, parse_exp6(lexer)?);
+        exp = optimize_bitwise_binary_op(band);
+    }
+    Ok(exp)
+}
+
+// shift
+fn parse_exp6(lexer: &mut Lexer) -> Result<Exp, String> {
+    let mut exp = parse_exp5(lexer)?;
+    loop {
+        match lexer.look_ahead()? {
+            TOKEN_OP_SHL | TOKEN_OP_SHR => {
+                let (line, op, _) = lexer.next_token()?;
+                let shx = binop(line, op, exp, parse_exp5(lexer)?);
+                exp = optimize_bitwise_binary_op(shx);
+            }
+            _ => return Ok(exp),
+        }
+    }
+}
+
+// a .. b
+fn parse_exp5(lexer: &mut Lexer) -> Result<Exp, String> {
+    let exp = parse_exp4(lexer)?;
+    if lexer.look_ahead()? != TOKEN_OP_CONCAT {
+        return Ok(exp);
+    }
+
+    let mut line = 0;
+    let mut exps = vec![exp];
+    while lexer.look_ahead()? == TOKEN_OP_CONCAT {
+        line = lexer.next_token()?.0;
+        exps.push(parse_exp4(lexer)?);
+    }
+    Ok(Exp::Concat { line, exps })
+}
+
+// x +/- y
+fn parse_exp4(lexer: &mut Lexer) -> Result<Exp, String> {
+    let mut exp = parse_exp3(lexer)?;
+    loop {
+        match lexer.look_ahead()? {
+            TOKEN_OP_ADD | TOKEN_OP_SUB => {
+                let (line, op, _) = lexer.next_token()?;
+                let arith = binop(line, op, exp, parse_exp3(lexer)?);
+                exp = optimize_arith_binary_op(arith);
+            }
+            _ => return Ok(exp),
+        }
+    }
+}
+
+// *, %, /, //
+fn parse_exp3(lexer: &mut Lexer) -> Result<Exp, String> {
+    let mut exp = parse_exp2(lexer)?;
+    loop {
+        match lexer.look_ahead()? {
+            TOKEN_OP_MUL | TOKEN_OP_MOD | TOKEN_OP_DIV | TOKEN_OP_IDIV => {
+                let (line, op, _) = lexer.next_token()?;
+                let arith = binop(line, op, exp, parse_exp2(lexer)?);
+                exp = optimize_arith_binary_op(arith);
+            }
+            _ => return Ok(exp),
+        }
+    }
+}
+
+// unary
+fn parse_exp2(lexer: &mut Lexer) -> Result<Exp, String> {
+    match lexer.look_ahead()? {
+        TOKEN_OP_UNM | TOKEN_OP_BNOT | TOKEN_OP_LEN | TOKEN_OP_NOT => {
+            let (line, op, _) = lexer.next_token()?;
+            lexer.enter_level()?;
+            let exp = Exp::Unop {
+                line,
+                op,
+                exp: Box::new(parse_exp2(lexer)?),
+            };
+            lexer.leave_level();
+            Ok(optimize_unary_op(exp))
+        }
+        _ => parse_exp1(lexer),
+    }
+}
+
+// x ^ y
+fn parse_exp1(lexer: &mut Lexer) -> Result<Exp, String> {
+    // pow is right associative
+    let exp = parse_exp0(lexer)?;
+    if lexer.look_ahead()? == TOKEN_OP_POW {
+        let (line, op, _) = lexer.next_token()?;
+        lexer.enter_level()?;
+        let pow = binop(line, op, exp, parse_exp2(lexer)?);
+        lexer.leave_level();
+        return Ok(optimize_pow(pow));
+    }
+    Ok(exp)
+}
+
+fn parse_exp0(lexer: &mut Lexer) -> Result<Exp, String> {
+    match lexer.look_ahead()? {
+        TOKEN_VARARG => {
+            // ...
+            let (line, _, _) = lexer.next_token()?;
+            Ok(Exp::Vararg { line })
+        }
+        TOKEN_KW_NIL => {
+            // nil
+            let (line, _, _) = lexer.next_token()?;
+            Ok(Exp::Nil { line })
+        }
+        TOKEN_KW_TRUE => {
+            // true
+            let (line, _, _) = lexer.next_token()?;
+            Ok(Exp::True { line })
+        }
+        TOKEN_KW_FALSE => {
+            // false
+            let (line, _, _) = lexer.next_token()?;
+            Ok(Exp::False { line })
+        }
+        TOKEN_STRING => {
+            // LiteralString
+            let (line, s) = lexer.next_string()?;
+            Ok(Exp::String { line, val: s })
+        }
+        TOKEN_NUMBER => parse_number_exp(lexer), // Numeral
+        TOKEN_SEP_LCURLY => parse_table_constructor_exp(lexer), // tableconstructor
+        TOKEN_KW_FUNCTION => {
+            // functiondef
+            lexer.next_token()?;
+            Ok(Exp::FuncDef(parse_func_def_exp(lexer)?))
+        }
+        _ => parse_prefix_exp(lexer), // prefixexp
+    }
+}
+
+fn parse_number_exp(lexer: &mut Lexer) -> Result<Exp, String> {
+    let (line, _, token) = lexer.next_token()?;
+    if let Some(val) = parse_integer(&token) {
+        Ok(Exp::Integer { line, val })
+    } else if let Some(val) = parse_float(&token) {
+        Ok(Exp::Float { line, val })
+    } else {
+        lexer.error(&format!("malformed number near '{token}'"))
+    }
+}
+
+// functiondef ::= function funcbody
+// funcbody ::= '(' [parlist] ')' block end
+pub fn parse_func_def_exp(lexer: &mut Lexer) -> Result<FuncDefExp, String> {
+    let line = lexer.last_line(); // function
+    lexer.next_token_of_kind(TOKEN_SEP_LPAREN)?; // (
+    let (par_list, is_vararg) = parse_par_list(lexer)?; // [parlist]
+    lexer.next_token_of_kind(TOKEN_SEP_RPAREN)?; // )
+    let block = parse_block(lexer)?; // block
+    let last_line = lexer.check_match(TOKEN_KW_END, TOKEN_KW_FUNCTION, line)?; // end
+    Ok(FuncDefExp {
+        line,
+        last_line,
+        par_list,
+        is_vararg,
+        block: Box::new(block),
+    })
+}
+
+// [parlist]
+// parlist ::= namelist [',' '...'] | '...'
+fn parse_par_list(lexer: &mut Lexer) -> Result<(Vec<String>, bool), String> {
+    match lexer.look_ahead()? {
+        TOKEN_SEP_RPAREN => return Ok((vec![], false)),
+        TOKEN_VARARG => {
+            lexer.next_token()?;
+            return Ok((vec![], true));
+        }
+        _ => {}
+    }
+
+    let mut names = vec![lexer.next_identifier()?.1];
+    let mut is_vararg = false;
+    while lexer.look_ahead()? == TOKEN_SEP_COMMA {
+        lexer.next_token()?;
+        if lexer.look_ahead()? == TOKEN_IDENTIFIER {
+            let (_, name) = lexer.next_identifier()?;
+            names.push(name);
+        } else if lexer.look_ahead()? == TOKEN_VARARG {
+            lexer.next_token()?;
+            is_vararg = true;
+            break;
+        } else {
+            return lexer.error_near("<name> expected");
+        }
+    }
+    Ok((names, is_vararg))
+}
+
+// tableconstructor ::= '{' [fieldlist] '}'
+pub fn parse_table_constructor_exp(lexer: &mut Lexer) -> Result<Exp, String> {
+    let (line, _) = lexer.next_token_of_kind(TOKEN_SEP_LCURLY)?; // {
+    let (key_exps, val_exps) = parse_field_list(lexer)?; // [fieldlist]
+    let last_line = lexer.check_match(TOKEN_SEP_RCURLY, TOKEN_SEP_LCURLY, line)?; // }
+    Ok(Exp::TableConstructor {
+        line,
+        last_line,
+        key_exps,
+        val_exps,
+    })
+}
+
+type FieldList = (Vec<Option<Exp>>, Vec<Exp>);
+
+// fieldlist ::= field {fieldsep field} [fieldsep]
+fn parse_field_list(lexer: &mut Lexer) -> Result<FieldList, String> {
+    let mut ks = vec![];
+    let mut vs = vec![];
+    if lexer.look_ahead()? != TOKEN_SEP_RCURLY {
+        let (k, v) = parse_field(lexer)?;
+        ks.push(k);
+        vs.push(v);
+
+        while is_field_sep(lexer.look_ahead()?) {
+            lexer.next_token()?;
+            if lexer.look_ahead()? != TOKEN_SEP_RCURLY {
+                let (k, v) = parse_field(lexer)?;
+                ks.push(k);
+                vs.push(v);
+            } else {
+                break;
+            }
+        }
+    }
+    Ok((ks, vs))
+}
+
+// fieldsep ::= ',' | ';'
+fn is_field_sep(token_kind: u8) -> bool {
+    token_kind == TOKEN_SEP_COMMA || token_kind == TOKEN_SEP_SEMI
+}
+
+// field ::= '[' exp ']' '=' exp | Name '=' exp | exp
+fn parse_field(lexer: &mut Lexer) -> Result<(Option<Exp>, Exp), String> {
+    if lexer.look_ahead()? == TOKEN_SEP_LBRACK {
+        lexer.next_token()?; // [
+        let k = parse_exp(lexer)?; // exp
+        lexer.next_token_of_kind(TOKEN_SEP_RBRACK)?; // ]
+        lexer.next_token_of_kind(TOKEN_OP_ASSIGN)?; // =
+        let v = parse_exp(lexer)?; // exp
+        return Ok((Some(k), v));
+    }
+
+    let exp = parse_exp(lexer)?;
+    if let Exp::Name { line, name } = &exp {
+        if lexer.look_ahead()? == TOKEN_OP_ASSIGN {
+            // Name '=' exp => '[' LiteralString ']' = exp
+            lexer.next_token()?;
+            let k = Exp::String {
+                line: *line,
+                val: name.as_bytes().to_vec(),
+            };
+            let v = parse_exp(lexer)?;
+            return Ok((Some(k), v));
+        }
+    }
+
+    Ok((None, exp))
+}
@@ -0,0 +1,124 @@
+use crate::compiler::{
+    ast::{Exp, FuncCallExp},
+    lexer::{token::*, Lexer},
+};
+
+use super::parse_exp::{parse_exp, parse_exp_list, parse_table_constructor_exp};
+
+/*
+prefixexp ::= var | functioncall | '(' exp ')'
+var ::=  Name | prefixexp '[' exp ']' | prefixexp '.' Name
+functioncall ::=  prefixexp args | prefixexp ':' Name args
+
+prefixexp ::= Name
+    | '(' exp ')'
+    | prefixexp '[' exp ']'
+    | prefixexp '.' Name
+    | prefixexp [':' Name] args
+*/
+pub fn parse_prefix_exp(lexer: &mut Lexer) -> Result<Exp, String> {
+    let exp = match lexer.look_ahead()? {
+        TOKEN_IDENTIFIER => {
+            let (line, name) = lexer.next_identifier()?; // Name
+            Exp::Name { line, name }
+        }
+        TOKEN_SEP_LPAREN => parse_parens_exp(lexer)?, // '(' exp ')'
+        _ => return lexer.error_near("unexpected symbol"),
+    };
+    finish_prefix_exp(lexer, exp)
+}
+
+fn parse_parens_exp(lexer: &mut Lexer) -> Result<Exp, String> {
+    let (line, _) = lexer.next_token_of_kind(TOKEN_SEP_LPAREN)?; // (
+    let exp = parse_exp(lexer)?; // exp
+    lexer.check_match(TOKEN_SEP_RPAREN, TOKEN_SEP_LPAREN, line)?; // )
+
+    match exp {
+        Exp::Vararg { .. } | Exp::FuncCall(_) | Exp::Name { .. } | Exp::TableAccess { .. } => {
+            Ok(Exp::Parens(Box::new(exp)))
+        }
+        // no need to keep parens
+        _ => Ok(exp),
+    }
+}
+
+fn finish_prefix_exp(lexer: &mut Lexer, mut exp: Exp) -> Result<Exp, String> {
+    loop {
+        match lexer.look_ahead()? {
+            TOKEN_SEP_LBRACK => {
+                // prefixexp '[' exp ']'
+                lexer.next_token()?; // '['
+                let key_exp = parse_exp(lexer)?; // exp
+                lexer.next_token_of_kind(TOKEN_SEP_RBRACK)?; // ']'
+                exp = Exp::TableAccess {
+                    last_line: lexer.last_line(),
+                    prefix_exp: Box::new(exp),
+                    key_exp: Box::new(key_exp),
+                };
+            }
+            TOKEN_SEP_DOT => {
+                // prefixexp '.' Name
+                lexer.next_token()?; // '.'
+                let (line, name) = lexer.next_identifier()?; // Name
+                let key_exp = Exp::String { line, val: name.into_bytes() };
+                exp = Exp::TableAccess {
+                    last_line: line,
+                    prefix_exp: Box::new(exp),
+                    key_exp: Box::new(key_exp),
+                };
+            }
+            TOKEN_SEP_COLON | TOKEN_SEP_LPAREN | TOKEN_SEP_LCURLY | TOKEN_STRING => {
+                // prefixexp ':' Name args | prefixexp args
+                exp = Exp::FuncCall(parse_func_call_exp(lexer, exp)?);
+            }
+            _ => return Ok(exp),
+        }
+    }
+}
+
+// functioncall ::=  prefixexp args | prefixexp ':' Name args
+fn parse_func_call_exp(lexer: &mut Lexer, prefix_exp: Exp) -> Result<FuncCallExp, String> {
+    let line = prefix_exp.line();
+    let name_exp = parse_name_exp(lexer)?; // [':' Name]
+    let args = parse_args(lexer)?; // args
+    let last_line = lexer.last_line();
+    Ok(FuncCallExp {
+        line,
+        last_line,
+        prefix_exp: Box::new(prefix_exp),
+        name_exp,
+        args,
+    })
+}
+
+fn parse_name_exp(lexer: &mut Lexer) -> Result<Option<Box<Exp>>, String> {
+    if lexer.look_ahead()? == TOKEN_SEP_COLON {
+        lexer.next_token()?;
+        let (line, name) = lexer.next_identifier()?;
+        return Ok(Some(Box::new(Exp::String { line, val: name.into_bytes() })));
+    }
+    Ok(None)
+}
+
+// args ::=  '(' [explist] ')' | tableconstructor | LiteralString
+fn parse_args(lexer: &mut Lexer) -> Result<Vec<Exp>, String> {
+    match lexer.look_ahead()? {
+        TOKEN_SEP_LPAREN => {
+            // '(' [explist] ')'
+            let (line, _) = lexer.next_token_of_kind(TOKEN_SEP_LPAREN)?;
+            let mut args = vec![];
+            if lexer.look_ahead()? != TOKEN_SEP_RPAREN {
+                args = parse_exp_list(lexer)?;
+            }
+            lexer.check_match(TOKEN_SEP_RPAREN, TOKEN_SEP_LPAREN, line)?;
+            Ok(args)
+        }
+        TOKEN_SEP_LCURLY => Ok(vec![parse_table_constructor_exp(lexer)?]), // '{' [fieldlist] '}'
+        TOKEN_STRING => {
+            // LiteralString
+            let (line, s) = lexer.next_string()?;
+            Ok(vec![Exp::String { line, val: s }])
+        }
+        _ => lexer.error_near("function arguments expected"),
+    }
+}
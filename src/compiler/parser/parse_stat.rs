@@ -0,0 +1,344 @@
+use crate::compiler::{
+    ast::{Exp, Stat},
+    lexer::{token::*, Lexer},
+};
+
+use super::{
+    parse_block::parse_block,
+    parse_exp::{parse_exp, parse_exp_list, parse_func_def_exp},
+    parse_prefix_exp::parse_prefix_exp,
+};
+
+pub fn parse_stat(lexer: &mut Lexer) -> Result<Stat, String> {
+    lexer.enter_level()?;
+    let stat = match lexer.look_ahead()? {
+        TOKEN_SEP_SEMI => parse_empty_stat(lexer),
+        TOKEN_KW_BREAK => parse_break_stat(lexer),
+        TOKEN_SEP_LABEL => parse_label_stat(lexer),
+        TOKEN_KW_GOTO => parse_goto_stat(lexer),
+        TOKEN_KW_DO => parse_do_stat(lexer),
+        TOKEN_KW_WHILE => parse_while_stat(lexer),
+        TOKEN_KW_REPEAT => parse_repeat_stat(lexer),
+        TOKEN_KW_IF => parse_if_stat(lexer),
+        TOKEN_KW_FOR => parse_for_stat(lexer),
+        TOKEN_KW_FUNCTION => parse_func_def_stat(lexer),
+        TOKEN_KW_LOCAL => parse_local_assign_or_func_def_stat(lexer),
+        _ => parse_assign_or_func_call_stat(lexer),
+    }?;
+    lexer.leave_level();
+    Ok(stat)
+}
+
+// ;
+fn parse_empty_stat(lexer: &mut Lexer) -> Result<Stat, String> {
+    lexer.next_token_of_kind(TOKEN_SEP_SEMI)?;
+    Ok(Stat::Empty)
+}
+
+// break
+fn parse_break_stat(lexer: &mut Lexer) -> Result<Stat, String> {
+    let (line, _) = lexer.next_token_of_kind(TOKEN_KW_BREAK)?;
+    Ok(Stat::Break { line })
+}
+
+// '::' Name '::'
+fn parse_label_stat(lexer: &mut Lexer) -> Result<Stat, String> {
+    lexer.next_token_of_kind(TOKEN_SEP_LABEL)?; // ::
+    let (line, name) = lexer.next_identifier()?; // name
+    lexer.next_token_of_kind(TOKEN_SEP_LABEL)?; // ::
+    Ok(Stat::Label { line, name })
+}
+
+// goto Name
+fn parse_goto_stat(lexer: &mut Lexer) -> Result<Stat, String> {
+    let (line, _) = lexer.next_token_of_kind(TOKEN_KW_GOTO)?; // goto
+    let (_, name) = lexer.next_identifier()?; // name
+    Ok(Stat::Goto { line, name })
+}
+
+// do block end
+fn parse_do_stat(lexer: &mut Lexer) -> Result<Stat, String> {
+    let (line, _) = lexer.next_token_of_kind(TOKEN_KW_DO)?; // do
+    let block = parse_block(lexer)?; // block
+    lexer.check_match(TOKEN_KW_END, TOKEN_KW_DO, line)?; // end
+    Ok(Stat::Do {
+        block: Box::new(block),
+    })
+}
+
+// while exp do block end
+fn parse_while_stat(lexer: &mut Lexer) -> Result<Stat, String> {
+    let (line, _) = lexer.next_token_of_kind(TOKEN_KW_WHILE)?; // while
+    let exp = parse_exp(lexer)?; // exp
+    lexer.next_token_of_kind(TOKEN_KW_DO)?; // do
+    let block = parse_block(lexer)?; // block
+    lexer.check_match(TOKEN_KW_END, TOKEN_KW_WHILE, line)?; // end
+    Ok(Stat::While {
+        exp,
+        block: Box::new(block),
+    })
+}
+
+// repeat block until exp
+fn parse_repeat_stat(lexer: &mut Lexer) -> Result<Stat, String> {
+    let (line, _) = lexer.next_token_of_kind(TOKEN_KW_REPEAT)?; // repeat
+    let block = parse_block(lexer)?; // block
+    lexer.check_match(TOKEN_KW_UNTIL, TOKEN_KW_REPEAT, line)?; // until
+    let exp = parse_exp(lexer)?; // exp
+    Ok(Stat::Repeat {
+        block: Box::new(block),
+        exp,
+    })
+}
+
+// if exp then block {elseif exp then block} [else block] end
+fn parse_if_stat(lexer: &mut Lexer) -> Result<Stat, String> {
+    let mut exps = Vec::with_capacity(4);
+    let mut blocks = Vec::with_capacity(4);
+
+    let (line, _) = lexer.next_token_of_kind(TOKEN_KW_IF)?; // if
+    exps.push(parse_exp(lexer)?); // exp
+    lexer.next_token_of_kind(TOKEN_KW_THEN)?; // then
+    blocks.push(parse_block(lexer)?); // block
+
+    // {elseif exp then block}
+    while lexer.look_ahead()? == TOKEN_KW_ELSEIF {
+        lexer.next_token()?; // elseif
+        exps.push(parse_exp(lexer)?); // exp
+        lexer.next_token_of_kind(TOKEN_KW_THEN)?; // then
+        blocks.push(parse_block(lexer)?); // block
+    }
+
+    // else block => elseif true then block
+    if lexer.look_ahead()? == TOKEN_KW_ELSE {
+        let (line, _, _) = lexer.next_token()?; // else
+        exps.push(Exp::True { line });
+        blocks.push(parse_block(lexer)?); // block
+    }
+
+    lexer.check_match(TOKEN_KW_END, TOKEN_KW_IF, line)?; // end
+    Ok(Stat::If { exps, blocks })
+}
+
+// for Name '=' exp ',' exp [',' exp] do block end
+// for namelist in explist do block end
+fn parse_for_stat(lexer: &mut Lexer) -> Result<Stat, String> {
+    let (line_of_for, _) = lexer.next_token_of_kind(TOKEN_KW_FOR)?;
+    let (_, name) = lexer.next_identifier()?;
+    match lexer.look_ahead()? {
+        TOKEN_OP_ASSIGN => finish_for_num_stat(lexer, line_of_for, name),
+        TOKEN_SEP_COMMA | TOKEN_KW_IN => finish_for_in_stat(lexer, line_of_for, name),
+        _ => lexer.error_near("'=' or 'in' expected"),
+    }
+}
+
+fn finish_for_num_stat(
+    lexer: &mut Lexer,
+    line_of_for: usize,
+    var_name: String,
+) -> Result<Stat, String> {
+    lexer.next_token_of_kind(TOKEN_OP_ASSIGN)?; // =
+    let init_exp = parse_exp(lexer)?; // exp
+    lexer.next_token_of_kind(TOKEN_SEP_COMMA)?; // ,
+    let limit_exp = parse_exp(lexer)?; // exp
+
+    let step_exp = if lexer.look_ahead()? == TOKEN_SEP_COMMA {
+        lexer.next_token()?; // ,
+        parse_exp(lexer)? // exp
+    } else {
+        Exp::Integer {
+            line: lexer.line(),
+            val: 1,
+        }
+    };
+
+    let (line_of_do, _) = lexer.next_token_of_kind(TOKEN_KW_DO)?; // do
+    let block = parse_block(lexer)?; // block
+    lexer.check_match(TOKEN_KW_END, TOKEN_KW_FOR, line_of_for)?; // end
+
+    Ok(Stat::ForNum {
+        line_of_for,
+        line_of_do,
+        var_name,
+        init_exp,
+        limit_exp,
+        step_exp,
+        block: Box::new(block),
+    })
+}
+
+fn finish_for_in_stat(
+    lexer: &mut Lexer,
+    line_of_for: usize,
+    name0: String,
+) -> Result<Stat, String> {
+    let name_list = finish_name_list(lexer, name0)?; // for namelist
+    lexer.next_token_of_kind(TOKEN_KW_IN)?; // in
+    let exp_list = parse_exp_list(lexer)?; // explist
+    let (line_of_do, _) = lexer.next_token_of_kind(TOKEN_KW_DO)?; // do
+    let block = parse_block(lexer)?; // block
+    lexer.check_match(TOKEN_KW_END, TOKEN_KW_FOR, line_of_for)?; // end
+
+    Ok(Stat::ForIn {
+        line_of_do,
+        name_list,
+        exp_list,
+        block: Box::new(block),
+    })
+}
+
+// namelist ::= Name {',' Name}
+fn finish_name_list(lexer: &mut Lexer, name0: String) -> Result<Vec<String>, String> {
+    let mut names = vec![name0];
+    while lexer.look_ahead()? == TOKEN_SEP_COMMA {
+        lexer.next_token()?; // ,
+        let (_, name) = lexer.next_identifier()?; // Name
+        names.push(name);
+    }
+    Ok(names)
+}
+
+// local function Name funcbody
+// local namelist ['=' explist]
+fn parse_local_assign_or_func_def_stat(lexer: &mut Lexer) -> Result<Stat, String> {
+    lexer.next_token_of_kind(TOKEN_KW_LOCAL)?;
+    if lexer.look_ahead()? == TOKEN_KW_FUNCTION {
+        finish_local_func_def_stat(lexer)
+    } else {
+        finish_local_var_decl_stat(lexer)
+    }
+}
+
+/*
+http://www.lua.org/manual/5.3/manual.html#3.4.11
+
+function f() end          =>  f = function() end
+function t.a.b.c.f() end  =>  t.a.b.c.f = function() end
+function t.a.b.c:f() end  =>  t.a.b.c.f = function(self) end
+local function f() end    =>  local f; f = function() end
+
+The statement `local function f () body end`
+translates to `local f; f = function () body end`
+not to `local f = function () body end`
+(This only makes a difference when the body of the function
+ contains references to f.)
+*/
+fn finish_local_func_def_stat(lexer: &mut Lexer) -> Result<Stat, String> {
+    lexer.next_token_of_kind(TOKEN_KW_FUNCTION)?; // local function
+    let (_, name) = lexer.next_identifier()?; // name
+    let exp = parse_func_def_exp(lexer)?; // funcbody
+    Ok(Stat::LocalFuncDef { name, exp })
+}
+
+fn finish_local_var_decl_stat(lexer: &mut Lexer) -> Result<Stat, String> {
+    let (line, name0) = lexer.next_identifier()?; // local Name
+    let name_list = finish_name_list(lexer, name0)?; // { , Name }
+    let mut exp_list = vec![];
+    if lexer.look_ahead()? == TOKEN_OP_ASSIGN {
+        lexer.next_token()?; // =
+        exp_list = parse_exp_list(lexer)?; // explist
+    }
+    let last_line = lexer.last_line();
+    Ok(Stat::LocalVarDecl {
+        line,
+        last_line,
+        name_list,
+        exp_list,
+    })
+}
+
+// varlist '=' explist
+// functioncall
+fn parse_assign_or_func_call_stat(lexer: &mut Lexer) -> Result<Stat, String> {
+    let prefix_exp = parse_prefix_exp(lexer)?;
+    if let Exp::FuncCall(fc) = prefix_exp {
+        Ok(Stat::FuncCall(fc))
+    } else {
+        parse_assign_stat(lexer, prefix_exp)
+    }
+}
+
+// varlist '=' explist |
+fn parse_assign_stat(lexer: &mut Lexer, var0: Exp) -> Result<Stat, String> {
+    let var_list = finish_var_list(lexer, var0)?; // varlist
+    lexer.next_token_of_kind(TOKEN_OP_ASSIGN)?; // =
+    let exp_list = parse_exp_list(lexer)?; // explist
+    let last_line = lexer.last_line();
+    Ok(Stat::Assign {
+        last_line,
+        var_list,
+        exp_list,
+    })
+}
+
+// varlist ::= var {',' var}
+fn finish_var_list(lexer: &mut Lexer, var0: Exp) -> Result<Vec<Exp>, String> {
+    let mut vars = vec![check_var(lexer, var0)?]; // var
+    while lexer.look_ahead()? == TOKEN_SEP_COMMA {
+        lexer.next_token()?; // ,
+        let exp = parse_prefix_exp(lexer)?; // var
+        vars.push(check_var(lexer, exp)?);
+    }
+    Ok(vars)
+}
+
+// var ::=  Name | prefixexp '[' exp ']' | prefixexp '.' Name
+fn check_var(lexer: &mut Lexer, exp: Exp) -> Result<Exp, String> {
+    match exp {
+        Exp::Name { .. } | Exp::TableAccess { .. } => Ok(exp),
+        _ => lexer.error_near("syntax error"),
+    }
+}
+
+// function funcname funcbody
+// funcname ::= Name {'.' Name} [':' Name]
+// funcbody ::= '(' [parlist] ')' block end
+// parlist ::= namelist [',' '...'] | '...'
+// namelist ::= Name {',' Name}
+fn parse_func_def_stat(lexer: &mut Lexer) -> Result<Stat, String> {
+    lexer.next_token_of_kind(TOKEN_KW_FUNCTION)?; // function
+    let (fn_exp, has_colon) = parse_func_name(lexer)?; // funcname
+    let mut fd_exp = parse_func_def_exp(lexer)?; // funcbody
+    if has_colon {
+        // insert self
+        fd_exp.par_list.insert(0, String::from("self"));
+    }
+
+    Ok(Stat::Assign {
+        last_line: fd_exp.line,
+        var_list: vec![fn_exp],
+        exp_list: vec![Exp::FuncDef(fd_exp)],
+    })
+}
+
+// funcname ::= Name {'.' Name} [':' Name]
+fn parse_func_name(lexer: &mut Lexer) -> Result<(Exp, bool), String> {
+    let (line, name) = lexer.next_identifier()?;
+    let mut exp = Exp::Name { line, name };
+    let mut has_colon = false;
+
+    while lexer.look_ahead()? == TOKEN_SEP_DOT {
+        lexer.next_token()?;
+        let (line, name) = lexer.next_identifier()?;
+        let key_exp = Exp::String { line, val: name.into_bytes() };
+        exp = Exp::TableAccess {
+            last_line: line,
+            prefix_exp: Box::new(exp),
+            key_exp: Box::new(key_exp),
+        };
+    }
+
+    if lexer.look_ahead()? == TOKEN_SEP_COLON {
+        lexer.next_token()?;
+        let (line, name) = lexer.next_identifier()?;
+        let key_exp = Exp::String { line, val: name.into_bytes() };
+        exp = Exp::TableAccess {
+            last_line: line,
+            prefix_exp: Box::new(exp),
+            key_exp: Box::new(key_exp),
+        };
+        has_colon = true;
+    }
+
+    Ok((exp, has_colon))
+}
@@ -1,7 +1,11 @@
 mod api;
 mod binary;
+mod compiler;
 mod math;
 mod state;
+mod stdlib;
+#[cfg(test)]
+mod tests;
 mod vm;
 
 use std::{
@@ -11,17 +15,41 @@ use std::{
     path::Path
 };
 
-use crate::api::lua_vm::LuaAPI;
+use crate::{
+    api::{
+        basic::LUA_OK,
+        lua_vm::{LuaAPI, RustFunction},
+    },
+    binary::chunk::LUA_SIGNATURE,
+    state::LuaError,
+};
 
 fn main() {
-    if env::args().len() > 1 {
-        let filename = env::args().nth(1).unwrap();
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    // `-l file` lists the bytecode of file like `luac -l -l`
+    let listing = args.first().is_some_and(|arg| arg == "-l");
+    if listing {
+        args.remove(0);
+    }
+
+    if let Some(filename) = args.first() {
         match read_file(filename) {
+            Ok(data) if listing => match list(&data, &format!("@{}", filename)) {
+                Ok(listing) => print!("{}", listing),
+                Err(msg) => eprintln!("{}", msg),
+            },
             Ok(data) => {
                 let mut ls = state::new_lua_state();
-                ls.register("print", print);
-                ls.load(data, "test.lua", "b");
-                ls.call(0, 0);
+                if let Err(err) = ls.open_libs() {
+                    eprintln!("{}", err);
+                    return;
+                }
+                ls.push_rust_fn(RustFunction::Ptr(msg_handler));
+                if ls.load(data, &format!("@{}", filename), "bt") != LUA_OK
+                    || ls.pcall(0, 0, 1) != LUA_OK
+                {
+                    eprintln!("{}", error_message(&ls));
+                }
             }
             Err(e) => {
                 eprintln!("Error reading file: {}", e);
@@ -30,6 +58,15 @@ fn main() {
     }
 }
 
+fn list(chunk: &[u8], chunk_name: &str) -> Result<String, String> {
+    let proto = if chunk.starts_with(LUA_SIGNATURE) {
+        binary::un_dump(chunk).map_err(|e| e.to_string())?
+    } else {
+        compiler::compile(chunk, chunk_name)?
+    };
+    Ok(binary::lister::list(&proto, true))
+}
+
 fn read_file<P: AsRef<Path>>(path: P) -> Result<Vec<u8>, std::io::Error> {
     let file = File::open(path)?;
     let mut reader = BufReader::new(file);
@@ -38,20 +75,20 @@ fn read_file<P: AsRef<Path>>(path: P) -> Result<Vec<u8>, std::io::Error> {
     Ok(contents)
 }
 
-fn print(ls: &dyn LuaAPI) -> usize {
-    let n_args = ls.top();
-    for i in 1..(n_args + 1) {
-        if ls.is_string(i) {
-            print!("{}", ls.to_string(i));
-        } else {
-            print!("{}", ls.type_name_str(ls.type_enum_id(i)));
-        }
-        if i < n_args {
-            print!("\t")
-        }
-    }
-
-    println!();
-    0
+// appends a traceback to the error message, like the standalone interpreter
+fn msg_handler(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    let msg = error_message(ls);
+    ls.traceback(Some(&msg), 1);
+    Ok(1)
 }
 
+// message of the error object on the top of the stack
+fn error_message(ls: &dyn LuaAPI) -> String {
+    match ls.to_stringx(-1) {
+        Some(msg) => msg,
+        None => format!(
+            "(error object is a {} value)",
+            ls.type_name_str(ls.type_enum_id(-1))
+        ),
+    }
+}
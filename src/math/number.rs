@@ -1,10 +1,19 @@
-use std::os::raw::c_double;
+use std::{
+    ffi::CString,
+    os::raw::{c_char, c_double, c_int, c_longlong},
+};
 
 pub fn i_floor_div(a: i64, b: i64) -> i64 {
-    if a > 0 && b > 0 || a < 0 && b < 0 || a % b == 0 {
-        a / b
+    if b == -1 {
+        // avoid overflow with 0x80000...//-1
+        return a.wrapping_neg();
+    }
+
+    let q = a / b;
+    if (a ^ b) < 0 && q * b != a {
+        q - 1
     } else {
-        a / b - 1
+        q
     }
 }
 
@@ -13,29 +22,84 @@ pub fn f_floor_div(a: f64, b: f64) -> f64 {
 }
 
 pub fn i_mod(a: i64, b: i64) -> i64 {
-    a - i_floor_div(a, b) * b
+    if b == -1 {
+        // avoid overflow with 0x80000...%-1
+        return 0;
+    }
+
+    let r = a % b;
+    if r != 0 && (r ^ b) < 0 {
+        r + b
+    } else {
+        r
+    }
 }
 
 extern "C" {
     fn fmod(x: c_double, y: c_double) -> c_double;
+    fn snprintf(s: *mut c_char, n: usize, format: *const c_char, ...) -> c_int;
 }
 
 pub fn f_mod(a: f64, b: f64) -> f64 {
-    unsafe { fmod(a, b) }
+    let m = unsafe { fmod(a, b) };
+    if m * b < 0.0 {
+        m + b
+    } else {
+        m
+    }
 }
 
 pub fn shift_left(a: i64, n: i64) -> i64 {
-    a << n
+    if n <= -64 || n >= 64 {
+        0
+    } else if n >= 0 {
+        ((a as u64) << n) as i64
+    } else {
+        ((a as u64) >> -n) as i64
+    }
 }
 
 pub fn shift_right(a: i64, n: i64) -> i64 {
-    a >> n
+    shift_left(a, n.wrapping_neg())
 }
 
 pub fn float_to_integer(f: f64) -> Option<i64> {
-    if f < i64::MIN as f64 || f > i64::MAX as f64 {
-        None
+    // -2^63 <= f < 2^63 and f has no fractional part
+    if f.floor() == f && (-9223372036854775808.0..9223372036854775808.0).contains(&f) {
+        Some(f as i64)
     } else {
-        Some(f.round() as i64)
+        None
+    }
+}
+
+// runs one snprintf call, sized by a first call that writes nothing
+fn sprintf(spec: &str, print: impl Fn(*mut c_char, usize, *const c_char) -> c_int) -> String {
+    let spec = CString::new(spec).unwrap();
+    let n = print(std::ptr::null_mut(), 0, spec.as_ptr());
+    let mut buf = vec![0u8; n as usize + 1];
+    print(buf.as_mut_ptr() as *mut c_char, buf.len(), spec.as_ptr());
+    buf.truncate(n as usize);
+    String::from_utf8_lossy(&buf).into_owned()
+}
+
+// formats f with a single C conversion spec such as "%.14g"
+pub fn format_float(spec: &str, f: f64) -> String {
+    sprintf(spec, |buf, n, spec| unsafe { snprintf(buf, n, spec, f as c_double) })
+}
+
+// formats i with a single C conversion spec such as "%5d", which gets the
+// "ll" length modifier for an i64
+pub fn format_integer(spec: &str, i: i64) -> String {
+    let (flags, conv) = spec.split_at(spec.len() - 1);
+    let spec = format!("{}ll{}", flags, conv);
+    sprintf(&spec, |buf, n, spec| unsafe { snprintf(buf, n, spec, i as c_longlong) })
+}
+
+// "%.14g", with a ".0" added to floats that would look like integers
+pub fn float_to_str(f: f64) -> String {
+    let mut s = format_float("%.14g", f);
+    if s.bytes().all(|c| c == b'-' || c.is_ascii_digit()) {
+        s.push_str(".0");
     }
+    s
 }
@@ -1,13 +1,122 @@
+fn is_space(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\n' | '\r' | '\x0b' | '\x0c')
+}
+
+fn split_sign(s: &str) -> (bool, &str) {
+    match s.as_bytes().first() {
+        Some(b'-') => (true, &s[1..]),
+        Some(b'+') => (false, &s[1..]),
+        _ => (false, s),
+    }
+}
+
+fn strip_hex_prefix(s: &str) -> Option<&str> {
+    s.strip_prefix("0x").or_else(|| s.strip_prefix("0X"))
+}
+
+// decimal integers that overflow are not integers (they become floats),
+// hexadecimal integers wrap around
 pub fn parse_integer(s: &str) -> Option<i64> {
-    match s.parse::<i64>() {
-        Ok(value) => Some(value),
-        Err(_) => None,
+    let (neg, s) = split_sign(s.trim_matches(is_space));
+
+    if let Some(hex) = strip_hex_prefix(s) {
+        if hex.is_empty() {
+            return None;
+        }
+        let mut n: u64 = 0;
+        for c in hex.chars() {
+            n = n.wrapping_mul(16).wrapping_add(c.to_digit(16)? as u64);
+        }
+        let n = n as i64;
+        return Some(if neg { n.wrapping_neg() } else { n });
+    }
+
+    if s.is_empty() {
+        return None;
+    }
+    let limit = if neg {
+        i64::MIN.unsigned_abs()
+    } else {
+        i64::MAX as u64
+    };
+    let mut n: u64 = 0;
+    for c in s.chars() {
+        let d = c.to_digit(10)? as u64;
+        n = n.checked_mul(10)?.checked_add(d)?;
+        if n > limit {
+            return None;
+        }
     }
+    Some(if neg {
+        (n as i64).wrapping_neg()
+    } else {
+        n as i64
+    })
 }
 
 pub fn parse_float(s: &str) -> Option<f64> {
-    match s.parse::<f64>() {
-        Ok(value) => Some(value),
-        Err(_) => None,
+    let s = s.trim_matches(is_space);
+    // reject 'inf' and 'nan'
+    if s.contains(['n', 'N']) {
+        return None;
     }
+
+    let (neg, body) = split_sign(s);
+    if let Some(hex) = strip_hex_prefix(body) {
+        let f = parse_hex_float(hex)?;
+        return Some(if neg { -f } else { f });
+    }
+
+    if s.chars()
+        .all(|c| c.is_ascii_digit() || matches!(c, '.' | 'e' | 'E' | '+' | '-'))
+    {
+        s.parse::<f64>().ok()
+    } else {
+        None
+    }
+}
+
+// hexadecimal mantissa with an optional binary exponent: a.bP[+-]c
+fn parse_hex_float(s: &str) -> Option<f64> {
+    let mut mantissa = 0.0;
+    let mut exp: i64 = 0;
+    let mut any_digit = false;
+    let mut seen_dot = false;
+
+    let mut chars = s.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c == '.' {
+            if seen_dot {
+                return None;
+            }
+            seen_dot = true;
+        } else if let Some(d) = c.to_digit(16) {
+            mantissa = mantissa * 16.0 + d as f64;
+            any_digit = true;
+            if seen_dot {
+                exp -= 4;
+            }
+        } else {
+            break;
+        }
+        chars.next();
+    }
+    if !any_digit {
+        return None;
+    }
+
+    if let Some('p' | 'P') = chars.peek() {
+        chars.next();
+        let rest: String = chars.collect();
+        let (neg, digits) = split_sign(&rest);
+        if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        let e = digits.parse::<i64>().unwrap_or(i64::MAX);
+        exp = exp.saturating_add(if neg { -e } else { e });
+    } else if chars.next().is_some() {
+        return None;
+    }
+
+    Some(mantissa * 2f64.powi(exp.clamp(-2200, 2200) as i32))
 }
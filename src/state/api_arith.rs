@@ -1,24 +1,27 @@
 use crate::{
     api::basic::Arithmetic,
-    math::number::{f_floor_div, f_mod, i_floor_div, i_mod, shift_left, shift_right},
+    math::{
+        number::{f_floor_div, f_mod, i_floor_div, i_mod, shift_left, shift_right},
+        parser::parse_integer,
+    },
 };
 
 use super::lua_value::LuaValue;
 
 fn iadd(a: i64, b: i64) -> i64 {
-    a + b
+    a.wrapping_add(b)
 }
 fn fadd(a: f64, b: f64) -> f64 {
     a + b
 }
 fn isub(a: i64, b: i64) -> i64 {
-    a - b
+    a.wrapping_sub(b)
 }
 fn fsub(a: f64, b: f64) -> f64 {
     a - b
 }
 fn imul(a: i64, b: i64) -> i64 {
-    a * b
+    a.wrapping_mul(b)
 }
 fn fmul(a: f64, b: f64) -> f64 {
     a * b
@@ -57,7 +60,7 @@ fn shr(a: i64, b: i64) -> i64 {
     shift_right(a, b)
 }
 fn iunm(a: i64, _: i64) -> i64 {
-    -a
+    a.wrapping_neg()
 }
 fn funm(a: f64, _: f64) -> f64 {
     -a
@@ -93,12 +96,31 @@ pub const OPS: &'static [(IAddress, FAddress)] = &[
     (bnot, fnone),
 ];
 
+// metamethod names, in the same order as OPS
+pub const EVENTS: &[&str] = &[
+    "__add", "__sub", "__mul", "__mod", "__pow", "__div", "__idiv", "__band", "__bor", "__bxor",
+    "__shl", "__shr", "__unm", "__bnot",
+];
+
+pub fn is_bitwise(op: &Arithmetic) -> bool {
+    OPS[op.index() as usize].1 == NONE[0].1
+}
+
 pub const NONE: &'static [(IAddress, FAddress); 1] = &[(inone, fnone)];
 
+// integers, and strings that spell integers, take part in integer arithmetic
+pub fn arith_integer(v: &LuaValue) -> Option<i64> {
+    match v {
+        LuaValue::Integer(i) => Some(*i),
+        LuaValue::String(s) => std::str::from_utf8(s).ok().and_then(parse_integer),
+        _ => None,
+    }
+}
+
 pub fn arith(a: &LuaValue, b: &LuaValue, op: &Arithmetic) -> Option<LuaValue> {
     let iop = OPS[op.index() as usize].0;
     let fop = OPS[op.index() as usize].1;
-    if fop == NONE[0].1 {
+    if is_bitwise(op) {
         // bitwise
         if let Some(x) = a.to_integer() {
             if let Some(y) = b.to_integer() {
@@ -109,10 +131,8 @@ pub fn arith(a: &LuaValue, b: &LuaValue, op: &Arithmetic) -> Option<LuaValue> {
         // arith
         if iop != NONE[0].0 {
             // add,sub,mul,mod,idiv,unm
-            if let LuaValue::Integer(x) = a {
-                if let LuaValue::Integer(y) = b {
-                    return Some(LuaValue::Integer(iop(*x, *y)));
-                }
+            if let (Some(x), Some(y)) = (arith_integer(a), arith_integer(b)) {
+                return Some(LuaValue::Integer(iop(x, y)));
             }
         }
         if let Some(x) = a.to_float() {
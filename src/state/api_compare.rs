@@ -1,16 +1,5 @@
-use crate::api::basic::Comparison;
-
 use super::lua_value::LuaValue;
 
-pub fn compare(a: &LuaValue, b: &LuaValue, op: Comparison) -> Option<bool> {
-    match op {
-        Comparison::LUA_OPEQ => Some(eq(a, b)),
-        Comparison::LUA_OPLT => lt(a, b),
-        Comparison::LUA_OPLE => le(a, b),
-        _ => None,
-    }
-}
-
 macro_rules! cmp {
     ($a:ident $op:tt $b:ident) => {
         match ($a, $b) {
@@ -34,6 +23,7 @@ pub fn eq(a: &LuaValue, b: &LuaValue) -> bool {
                 LuaValue::Boolean(y) => x == y,
                 _ => false,
             },
+            LuaValue::Table(_) | LuaValue::Function(_) => a == b,
             _ => false,
         }
     }
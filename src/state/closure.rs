@@ -1,37 +1,23 @@
-use std::{
-    borrow::Borrow,
-    cell::RefCell,
-    hash::{Hash, Hasher},
-    rc::Rc,
-};
+use std::{borrow::Borrow, cell::RefCell, rc::Rc};
 
-use crate::{api::lua_vm::RustFn, binary::chunk::Prototype};
+use crate::{api::lua_vm::RustFunction, binary::chunk::Prototype};
 
 use super::lua_value::LuaValue;
 
 #[derive(Debug)]
 pub struct Closure {
     proto: Rc<Prototype>,
-    rust_fn: Option<RustFn>,
+    rust_fn: Option<RustFunction>,
     pub upvals: Vec<Rc<RefCell<UpValue>>>,
-    pub address: usize,
 }
 
-impl Hash for Closure {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        self.address.hash(state);
-    }
-}
 impl Closure {
     pub fn new(proto: Rc<Prototype>) -> Self {
-        let mut this = Self {
+        Self {
             proto,
             rust_fn: None,
             upvals: vec![],
-            address: 0,
-        };
-        this.address = std::ptr::addr_of!(this) as usize;
-        this
+        }
     }
 
     pub fn new_lua_closure(proto: Rc<Prototype>) -> Self {
@@ -45,7 +31,7 @@ impl Closure {
         this
     }
 
-    pub fn new_rust_closure(f: RustFn, n_upvals: usize) -> Self {
+    pub fn new_rust_closure(f: RustFunction, n_upvals: usize) -> Self {
         let mut this = Self::new(Rc::new(Prototype::new()));
         this.rust_fn = Some(f);
         this.upvals = Vec::with_capacity(n_upvals);
@@ -56,8 +42,8 @@ impl Closure {
         self.proto.borrow()
     }
 
-    pub fn rust_fn(&self) -> Option<RustFn> {
-        self.rust_fn
+    pub fn rust_fn(&self) -> Option<&RustFunction> {
+        self.rust_fn.as_ref()
     }
 }
 
@@ -0,0 +1,177 @@
+use crate::{
+    binary::chunk::{ConstantType, Prototype},
+    vm::{instruction::Instruction, opcode::*},
+};
+
+use super::api_arith::EVENTS;
+
+const BITRK: isize = 1 << 8; // this bit set means the operand is a constant
+
+// name of the local variable number n (1-based) active at pc
+fn local_name(proto: &Prototype, mut n: usize, pc: usize) -> Option<String> {
+    for var in proto.locvars() {
+        if var.start_pc as usize > pc {
+            break;
+        }
+        if pc < var.end_pc as usize {
+            // variable is active
+            n -= 1;
+            if n == 0 {
+                return Some(var.var_name.clone());
+            }
+        }
+    }
+    None
+}
+
+fn upvalue_name(proto: &Prototype, idx: usize) -> String {
+    match proto.upvalue_names().get(idx) {
+        Some(name) if !name.is_empty() => name.clone(),
+        _ => "?".to_string(),
+    }
+}
+
+fn constant_name(proto: &Prototype, idx: usize) -> Option<String> {
+    match proto.constants().get(idx) {
+        Some(ConstantType::String(s)) => Some(String::from_utf8_lossy(s).into_owned()),
+        _ => None,
+    }
+}
+
+// name of the key RK(c), '?' if it is not a constant string
+fn rk_name(proto: &Prototype, pc: usize, c: isize) -> String {
+    let name = if c & BITRK != 0 {
+        constant_name(proto, (c & !BITRK) as usize)
+    } else {
+        match obj_name(proto, pc, c as usize) {
+            Some(("constant", name)) => Some(name),
+            _ => None,
+        }
+    };
+    name.unwrap_or_else(|| "?".to_string())
+}
+
+// whether op assigns register A
+fn sets_a(op: u8) -> bool {
+    !matches!(
+        op,
+        OP_SETTABUP
+            | OP_SETUPVAL
+            | OP_SETTABLE
+            | OP_JMP
+            | OP_EQ
+            | OP_LT
+            | OP_LE
+            | OP_TEST
+            | OP_RETURN
+            | OP_TFORCALL
+            | OP_SETLIST
+            | OP_EXTRAARG
+    )
+}
+
+// the last instruction before lastpc that changed reg, skipping those that
+// may have been jumped over
+fn find_set_reg(proto: &Prototype, lastpc: usize, reg: usize) -> Option<usize> {
+    let reg = reg as isize;
+    let mut setreg = None;
+    let mut jmptarget = 0;
+    for (pc, &i) in proto.code().iter().enumerate().take(lastpc) {
+        let (a, b, _) = i.abc();
+        let change = match i.opcode() {
+            OP_LOADNIL => a <= reg && reg <= a + b,
+            OP_TFORCALL => reg >= a + 2,
+            OP_CALL | OP_TAILCALL => reg >= a,
+            OP_JMP => {
+                let (_, sbx) = i.a_sbx();
+                let dest = pc as isize + 1 + sbx;
+                // jump is forward and does not skip lastpc
+                if (pc as isize) < dest && dest <= lastpc as isize && dest > jmptarget {
+                    jmptarget = dest;
+                }
+                false
+            }
+            op => sets_a(op) && reg == a,
+        };
+        if change {
+            setreg = if (pc as isize) < jmptarget {
+                None
+            } else {
+                Some(pc)
+            };
+        }
+    }
+    setreg
+}
+
+// describes the value register reg holds at pc, as ("local", "x") or ("global", "print")
+pub fn obj_name(proto: &Prototype, lastpc: usize, reg: usize) -> Option<(&'static str, String)> {
+    if let Some(name) = local_name(proto, reg + 1, lastpc) {
+        return Some(("local", name));
+    }
+
+    // try symbolic execution
+    let pc = find_set_reg(proto, lastpc, reg)?;
+    let i = proto.code()[pc];
+    let (a, b, c) = i.abc();
+    match i.opcode() {
+        OP_MOVE if b < a => obj_name(proto, pc, b as usize),
+        OP_GETTABUP | OP_GETTABLE => {
+            let t = if i.opcode() == OP_GETTABLE {
+                local_name(proto, b as usize + 1, pc)
+            } else {
+                Some(upvalue_name(proto, b as usize))
+            };
+            let name = rk_name(proto, pc, c);
+            if t.as_deref() == Some("_ENV") {
+                Some(("global", name))
+            } else {
+                Some(("field", name))
+            }
+        }
+        OP_GETUPVAL => Some(("upvalue", upvalue_name(proto, b as usize))),
+        OP_LOADK | OP_LOADKX => {
+            let idx = if i.opcode() == OP_LOADK {
+                i.a_bx().1
+            } else {
+                proto.code()[pc + 1].ax()
+            };
+            constant_name(proto, idx as usize).map(|name| ("constant", name))
+        }
+        OP_SELF => Some(("method", rk_name(proto, pc, c))),
+        _ => None,
+    }
+}
+
+// describes the function called by the instruction at pc
+pub fn func_name(proto: &Prototype, pc: usize) -> Option<(&'static str, String)> {
+    let i = *proto.code().get(pc)?;
+    let event = match i.opcode() {
+        OP_CALL | OP_TAILCALL => return obj_name(proto, pc, i.abc().0 as usize),
+        OP_TFORCALL => return Some(("for iterator", "for iterator".to_string())),
+        // other instructions can do calls through metamethods
+        OP_SELF | OP_GETTABUP | OP_GETTABLE => "index",
+        OP_SETTABUP | OP_SETTABLE => "newindex",
+        op @ OP_ADD..=OP_BNOT => &EVENTS[(op - OP_ADD) as usize][2..],
+        OP_LEN => "len",
+        OP_CONCAT => "concat",
+        OP_EQ => "eq",
+        OP_LT => "lt",
+        OP_LE => "le",
+        _ => return None,
+    };
+    Some(("metamethod", event.to_string()))
+}
+
+// describes the operand of the instruction at pc that holds a bad value
+pub fn var_info(proto: &Prototype, pc: usize) -> Option<(&'static str, String)> {
+    let i = *proto.code().get(pc)?;
+    let (a, b, _) = i.abc();
+    match i.opcode() {
+        OP_CALL | OP_TAILCALL | OP_SETTABLE => obj_name(proto, pc, a as usize),
+        OP_GETTABLE | OP_SELF => obj_name(proto, pc, b as usize),
+        OP_GETTABUP => Some(("upvalue", upvalue_name(proto, b as usize))),
+        OP_SETTABUP => Some(("upvalue", upvalue_name(proto, a as usize))),
+        _ => None,
+    }
+}
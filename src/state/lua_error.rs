@@ -0,0 +1,48 @@
+use std::fmt;
+
+use crate::api::basic::{LUA_ERRMEM, LUA_ERRRUN};
+
+use super::lua_value::LuaValue;
+
+// an error raised by Lua code or a Rust function, carrying the error object
+#[derive(Debug, Clone)]
+pub struct LuaError {
+    status: u8,
+    value: LuaValue,
+}
+
+impl LuaError {
+    pub fn new(status: u8, value: LuaValue) -> Self {
+        Self { status, value }
+    }
+
+    pub fn runtime(msg: String) -> Self {
+        Self::new(LUA_ERRRUN, LuaValue::from(msg))
+    }
+
+    // raised when a buffer cannot be allocated
+    pub fn memory() -> Self {
+        Self::new(LUA_ERRMEM, LuaValue::from("not enough memory"))
+    }
+
+    pub fn status(&self) -> u8 {
+        self.status
+    }
+
+    pub fn into_value(self) -> LuaValue {
+        self.value
+    }
+}
+
+impl fmt::Display for LuaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.value {
+            LuaValue::String(s) => write!(f, "{}", String::from_utf8_lossy(s)),
+            LuaValue::Integer(i) => write!(f, "{}", i),
+            LuaValue::Number(n) => write!(f, "{}", crate::math::number::float_to_str(*n)),
+            v => write!(f, "(error object is a {} value)", v.type_id().name()),
+        }
+    }
+}
+
+impl std::error::Error for LuaError {}
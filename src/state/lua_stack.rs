@@ -1,6 +1,6 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
-use crate::api::basic::LUA_REGISTRYINDEX;
+use crate::api::{basic::LUA_REGISTRYINDEX, lua_vm::RustKFn};
 
 use super::{
     closure::{Closure, UpValue},
@@ -12,9 +12,22 @@ pub struct LuaStack {
     pub slot: Vec<Rc<RefCell<LuaValue>>>,
     pub registry: LuaValue,
     pub closure: Rc<RefCell<Closure>>,
-    pub openuvs: Vec<Rc<RefCell<UpValue>>>,
+    pub openuvs: HashMap<usize, Rc<RefCell<UpValue>>>, // keyed by register
     pub varargs: Vec<Rc<RefCell<LuaValue>>>,
     pub pc: isize,
+    pub n_results: isize,            // number of results the caller expects
+    pub k: Option<(RustKFn, isize)>, // continuation of a Rust function, and its context
+    pub pcall: Option<PendingPCall>, // the yieldable pcall this Rust function waits on
+    pub lt_for_le: bool,             // the pending '__lt' call stands in for '__le'
+    pub is_tail: bool,               // called by a tail call, its caller's frame is gone
+}
+
+// a call made with pcall_k, whose caller no longer is on the Rust stack to
+// catch the errors of its callee
+#[derive(Debug)]
+pub struct PendingPCall {
+    pub func_idx: isize,           // where the called function sat
+    pub handler: Option<LuaValue>, // message handler
 }
 
 impl LuaStack {
@@ -23,9 +36,14 @@ impl LuaStack {
             slot: Vec::with_capacity(size),
             registry,
             closure,
-            openuvs: Vec::with_capacity(10),
+            openuvs: HashMap::new(),
             varargs: Vec::with_capacity(10),
             pc: 0,
+            n_results: 0,
+            k: None,
+            pcall: None,
+            lt_for_le: false,
+            is_tail: false,
         }
     }
 
@@ -170,6 +188,20 @@ impl LuaStack {
         }
     }
 
+    // closes the open upvalues of registers >= level, which keep a copy of
+    // the value and no longer see the register
+    pub fn close_upvalues(&mut self, level: usize) {
+        self.openuvs.retain(|&idx, uv| {
+            if idx < level {
+                return true;
+            }
+            let mut uv = uv.borrow_mut();
+            let val = uv.val.borrow().clone();
+            uv.val = val.to_ptr();
+            false
+        });
+    }
+
     pub fn reverse(&mut self, mut from: usize, mut to: usize) {
         while from < to {
             self.slot.swap(from, to);
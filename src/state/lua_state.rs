@@ -1,52 +1,93 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, fmt::Write, rc::Rc};
 
 use crate::{
     api::{
-        basic::{Arithmetic, BasicType, Comparison, LUA_REGISTRYINDEX},
-        lua_vm::{LuaAPI, LuaVM, RustFn},
+        basic::{
+            Arithmetic, BasicType, Comparison, LUA_ERRERR, LUA_ERRRUN, LUA_ERRSYNTAX,
+            LUAI_MAXCCALLS, LUAI_MAXSTACK, LUA_MINSTACK, LUA_MULTRET, LUA_OK, LUA_REGISTRYINDEX, LUA_YIELD,
+        },
+        lua_vm::{LuaAPI, LuaVM, RustFunction, RustKFn},
     },
     binary::{
         self,
-        chunk::{ConstantType, Prototype, Upvalue},
+        chunk::{ConstantType, Prototype, Upvalue, LUA_SIGNATURE},
+    },
+    compiler::{self, lexer::chunk_id},
+    math::{number, parser},
+    stdlib,
+    vm::{
+        instruction::Instruction,
+        opcode::OP_RETURN,
     },
-    vm::instruction::Instruction,
 };
 
 use super::{
-    api_compare,
+    api_arith, api_compare,
     closure::{Closure, UpValue},
-    lua_stack::LuaStack,
-    lua_table::{self, new_table},
+    lua_debug,
+    lua_error::LuaError,
+    lua_stack::{LuaStack, PendingPCall},
+    lua_table::{self, new_table, LuaTable},
+    lua_thread::{LuaThread, ThreadStatus},
     lua_value::LuaValue,
     util::MyVec,
 };
 
+const LUA_RIDX_MAINTHREAD: LuaValue =
+    LuaValue::Integer(crate::api::basic::LUA_RIDX_MAINTHREAD as i64);
 const LUA_RIDX_GLOBALS: LuaValue = LuaValue::Integer(crate::api::basic::LUA_RIDX_GLOBALS as i64);
 
+/* limit for table tag-method chains (to avoid loops) */
+const MAXTAGLOOP: usize = 2000;
+
+/* limit for the number of nested calls */
+const LUAI_MAXFRAMES: usize = 200000;
+
+/* size of the first and second parts of a long traceback */
+const LEVELS1: usize = 10;
+const LEVELS2: usize = 11;
+
+fn metatable_key(val: &LuaValue) -> LuaValue {
+    LuaValue::from(format!("_MT{}", val.type_id().index()))
+}
+
 #[derive(Debug)]
 pub struct LuaState {
     registry: LuaValue,
-    frames: Vec<LuaStack>,
+    frames: Vec<LuaStack>,          // frames of the running thread
+    thread: Rc<RefCell<LuaThread>>, // the running thread
+    nny: usize,                     // number of non-yieldable calls of the running thread
+    n_ccalls: usize,                // number of nested Rust-level calls, across threads
 }
 
 impl LuaState {
     pub fn new() -> Self {
         let registry = lua_table::new_table(0, 0);
+        let main_thread = Rc::new(RefCell::new(LuaThread::new(vec![])));
+        main_thread.borrow_mut().status = ThreadStatus::Running;
         if let LuaValue::Table(t) = &registry {
             let globals = lua_table::new_table(0, 0);
+            t.borrow_mut().put(LUA_RIDX_MAINTHREAD, LuaValue::Thread(main_thread.clone()));
             t.borrow_mut().put(LUA_RIDX_GLOBALS, globals);
         }
 
-        let fake_proto = Rc::new(Prototype::new());
-        let fake_closure = Rc::new(RefCell::new(Closure::new(fake_proto)));
-        let fake_frame = LuaStack::new(20, registry.clone(), fake_closure);
-
+        let base_frame = Self::base_frame(&registry);
         Self {
             registry,
-            frames: vec![fake_frame],
+            frames: vec![base_frame],
+            thread: main_thread,
+            nny: 0,
+            n_ccalls: 0,
         }
     }
 
+    // the frame at the bottom of every thread, it runs no function
+    fn base_frame(registry: &LuaValue) -> LuaStack {
+        let fake_proto = Rc::new(Prototype::new());
+        let fake_closure = Rc::new(RefCell::new(Closure::new(fake_proto)));
+        LuaStack::new(20, registry.clone(), fake_closure)
+    }
+
     fn stack(&self) -> &LuaStack {
         self.frames.last().unwrap()
     }
@@ -74,6 +115,9 @@ impl LuaAPI for LuaState {
     }
 
     fn check_stack(&mut self, n: usize) -> bool {
+        if self.stack().top() as usize + n > LUAI_MAXSTACK {
+            return false;
+        }
         self.stack_mut().check(n);
         true
     }
@@ -92,8 +136,9 @@ impl LuaAPI for LuaState {
     }
 
     fn push_value(&mut self, idx: isize) {
-        let val = self.stack().get(idx);
-        self.stack_mut().push(val);
+        // push a copy, a shared cell would let the two slots write through each other
+        let val = self.stack().get(idx).borrow().clone();
+        self.stack_mut().push(val.to_ptr());
     }
 
     fn replace(&mut self, idx: isize) {
@@ -142,17 +187,7 @@ impl LuaAPI for LuaState {
 
     /* access functions (stack() -> rust) */
     fn type_name_str(&self, tp: BasicType) -> &str {
-        match tp {
-            BasicType::LUA_TNONE => "no value",
-            BasicType::LUA_TNIL => "nil",
-            BasicType::LUA_TBOOLEAN => "boolean",
-            BasicType::LUA_TNUMBER => "number",
-            BasicType::LUA_TSTRING => "string",
-            BasicType::LUA_TTABLE => "table",
-            BasicType::LUA_TFUNCTION => "function",
-            BasicType::LUA_TTHREAD => "thread",
-            _ => "userdata",
-        }
+        tp.name()
     }
 
     fn type_enum_id(&self, idx: isize) -> BasicType {
@@ -227,7 +262,7 @@ impl LuaAPI for LuaState {
     }
 
     fn to_integer(&self, idx: isize) -> i64 {
-        self.to_integerx(idx).unwrap()
+        self.to_integerx(idx).unwrap_or(0)
     }
 
     fn to_integerx(&self, idx: isize) -> Option<i64> {
@@ -238,7 +273,7 @@ impl LuaAPI for LuaState {
     }
 
     fn to_number(&self, idx: isize) -> f64 {
-        self.to_numberx(idx).unwrap()
+        self.to_numberx(idx).unwrap_or(0.0)
     }
 
     fn to_numberx(&self, idx: isize) -> Option<f64> {
@@ -249,31 +284,64 @@ impl LuaAPI for LuaState {
     }
 
     fn to_string(&self, idx: isize) -> String {
-        self.to_stringx(idx).unwrap()
+        self.to_stringx(idx).unwrap_or_default()
     }
 
+    // the string at idx, with invalid UTF-8 replaced by U+FFFD
     fn to_stringx(&self, idx: isize) -> Option<String> {
+        self.to_bytesx(idx).map(|s| String::from_utf8_lossy(&s).into_owned())
+    }
+
+    fn to_bytes(&self, idx: isize) -> Vec<u8> {
+        self.to_bytesx(idx).unwrap_or_default()
+    }
+
+    fn to_bytesx(&self, idx: isize) -> Option<Vec<u8>> {
         let rc_val = self.stack().get(idx);
         let val = &*rc_val.borrow();
 
         match val {
             LuaValue::String(s) => Some(s.clone()),
-            LuaValue::Number(n) => Some(n.to_string()),
-            LuaValue::Integer(i) => Some(i.to_string()),
+            LuaValue::Number(n) => Some(number::float_to_str(*n).into_bytes()),
+            LuaValue::Integer(i) => Some(i.to_string().into_bytes()),
             _ => None,
         }
     }
 
-    fn to_rust_function(&self, idx: isize) -> Option<RustFn> {
+    fn to_rust_function(&self, idx: isize) -> Option<RustFunction> {
         let rc_val = self.stack().get(idx);
         let val = &*rc_val.borrow();
 
         match val {
-            LuaValue::Function(c) => c.borrow().rust_fn(),
+            LuaValue::Function(c) => c.borrow().rust_fn().cloned(),
             _ => None,
         }
     }
 
+    // the address of a table, function or thread, 0 for other values
+    fn to_pointer(&self, idx: isize) -> usize {
+        let rc_val = self.stack().get(idx);
+        let val = &*rc_val.borrow();
+
+        match val {
+            LuaValue::Table(t) => Rc::as_ptr(t) as usize,
+            LuaValue::Function(c) => Rc::as_ptr(c) as usize,
+            LuaValue::Thread(t) => Rc::as_ptr(t) as usize,
+            _ => 0,
+        }
+    }
+
+    fn raw_len(&self, idx: isize) -> usize {
+        let rc_val = self.stack().get(idx);
+        let val = &*rc_val.borrow();
+
+        match val {
+            LuaValue::String(s) => s.len(),
+            LuaValue::Table(t) => t.borrow().len(),
+            _ => 0,
+        }
+    }
+
     /* push functions (rust -> stack()) */
     fn push_nil(&mut self) {
         self.stack_mut().push(LuaValue::Nil.to_ptr());
@@ -292,10 +360,14 @@ impl LuaAPI for LuaState {
     }
 
     fn push_string(&mut self, s: String) {
+        self.stack_mut().push(LuaValue::from(s).to_ptr());
+    }
+
+    fn push_bytes(&mut self, s: Vec<u8>) {
         self.stack_mut().push(LuaValue::String(s).to_ptr());
     }
 
-    fn push_rust_fn(&mut self, f: RustFn) {
+    fn push_rust_fn(&mut self, f: RustFunction) {
         self.stack_mut().push(LuaValue::new_rust_fn(f, 0).to_ptr());
     }
 
@@ -306,98 +378,226 @@ impl LuaAPI for LuaState {
         }
     }
 
-    fn push_rust_closure(&mut self, f: RustFn, n: isize) {
+    fn push_rust_closure(&mut self, f: RustFunction, n: isize) {
         let f = LuaValue::new_rust_fn(f, n as usize);
         if let LuaValue::Function(c) = &f {
-            for i in (0..n).rev() {
-                let val = self.stack_mut().pop();
-                // c.borrow_mut().upvals[i as usize] = UpValue { val };
-            }
+            let vals = self.stack_mut().pop_n(n as usize);
+            c.borrow_mut().upvals = vals
+                .into_iter()
+                .map(|val| Rc::new(RefCell::new(UpValue { val })))
+                .collect();
         }
 
         self.stack_mut().push(f.to_ptr());
     }
 
-    fn arith(&mut self, op: Arithmetic) {
-        if op != Arithmetic::LUA_OPUNM && op != Arithmetic::LUA_OPBNOT {
-            let b = {
-                let rc_b = self.stack_mut().pop();
-                let b = rc_b.borrow();
-                b.to_owned()
-            };
-
-            let a = {
-                let rc_a = self.stack_mut().pop();
-                let a = rc_a.borrow();
-                a.to_owned()
-            };
-
-            if let Some(result) = super::api_arith::arith(&a, &b, &op) {
-                self.stack_mut().push(result.to_ptr());
-                return;
-            }
+    fn arith(&mut self, op: Arithmetic) -> Result<(), LuaError> {
+        let b = self.stack_mut().pop().borrow().clone();
+        let a = if op != Arithmetic::LUA_OPUNM && op != Arithmetic::LUA_OPBNOT {
+            self.stack_mut().pop().borrow().clone()
         } else {
-            let a = {
-                let rc_a = self.stack_mut().pop();
-                let a = rc_a.borrow();
-                a.to_owned()
-            };
+            b.clone()
+        };
 
-            if let Some(result) = super::api_arith::arith(&a, &a, &op) {
-                self.stack_mut().push(result.to_ptr());
-                return;
+        let ints = (api_arith::arith_integer(&a), api_arith::arith_integer(&b));
+        if let (Some(_), Some(0)) = ints {
+            match op {
+                Arithmetic::LUA_OPMOD => {
+                    return Err(self.runtime_error("attempt to perform 'n%0'".to_string()))
+                }
+                Arithmetic::LUA_OPIDIV => {
+                    return Err(self.runtime_error("attempt to perform 'n//0'".to_string()))
+                }
+                _ => {}
             }
         }
-        panic!("arithmetic error!");
+
+        if let Some(result) = api_arith::arith(&a, &b, &op) {
+            self.stack_mut().push(result.to_ptr());
+            return Ok(());
+        }
+
+        let event = api_arith::EVENTS[op.index() as usize];
+        if let Some(result) = self.call_bin_metamethod(&a, &b, event)? {
+            self.stack_mut().push(result.to_ptr());
+            return Ok(());
+        }
+
+        let msg = if api_arith::is_bitwise(&op) {
+            let is_number = |v: &LuaValue| matches!(v, LuaValue::Integer(_) | LuaValue::Number(_));
+            if is_number(&a) && is_number(&b) {
+                "number has no integer representation".to_string()
+            } else {
+                let bad = if is_number(&a) { &b } else { &a };
+                format!(
+                    "attempt to perform bitwise operation on a {} value",
+                    self.type_name_str(bad.type_id())
+                )
+            }
+        } else {
+            let bad = if a.to_float().is_some() { &b } else { &a };
+            format!(
+                "attempt to perform arithmetic on a {} value",
+                self.type_name_str(bad.type_id())
+            )
+        };
+        Err(self.runtime_error(msg))
     }
 
-    fn compare(&self, idx1: isize, idx2: isize, op: Comparison) -> bool {
+    fn compare(&mut self, idx1: isize, idx2: isize, op: Comparison) -> Result<bool, LuaError> {
         if !self.stack().is_valid(idx1) || !self.stack().is_valid(idx2) {
-            false
-        } else {
-            let rc_a = self.stack().get(idx1);
-            let a = rc_a.borrow();
+            return Ok(false);
+        }
 
-            let rc_b = self.stack().get(idx2);
-            let b = rc_b.borrow();
+        let a = self.stack().get(idx1).borrow().clone();
+        let b = self.stack().get(idx2).borrow().clone();
+        let result = match op {
+            Comparison::LUA_OPEQ => {
+                if api_compare::eq(&a, &b) {
+                    Some(true)
+                } else if let (LuaValue::Table(_), LuaValue::Table(_)) = (&a, &b) {
+                    self.call_bin_metamethod(&a, &b, "__eq")?
+                        .map(|v| v.to_boolean())
+                        .or(Some(false))
+                } else {
+                    Some(false)
+                }
+            }
+            Comparison::LUA_OPLT => match api_compare::lt(&a, &b) {
+                Some(result) => Some(result),
+                None => self
+                    .call_bin_metamethod(&a, &b, "__lt")?
+                    .map(|v| v.to_boolean()),
+            },
+            Comparison::LUA_OPLE => match api_compare::le(&a, &b) {
+                Some(result) => Some(result),
+                None => match self.call_bin_metamethod(&a, &b, "__le")? {
+                    Some(v) => Some(v.to_boolean()),
+                    // a <= b is not (b < a)
+                    None => {
+                        let frame = self.frames.len() - 1;
+                        self.frames[frame].lt_for_le = true;
+                        let result = self.call_bin_metamethod(&b, &a, "__lt")?;
+                        self.frames[frame].lt_for_le = false;
+                        result.map(|v| !v.to_boolean())
+                    }
+                },
+            },
+        };
 
-            if let Some(result) = api_compare::compare(&*a, &*b, op) {
-                return result;
+        match result {
+            Some(result) => Ok(result),
+            None => {
+                let t1 = self.type_name_str(a.type_id());
+                let t2 = self.type_name_str(b.type_id());
+                let msg = if t1 == t2 {
+                    format!("attempt to compare two {} values", t1)
+                } else {
+                    format!("attempt to compare {} with {}", t1, t2)
+                };
+                Err(self.runtime_error(msg))
             }
-            panic!("comparison error!")
         }
     }
 
-    fn len(&mut self, idx: isize) {
-        let rc_val = self.stack().get(idx);
-        let val = &*rc_val.borrow();
+    fn raw_equal(&self, idx1: isize, idx2: isize) -> bool {
+        if !self.stack().is_valid(idx1) || !self.stack().is_valid(idx2) {
+            return false;
+        }
 
-        let len = match val {
-            LuaValue::String(s) => s.len(),
-            LuaValue::Table(t) => t.borrow().len(),
-            _ => panic!("length error!"),
+        let a = self.stack().get(idx1);
+        let b = self.stack().get(idx2);
+        let result = api_compare::eq(&a.borrow(), &b.borrow());
+        result
+    }
+
+    fn len(&mut self, idx: isize) -> Result<(), LuaError> {
+        let val = self.stack().get(idx).borrow().clone();
+
+        let len = if let LuaValue::String(s) = &val {
+            LuaValue::Integer(s.len() as i64)
+        } else {
+            let mm = self.metafield(&val, "__len");
+            if !mm.is_nil() {
+                self.call_metamethod(mm, vec![val.clone(), val])?
+            } else if let LuaValue::Table(t) = &val {
+                LuaValue::Integer(t.borrow().len() as i64)
+            } else {
+                let msg = format!(
+                    "attempt to get length of a {} value",
+                    self.type_name_str(val.type_id())
+                );
+                return Err(self.runtime_error(msg));
+            }
         };
 
-        self.stack_mut().push(LuaValue::Integer(len as i64).to_ptr());
+        self.stack_mut().push(len.to_ptr());
+        Ok(())
     }
 
-    fn concat(&mut self, n: isize) {
+    fn concat(&mut self, n: isize) -> Result<(), LuaError> {
         if n == 0 {
-            self.stack_mut().push(LuaValue::String(String::new()).to_ptr());
+            self.stack_mut().push(LuaValue::String(Vec::new()).to_ptr());
         } else if n >= 2 {
             for _ in 1..n {
                 if self.is_string(-1) && self.is_string(-2) {
-                    let s2 = self.to_string(-1);
-                    let mut s1 = self.to_string(-2);
-                    s1.push_str(&s2);
+                    let s2 = self.to_bytes(-1);
+                    let mut s1 = self.to_bytes(-2);
+                    s1.extend_from_slice(&s2);
                     self.stack_mut().pop();
                     self.stack_mut().pop();
                     self.stack_mut().push(LuaValue::String(s1).to_ptr());
-                } else {
-                    panic!("concatenation error!");
+                    continue;
+                }
+
+                let b = self.stack_mut().pop().borrow().clone();
+                let a = self.stack_mut().pop().borrow().clone();
+                if let Some(result) = self.call_bin_metamethod(&a, &b, "__concat")? {
+                    self.stack_mut().push(result.to_ptr());
+                    continue;
                 }
+
+                let is_string = |v: &LuaValue| {
+                    matches!(v, LuaValue::String(_) | LuaValue::Integer(_) | LuaValue::Number(_))
+                };
+                let bad = if is_string(&a) { &b } else { &a };
+                let msg = format!(
+                    "attempt to concatenate a {} value",
+                    self.type_name_str(bad.type_id())
+                );
+                return Err(self.runtime_error(msg));
             }
         }
+        Ok(())
+    }
+
+    fn next(&mut self, idx: isize) -> Result<bool, LuaError> {
+        let t = self.stack().get(idx).borrow().clone();
+        let LuaValue::Table(tbl) = t else {
+            let msg = format!("table expected, got {}", t.type_id().name());
+            return Err(self.runtime_error(msg));
+        };
+        let key = self.stack_mut().pop().borrow().clone();
+        let entry = tbl.borrow().next(&key)?;
+        if let Some((k, v)) = entry {
+            self.stack_mut().push(k.to_ptr());
+            self.stack_mut().push(v.to_ptr());
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    // pushes the number s converts to, keeping integers integers
+    fn string_to_number(&mut self, s: &str) -> bool {
+        let val = match parser::parse_integer(s) {
+            Some(i) => LuaValue::Integer(i),
+            None => match parser::parse_float(s) {
+                Some(f) => LuaValue::Number(f),
+                None => return false,
+            },
+        };
+        self.stack_mut().push(val.to_ptr());
+        true
     }
 
     /* get functions (Lua -> stack()) */
@@ -409,100 +609,164 @@ impl LuaAPI for LuaState {
         self.stack_mut().push(new_table(n_arr, n_rec).to_ptr());
     }
 
-    fn table(&mut self, idx: isize) -> BasicType {
-        let rc_t = self.stack().get(idx);
-        let t = &*rc_t.borrow();
-
-        let rc_k = self.stack_mut().pop();
-        let k = rc_k.borrow().to_owned();
+    fn table(&mut self, idx: isize) -> Result<BasicType, LuaError> {
+        let t = self.stack().get(idx).borrow().clone();
+        let k = self.stack_mut().pop().borrow().clone();
+        self.get_table_impl(&t, &k)
+    }
 
-        self.get_table_impl(t, &k)
+    fn field(&mut self, idx: isize, k: &str) -> Result<BasicType, LuaError> {
+        let t = self.stack().get(idx).borrow().clone();
+        let k = LuaValue::from(k);
+        self.get_table_impl(&t, &k)
     }
 
-    fn field(&mut self, idx: isize, k: &str) -> BasicType {
-        let rc_t = self.stack().get(idx);
-        let t = &*rc_t.borrow();
+    fn i(&mut self, idx: isize, i: i64) -> Result<BasicType, LuaError> {
+        let t = self.stack().get(idx).borrow().clone();
+        let k = LuaValue::Integer(i);
+        self.get_table_impl(&t, &k)
+    }
 
-        let k = LuaValue::String(k.to_string());
-        // TODO
-        self.get_table_impl(t, &k)
+    fn global(&mut self, name: &str) -> Result<BasicType, LuaError> {
+        let t = self.globals();
+        let k = LuaValue::from(name);
+        self.get_table_impl(&t, &k)
     }
 
-    fn i(&mut self, idx: isize, i: i64) -> BasicType {
-        let rc_t = self.stack().get(idx);
-        let t = &*rc_t.borrow();
+    fn raw_get(&mut self, idx: isize) -> Result<BasicType, LuaError> {
+        let t = self.stack().get(idx).borrow().clone();
+        let k = self.stack_mut().pop().borrow().clone();
+        self.raw_get_impl(&t, &k)
+    }
 
+    fn raw_get_i(&mut self, idx: isize, i: i64) -> Result<BasicType, LuaError> {
+        let t = self.stack().get(idx).borrow().clone();
         let k = LuaValue::Integer(i);
-        self.get_table_impl(t, &k)
+        self.raw_get_impl(&t, &k)
     }
 
-    fn global(&mut self, name: &str) -> BasicType {
-        if let LuaValue::Table(r) = &self.registry {
-            let t = r.borrow().get(&LUA_RIDX_GLOBALS);
-            let k = LuaValue::String(name.to_string()); // TODO
-            self.get_table_impl(&t, &k)
-        } else {
-            BasicType::LUA_TNONE
+    fn get_metatable(&mut self, idx: isize) -> bool {
+        let val = self.stack().get(idx).borrow().clone();
+        match self.metatable_of(&val) {
+            Some(mt) => {
+                // a '__metatable' field hides the real metatable
+                let protected = mt.borrow().get(&LuaValue::from("__metatable"));
+                if protected.is_nil() {
+                    self.stack_mut().push(LuaValue::Table(mt).to_ptr());
+                } else {
+                    self.stack_mut().push(protected.to_ptr());
+                }
+                true
+            }
+            None => false,
         }
     }
 
-    /* set functions (stack() -> Lua) */
-    fn set_table(&mut self, idx: isize) {
-        let rc_t = self.stack().get(idx);
-        let t = &*rc_t.borrow();
-
-        let rc_v = self.stack_mut().pop();
-        let v = rc_v.borrow().to_owned();
-
-        let rc_k = self.stack_mut().pop();
-        let k = rc_k.borrow().to_owned();
-
-        LuaState::set_table_impl(t, k, v);
+    // pushes field e of the metatable of the value at idx, unless it is nil
+    fn get_metafield(&mut self, idx: isize, e: &str) -> BasicType {
+        let val = self.stack().get(idx).borrow().clone();
+        let field = self.metafield(&val, e);
+        let tp = field.type_id();
+        if tp != BasicType::LUA_TNIL {
+            self.stack_mut().push(field.to_ptr());
+        }
+        tp
     }
 
-    fn set_field(&mut self, idx: isize, k: &str) {
-        let rc_t = self.stack().get(idx);
-        let t = &*rc_t.borrow();
+    /* set functions (stack() -> Lua) */
+    fn set_table(&mut self, idx: isize) -> Result<(), LuaError> {
+        let t = self.stack().get(idx).borrow().clone();
+        let v = self.stack_mut().pop().borrow().clone();
+        let k = self.stack_mut().pop().borrow().clone();
+        self.set_table_impl(&t, k, v, false)
+    }
 
-        let rc_v = self.stack_mut().pop();
-        let v = rc_v.borrow().to_owned();
+    fn set_field(&mut self, idx: isize, k: &str) -> Result<(), LuaError> {
+        let t = self.stack().get(idx).borrow().clone();
+        let v = self.stack_mut().pop().borrow().clone();
+        let k = LuaValue::from(k);
+        self.set_table_impl(&t, k, v, false)
+    }
 
-        let k = LuaValue::String(k.to_string());
-        // TODO
-        LuaState::set_table_impl(t, k, v);
+    fn set_i(&mut self, idx: isize, i: i64) -> Result<(), LuaError> {
+        let t = self.stack().get(idx).borrow().clone();
+        let v = self.stack_mut().pop().borrow().clone();
+        let k = LuaValue::Integer(i);
+        self.set_table_impl(&t, k, v, false)
     }
 
-    fn set_i(&mut self, idx: isize, i: i64) {
-        let rc_t = self.stack().get(idx);
-        let t = &*rc_t.borrow();
+    fn set_global(&mut self, name: &str) -> Result<(), LuaError> {
+        let t = self.globals();
+        let v = self.stack_mut().pop().borrow().clone();
+        let k = LuaValue::from(name);
+        self.set_table_impl(&t, k, v, false)
+    }
 
-        let rc_v = self.stack_mut().pop();
-        let v = rc_v.borrow().to_owned();
+    fn raw_set(&mut self, idx: isize) -> Result<(), LuaError> {
+        let t = self.stack().get(idx).borrow().clone();
+        let v = self.stack_mut().pop().borrow().clone();
+        let k = self.stack_mut().pop().borrow().clone();
+        self.set_table_impl(&t, k, v, true)
+    }
 
+    fn raw_set_i(&mut self, idx: isize, i: i64) -> Result<(), LuaError> {
+        let t = self.stack().get(idx).borrow().clone();
+        let v = self.stack_mut().pop().borrow().clone();
         let k = LuaValue::Integer(i);
-        LuaState::set_table_impl(t, k, v);
+        self.set_table_impl(&t, k, v, true)
     }
 
-    fn set_global(&mut self, name: &str) {
-        if let LuaValue::Table(r) = &self.registry {
-            let t = r.borrow().get(&LUA_RIDX_GLOBALS);
-
-            let rc_v = self.stack_mut().pop();
-            let v = rc_v.borrow().to_owned();   
+    fn set_metatable(&mut self, idx: isize) -> Result<(), LuaError> {
+        let val = self.stack().get(idx).borrow().clone();
+        let mt = match self.stack_mut().pop().borrow().clone() {
+            LuaValue::Nil => None,
+            LuaValue::Table(mt) => Some(mt),
+            _ => return Err(self.runtime_error("table expected".to_string())),
+        };
 
-            let k = LuaValue::String(name.to_string()); // TODO
-            LuaState::set_table_impl(&t, k, v);
+        if let Some(old) = self.metatable_of(&val) {
+            let key = LuaValue::from("__metatable");
+            if !old.borrow().get(&key).is_nil() {
+                let msg = "cannot change a protected metatable".to_string();
+                return Err(self.runtime_error(msg));
+            }
         }
+        self.set_metatable_of(&val, mt);
+        Ok(())
     }
 
-    fn register(&mut self, name: &str, f: RustFn) {
-        self.push_rust_fn(f);
-        self.set_global(name);
+    // sets the global raw, so registering never runs metamethods
+    fn register(&mut self, name: &str, f: RustFunction) {
+        if let LuaValue::Table(g) = self.globals() {
+            let k = LuaValue::from(name);
+            g.borrow_mut().put(k, LuaValue::new_rust_fn(f, 0));
+        }
     }
 
     /* 'load' and 'call' functions (load and run Lua code) */
     fn load(&mut self, chunk: Vec<u8>, chunk_name: &str, mode: &str) -> u8 {
-        let proto = binary::un_dump(chunk);
+        let is_binary = chunk.starts_with(LUA_SIGNATURE);
+        let kind = if is_binary { "binary" } else { "text" };
+        if !mode.contains(&kind[..1]) {
+            self.push_string(format!("attempt to load a {} chunk (mode is '{}')", kind, mode));
+            return LUA_ERRSYNTAX;
+        }
+
+        let result = if is_binary {
+            binary::un_dump(&chunk).map_err(|e| {
+                let name = chunk_name.strip_prefix(['@', '=']).unwrap_or(chunk_name);
+                format!("{}: {}", name, e)
+            })
+        } else {
+            compiler::compile(&chunk, chunk_name)
+        };
+        let proto = match result {
+            Ok(proto) => proto,
+            Err(msg) => {
+                self.push_string(msg);
+                return LUA_ERRSYNTAX;
+            }
+        };
         let size = proto.upvalues().len();
         let f = LuaValue::new_lua_fn(proto);
 
@@ -520,26 +784,291 @@ impl LuaAPI for LuaState {
 
         self.stack_mut().push(f.to_ptr());
 
-        0 // TODO
+        LUA_OK
     }
 
-    fn call(&mut self, n_args: usize, n_results: isize) {
-        let rc_val = self.stack().get(-(n_args as isize + 1));
-        let val = rc_val.borrow();
+    fn call(&mut self, n_args: usize, n_results: isize) -> Result<(), LuaError> {
+        // the Rust caller cannot be resumed, so nothing it calls may yield
+        self.nny += 1;
+        let result = self.call_yieldable(n_args, n_results);
+        self.nny -= 1;
+        result
+    }
+
+    fn pcall(&mut self, n_args: usize, n_results: isize, msgh: isize) -> u8 {
+        let handler = self.msg_handler(msgh);
+        let n_frames = self.frames.len();
+        let func_idx = self.top() - n_args as isize;
 
-        if let LuaValue::Function(c) = &*val {
-            if c.borrow().rust_fn().is_some() {
-                self.call_rust_closure(n_args, n_results, c.clone())
+        match self.call(n_args, n_results) {
+            Ok(()) => LUA_OK,
+            Err(err) => self.recover(err, n_frames, func_idx, handler),
+        }
+    }
+
+    fn pcall_k(
+        &mut self,
+        n_args: usize,
+        n_results: isize,
+        msgh: isize,
+        ctx: isize,
+        k: RustKFn,
+    ) -> Result<u8, LuaError> {
+        if self.nny > 0 {
+            return Ok(self.pcall(n_args, n_results, msgh));
+        }
+
+        // a yield leaves this frame for k to finish after the resume, and the
+        // errors raised from then on unwind to it
+        let handler = self.msg_handler(msgh);
+        let n_frames = self.frames.len();
+        let func_idx = self.top() - n_args as isize;
+        self.stack_mut().k = Some((k, ctx));
+        self.stack_mut().pcall = Some(PendingPCall {
+            func_idx,
+            handler: handler.clone(),
+        });
+
+        let result = match self.call_yieldable(n_args, n_results) {
+            Err(err) if err.status() == LUA_YIELD => return Err(err),
+            result => result,
+        };
+        let frame = &mut self.frames[n_frames - 1];
+        frame.k = None;
+        frame.pcall = None;
+        match result {
+            Ok(()) => Ok(LUA_OK),
+            Err(err) => Ok(self.recover(err, n_frames, func_idx, handler)),
+        }
+    }
+
+    fn dump(&self, strip: bool) -> Option<Vec<u8>> {
+        let val = self.stack().get(-1);
+        let val = val.borrow();
+        match &*val {
+            LuaValue::Function(c) if c.borrow().rust_fn().is_none() => {
+                Some(binary::dump(c.borrow().proto(), strip))
+            }
+            _ => None,
+        }
+    }
+
+    /* error functions */
+    fn error(&mut self) -> LuaError {
+        let val = self.stack_mut().pop().borrow().clone();
+        LuaError::new(LUA_ERRRUN, val)
+    }
+
+    fn position(&self, level: usize) -> String {
+        if level >= self.frames.len() {
+            return String::new();
+        }
+
+        // Rust functions have no current line
+        let frame = &self.frames[self.frames.len() - 1 - level];
+        let closure = frame.closure.borrow();
+        if closure.rust_fn().is_some() || frame.pc <= 0 {
+            return String::new();
+        }
+
+        let proto = closure.proto();
+        match proto.line_info().get(frame.pc as usize - 1) {
+            Some(line) => format!("{}:{}: ", chunk_id(proto.source()), line),
+            None => String::new(),
+        }
+    }
+
+    fn traceback(&mut self, msg: Option<&str>, level: usize) {
+        let mut out = String::new();
+        if let Some(msg) = msg {
+            out.push_str(msg);
+            out.push('\n');
+        }
+        out.push_str("stack traceback:");
+
+        // frame 0 is the base frame, not a function
+        let last = self.frames.len() - 1;
+        let mut skip = (level..last).len() > LEVELS1 + LEVELS2;
+        let mut lv = level;
+        while lv < last {
+            if skip && lv == level + LEVELS1 {
+                // too many levels, only the first and the last ones are shown
+                out.push_str("\n\t...");
+                lv = last - LEVELS2;
+                skip = false;
+                continue;
+            }
+
+            let idx = last - lv;
+            let frame = &self.frames[idx];
+            let closure = frame.closure.borrow();
+            if closure.rust_fn().is_some() {
+                out.push_str("\n\t[C]: in ");
             } else {
-                self.call_lua_closure(n_args, n_results, c.clone());
+                let proto = closure.proto();
+                let line = match frame.pc {
+                    pc if pc > 0 => proto.line_info().get(pc as usize - 1),
+                    _ => None,
+                };
+                match line {
+                    Some(line) => write!(out, "\n\t{}:{}: in ", chunk_id(proto.source()), line),
+                    None => write!(out, "\n\t{}: in ", chunk_id(proto.source())),
+                }
+                .unwrap();
             }
-        } else {
-            panic!("not function!");
+            drop(closure);
+            out.push_str(&self.frame_name(idx));
+            if frame.is_tail {
+                out.push_str("\n\t(...tail calls...)");
+            }
+            lv += 1;
         }
+
+        self.push_string(out);
+    }
+
+    /* coroutine functions */
+    fn new_thread(&mut self) {
+        let mut frame = Self::base_frame(&self.registry);
+        frame.check(LUA_MINSTACK);
+        let thread = LuaThread::new(vec![frame]);
+        self.stack_mut()
+            .push(LuaValue::Thread(Rc::new(RefCell::new(thread))).to_ptr());
+    }
+
+    fn push_thread(&mut self) -> bool {
+        let thread = LuaValue::Thread(self.thread.clone());
+        self.stack_mut().push(thread.to_ptr());
+        self.is_main_thread()
+    }
+
+    fn xmove(&mut self, idx: isize, n: usize) -> Result<(), LuaError> {
+        let co = self.thread_at(idx)?;
+        if Rc::ptr_eq(&co, &self.thread) {
+            return Ok(());
+        }
+
+        let vals = self.stack_mut().pop_n(n);
+        let mut co = co.borrow_mut();
+        if let Some(frame) = co.frames.last_mut() {
+            frame.push_n(vals, -1);
+        }
+        Ok(())
+    }
+
+    fn resume(&mut self, idx: isize, n_args: usize) -> u8 {
+        let co = match self.thread_at(idx) {
+            Ok(co) => co,
+            Err(err) => {
+                self.stack_mut().push(err.into_value().to_ptr());
+                return LUA_ERRRUN;
+            }
+        };
+        let args = self.stack_mut().pop_n(n_args);
+
+        let msg = {
+            let co = co.borrow();
+            match co.status {
+                // a suspended thread has yielded or still has its body to run
+                ThreadStatus::Suspended if co.frames.len() > 1 || co.frames[0].top() > 0 => None,
+                ThreadStatus::Suspended | ThreadStatus::Dead => Some("cannot resume dead coroutine"),
+                _ => Some("cannot resume non-suspended coroutine"),
+            }
+        };
+        if let Some(msg) = msg {
+            self.push_string(msg.to_string());
+            return LUA_ERRRUN;
+        }
+        if self.n_ccalls >= LUAI_MAXCCALLS {
+            self.push_string("C stack overflow".to_string());
+            return LUA_ERRRUN;
+        }
+
+        let prev = self.switch_thread(co, ThreadStatus::Normal);
+        self.n_ccalls += 1;
+        let result = self.run_thread(args);
+        self.n_ccalls -= 1;
+        let (status, vals, co_status) = match result {
+            Ok(()) => {
+                let n = self.stack().top() as usize;
+                (LUA_OK, self.stack_mut().pop_n(n), ThreadStatus::Dead)
+            }
+            Err(err) if err.status() == LUA_YIELD => {
+                // the yielded values are on the top of the yielding Rust function
+                let n = match err.into_value() {
+                    LuaValue::Integer(n) => n as usize,
+                    _ => 0,
+                };
+                (LUA_YIELD, self.stack_mut().pop_n(n), ThreadStatus::Suspended)
+            }
+            Err(err) => (err.status(), vec![err.into_value().to_ptr()], ThreadStatus::Dead),
+        };
+        self.switch_thread(prev, co_status);
+
+        self.stack_mut().check(vals.len());
+        self.stack_mut().push_n(vals, -1);
+        status
+    }
+
+    fn yield_k(&mut self, n_results: usize, ctx: isize, k: Option<RustKFn>) -> LuaError {
+        if self.is_main_thread() {
+            return self.runtime_error("attempt to yield from outside a coroutine".to_string());
+        }
+        if self.nny > 0 {
+            return self.runtime_error("attempt to yield across a Rust-call boundary".to_string());
+        }
+
+        self.stack_mut().k = k.map(|k| (k, ctx));
+        LuaError::new(LUA_YIELD, LuaValue::Integer(n_results as i64))
+    }
+
+    fn is_yieldable(&self) -> bool {
+        !self.is_main_thread() && self.nny == 0
+    }
+
+    fn thread_status(&self, idx: isize) -> Result<&'static str, LuaError> {
+        Ok(self.thread_at(idx)?.borrow().status.name())
+    }
+
+    /* standard libraries */
+    fn open_libs(&mut self) -> Result<(), LuaError> {
+        stdlib::open_libs(self)
     }
 }
 
 impl LuaVM for LuaState {
+    fn pre_call(&mut self, n_args: usize, n_results: isize) -> Result<bool, LuaError> {
+        let (c, n_args) = self.callee(n_args)?;
+        if c.borrow().rust_fn().is_some() {
+            self.call_rust_closure(n_args, n_results, c)?;
+            Ok(true)
+        } else {
+            self.push_lua_frame(n_args, n_results, c, false)?;
+            Ok(false)
+        }
+    }
+
+    fn pre_tail_call(&mut self, n_args: usize) -> Result<bool, LuaError> {
+        let (c, n_args) = self.callee(n_args)?;
+
+        // a Rust callee is called as usual, so the errors it raises still
+        // point at this frame
+        if c.borrow().rust_fn().is_some() {
+            self.call_rust_closure(n_args, LUA_MULTRET, c)?;
+            return Ok(true);
+        }
+
+        // the callee and its arguments move down to the caller's stack,
+        // in place of the current frame
+        let vals = self.stack_mut().pop_n(n_args + 1);
+        self.stack_mut().close_upvalues(0);
+        let n_results = self.pop_frame().n_results;
+        self.stack_mut().check(vals.len());
+        self.stack_mut().push_n(vals, -1);
+        self.push_lua_frame(n_args, n_results, c, true)?;
+        Ok(false)
+    }
+
     fn pc(&self) -> isize {
         self.stack().pc
     }
@@ -609,22 +1138,17 @@ impl LuaVM for LuaState {
 
             for (i, uv_info) in proto.upvalues().iter().enumerate() {
                 let uv_idx = uv_info.idx as usize;
-                match uv_info.instack.cmp(&1) {
-                    std::cmp::Ordering::Equal => {
-                        if let Some(open_uv) = stack.openuvs.get(i) {
-                            closure.upvals.set(i, open_uv.clone());
-                        } else {
-                            let val = Rc::new(RefCell::new(UpValue {
-                                val: stack.slot[uv_idx].clone(),
-                            }));
-
-                            closure.upvals.set(i, val.clone());
-                            stack.openuvs.set(i, val);
-                        }
-                    }
-                    _ => closure
-                        .upvals
-                        .set(i, stack.closure.borrow_mut().upvals[uv_idx].clone()),
+                if uv_info.instack == 1 {
+                    // closures capturing the same register share its upvalue
+                    let open_uv = stack.openuvs.entry(uv_idx).or_insert_with(|| {
+                        Rc::new(RefCell::new(UpValue {
+                            val: stack.slot[uv_idx].clone(),
+                        }))
+                    });
+                    closure.upvals.set(i, open_uv.clone());
+                } else {
+                    let upval = stack.closure.borrow().upvals[uv_idx].clone();
+                    closure.upvals.set(i, upval);
                 }
             }
         }
@@ -641,44 +1165,394 @@ impl LuaVM for LuaState {
     }
 
     fn close_upvalues(&mut self, a: isize) {
-        let openuvs = &mut self.stack_mut().openuvs;
+        self.stack_mut().close_upvalues(a as usize - 1);
+    }
+}
+
+impl LuaState {
+    fn globals(&self) -> LuaValue {
+        match &self.registry {
+            LuaValue::Table(r) => r.borrow().get(&LUA_RIDX_GLOBALS),
+            _ => LuaValue::Nil,
+        }
+    }
+
+    // tables carry their own metatable, other types share one per type in the registry
+    fn metatable_of(&self, val: &LuaValue) -> Option<Rc<RefCell<LuaTable>>> {
+        if let LuaValue::Table(t) = val {
+            return t.borrow().metatable();
+        }
+
+        if let LuaValue::Table(r) = &self.registry {
+            if let LuaValue::Table(mt) = r.borrow().get(&metatable_key(val)) {
+                return Some(mt);
+            }
+        }
+        None
+    }
 
-        let indices_to_remove: Vec<usize> = openuvs
-            .iter()
-            .enumerate()
-            .filter(|(i, _)| *i as isize >= a - 1)
-            .map(|(i, _)| i)
-            .collect();
+    fn set_metatable_of(&mut self, val: &LuaValue, mt: Option<Rc<RefCell<LuaTable>>>) {
+        if let LuaValue::Table(t) = val {
+            t.borrow_mut().set_metatable(mt);
+        } else if let LuaValue::Table(r) = &self.registry {
+            let mt = mt.map_or(LuaValue::Nil, LuaValue::Table);
+            r.borrow_mut().put(metatable_key(val), mt);
+        }
+    }
 
-        for i in indices_to_remove.iter().rev() {
-            println!("close_upvalues {:?}", openuvs.remove(*i));
+    fn metafield(&self, val: &LuaValue, name: &str) -> LuaValue {
+        match self.metatable_of(val) {
+            Some(mt) => mt.borrow().get(&LuaValue::from(name)),
+            None => LuaValue::Nil,
         }
     }
-}
 
-impl LuaState {
-    fn get_table_impl(&mut self, t: &LuaValue, k: &LuaValue) -> BasicType {
-        if let LuaValue::Table(tbl) = t {
-            let v = tbl.borrow().get(k);
-            let type_id = v.type_id();
-            self.stack_mut().push(v.to_ptr());
-            type_id
+    // a call whose callee may yield, which unwinds the Rust caller; the
+    // caller is finished by a continuation or by finish_call after the resume
+    fn call_yieldable(&mut self, n_args: usize, n_results: isize) -> Result<(), LuaError> {
+        if self.n_ccalls >= LUAI_MAXCCALLS {
+            return Err(self.runtime_error("C stack overflow".to_string()));
+        }
+
+        self.n_ccalls += 1;
+        let result = match self.pre_call(n_args, n_results) {
+            Ok(false) => self.execute(self.frames.len()),
+            result => result.map(|_| ()),
+        };
+        self.n_ccalls -= 1;
+        result
+    }
+
+    // the message handler at index msgh, where 0 means none
+    fn msg_handler(&self, msgh: isize) -> Option<LuaValue> {
+        if msgh != 0 {
+            Some(self.stack().get(msgh).borrow().clone())
+        } else {
+            None
+        }
+    }
+
+    // unwinds the frames above n_frames after an error in a protected call,
+    // leaving the error object where the called function sat
+    fn recover(
+        &mut self,
+        err: LuaError,
+        n_frames: usize,
+        func_idx: isize,
+        handler: Option<LuaValue>,
+    ) -> u8 {
+        // the handler runs before unwinding, so it still sees the frames of the error
+        let (status, value) = match handler {
+            Some(h) => {
+                self.stack_mut().push(h.to_ptr());
+                self.stack_mut().push(err.into_value().to_ptr());
+                match self.call(1, 1) {
+                    Ok(()) => (LUA_ERRRUN, self.stack_mut().pop().borrow().clone()),
+                    Err(_) => (
+                        LUA_ERRERR,
+                        LuaValue::from("error in error handling"),
+                    ),
+                }
+            }
+            None => (err.status(), err.into_value()),
+        };
+
+        for frame in &mut self.frames[n_frames..] {
+            frame.close_upvalues(0);
+        }
+        self.frames.truncate(n_frames);
+        self.stack_mut().set_top(func_idx - 1);
+        self.stack_mut().push(value.to_ptr());
+        status
+    }
+
+    // calls metamethod mm with args and returns its first result; one called
+    // by an instruction of a Lua function may yield
+    fn call_metamethod(&mut self, mm: LuaValue, args: Vec<LuaValue>) -> Result<LuaValue, LuaError> {
+        let n_args = args.len();
+        self.stack_mut().check(n_args + 1);
+        self.stack_mut().push(mm.to_ptr());
+        for arg in args {
+            self.stack_mut().push(arg.to_ptr());
+        }
+        if self.is_lua_frame() {
+            self.call_yieldable(n_args, 1)?;
         } else {
-            panic!("not a table!"); // TODO
+            self.call(n_args, 1)?;
+        }
+        let result = self.stack_mut().pop();
+        let val = result.borrow().clone();
+        Ok(val)
+    }
+
+    // tries the metamethod event of a, then of b
+    fn call_bin_metamethod(
+        &mut self,
+        a: &LuaValue,
+        b: &LuaValue,
+        event: &str,
+    ) -> Result<Option<LuaValue>, LuaError> {
+        let mut mm = self.metafield(a, event);
+        if mm.is_nil() {
+            mm = self.metafield(b, event);
+        }
+
+        if mm.is_nil() {
+            Ok(None)
+        } else {
+            self.call_metamethod(mm, vec![a.clone(), b.clone()]).map(Some)
+        }
+    }
+
+    // an error with the position of the current instruction prepended
+    fn runtime_error(&self, msg: String) -> LuaError {
+        LuaError::runtime(format!("{}{}", self.position(0), msg))
+    }
+
+    // names the variable the current instruction took a bad value from, as " (global 'x')"
+    fn var_info(&self) -> String {
+        let frame = self.stack();
+        let closure = frame.closure.borrow();
+        if closure.rust_fn().is_some() || frame.pc <= 0 {
+            return String::new();
+        }
+
+        match lua_debug::var_info(closure.proto(), frame.pc as usize - 1) {
+            Some((kind, name)) => format!(" ({} '{}')", kind, name),
+            None => String::new(),
+        }
+    }
+
+    // describes the function running in frames[idx] for a traceback
+    fn frame_name(&self, idx: usize) -> String {
+        let closure = self.frames[idx].closure.clone();
+        if let Some(name) = self.global_name(&closure) {
+            return format!("function '{}'", name);
+        }
+
+        // the name the caller used, if it is a Lua function; a tail call
+        // leaves no caller to ask
+        let caller = &self.frames[idx - 1];
+        let called_name = {
+            let caller_closure = caller.closure.borrow();
+            if idx > 1
+                && !self.frames[idx].is_tail
+                && caller_closure.rust_fn().is_none()
+                && caller.pc > 0
+            {
+                lua_debug::func_name(caller_closure.proto(), caller.pc as usize - 1)
+            } else {
+                None
+            }
+        };
+
+        let closure = closure.borrow();
+        match called_name {
+            Some(("global", name)) => format!("function '{}'", name),
+            Some((kind, name)) => format!("{} '{}'", kind, name),
+            None if closure.rust_fn().is_some() => "?".to_string(),
+            None if closure.proto().line_defined() == 0 => "main chunk".to_string(),
+            None => {
+                let proto = closure.proto();
+                format!("function <{}:{}>", chunk_id(proto.source()), proto.line_defined())
+            }
         }
     }
 
-    fn set_table_impl(t: &LuaValue, k: LuaValue, v: LuaValue) {
-        if let LuaValue::Table(tbl) = t {
-            tbl.borrow_mut().put(k, v);
+    // the global name of a function, if it has one
+    fn global_name(&self, closure: &Rc<RefCell<Closure>>) -> Option<String> {
+        let LuaValue::Table(g) = self.globals() else {
+            return None;
+        };
+
+        let g = g.borrow();
+        let mut key = LuaValue::Nil;
+        while let Ok(Some((k, v))) = g.next(&key) {
+            if let (LuaValue::String(name), LuaValue::Function(f)) = (&k, &v) {
+                if Rc::ptr_eq(f, closure) {
+                    return Some(String::from_utf8_lossy(name).into_owned());
+                }
+            }
+            key = k;
+        }
+        None
+    }
+
+    fn get_table_impl(&mut self, t: &LuaValue, k: &LuaValue) -> Result<BasicType, LuaError> {
+        let v = self.index(t.clone(), k)?;
+        let type_id = v.type_id();
+        self.stack_mut().push(v.to_ptr());
+        Ok(type_id)
+    }
+
+    fn raw_get_impl(&mut self, t: &LuaValue, k: &LuaValue) -> Result<BasicType, LuaError> {
+        let v = match t {
+            LuaValue::Table(tbl) => tbl.borrow().get(k),
+            _ => return Err(self.runtime_error("table expected".to_string())),
+        };
+        let type_id = v.type_id();
+        self.stack_mut().push(v.to_ptr());
+        Ok(type_id)
+    }
+
+    // t[k], following '__index' chains
+    fn index(&mut self, mut t: LuaValue, k: &LuaValue) -> Result<LuaValue, LuaError> {
+        for loop_count in 0..MAXTAGLOOP {
+            let tm = if let LuaValue::Table(tbl) = &t {
+                let v = tbl.borrow().get(k);
+                if !v.is_nil() {
+                    return Ok(v);
+                }
+                let tm = self.metafield(&t, "__index");
+                if tm.is_nil() {
+                    return Ok(v);
+                }
+                tm
+            } else {
+                let tm = self.metafield(&t, "__index");
+                if tm.is_nil() {
+                    // only the first value indexed comes from the instruction
+                    let info = if loop_count == 0 {
+                        self.var_info()
+                    } else {
+                        String::new()
+                    };
+                    let msg = format!(
+                        "attempt to index a {} value{}",
+                        self.type_name_str(t.type_id()),
+                        info
+                    );
+                    return Err(self.runtime_error(msg));
+                }
+                tm
+            };
+
+            if let LuaValue::Function(_) = tm {
+                return self.call_metamethod(tm, vec![t, k.clone()]);
+            }
+            t = tm;
+        }
+        Err(self.runtime_error("'__index' chain too long; possible loop".to_string()))
+    }
+
+    fn set_table_impl(
+        &mut self,
+        t: &LuaValue,
+        k: LuaValue,
+        v: LuaValue,
+        raw: bool,
+    ) -> Result<(), LuaError> {
+        if raw {
+            match t {
+                LuaValue::Table(tbl) => {
+                    self.check_key(&k)?;
+                    tbl.borrow_mut().put(k, v);
+                    Ok(())
+                }
+                _ => Err(self.runtime_error("table expected".to_string())),
+            }
         } else {
-            panic!("not a table!");
+            self.new_index(t.clone(), k, v)
+        }
+    }
+
+    fn check_key(&self, k: &LuaValue) -> Result<(), LuaError> {
+        match k {
+            LuaValue::Nil => Err(self.runtime_error("table index is nil".to_string())),
+            LuaValue::Number(n) if n.is_nan() => {
+                Err(self.runtime_error("table index is NaN".to_string()))
+            }
+            _ => Ok(()),
         }
     }
 
-    fn call_rust_closure(&mut self, nargs: usize, nresults: isize, c: Rc<RefCell<Closure>>) {
-        let rust_fn = c.borrow().rust_fn().unwrap();
+    // t[k] = v, following '__newindex' chains
+    fn new_index(&mut self, mut t: LuaValue, k: LuaValue, v: LuaValue) -> Result<(), LuaError> {
+        for loop_count in 0..MAXTAGLOOP {
+            let tm = if let LuaValue::Table(tbl) = &t {
+                // '__newindex' is only consulted for absent keys
+                let absent = tbl.borrow().get(&k).is_nil();
+                let tm = if absent {
+                    self.metafield(&t, "__newindex")
+                } else {
+                    LuaValue::Nil
+                };
+                if tm.is_nil() {
+                    self.check_key(&k)?;
+                    tbl.borrow_mut().put(k, v);
+                    return Ok(());
+                }
+                tm
+            } else {
+                let tm = self.metafield(&t, "__newindex");
+                if tm.is_nil() {
+                    // only the first value indexed comes from the instruction
+                    let info = if loop_count == 0 {
+                        self.var_info()
+                    } else {
+                        String::new()
+                    };
+                    let msg = format!(
+                        "attempt to index a {} value{}",
+                        self.type_name_str(t.type_id()),
+                        info
+                    );
+                    return Err(self.runtime_error(msg));
+                }
+                tm
+            };
+
+            if let LuaValue::Function(_) = tm {
+                self.call_metamethod(tm, vec![t, k, v])?;
+                return Ok(());
+            }
+            t = tm;
+        }
+        Err(self.runtime_error("'__newindex' chain too long; possible loop".to_string()))
+    }
+
+    // the closure called for the value below the n_args arguments, which may
+    // be the '__call' metamethod taking the value as an extra first argument;
+    // a callable '__call' metamethod is resolved in turn
+    fn callee(&mut self, mut n_args: usize) -> Result<(Rc<RefCell<Closure>>, usize), LuaError> {
+        for loop_count in 0..MAXTAGLOOP {
+            let val = self.stack().get(-(n_args as isize + 1)).borrow().clone();
+            if let LuaValue::Function(c) = val {
+                return Ok((c, n_args));
+            }
+            let mm = self.metafield(&val, "__call");
+            if mm.is_nil() {
+                // only the value called first comes from the instruction
+                let info = if loop_count == 0 {
+                    self.var_info()
+                } else {
+                    String::new()
+                };
+                let msg = format!(
+                    "attempt to call a {} value{}",
+                    self.type_name_str(val.type_id()),
+                    info
+                );
+                return Err(self.runtime_error(msg));
+            }
+            self.stack_mut().push(mm.to_ptr());
+            self.insert(-(n_args as isize + 2));
+            n_args += 1;
+        }
+        Err(self.runtime_error("'__call' chain too long; possible loop".to_string()))
+    }
+
+    // on error the callee's frame is left in place for the message handler,
+    // pcall unwinds it
+    fn call_rust_closure(
+        &mut self,
+        nargs: usize,
+        nresults: isize,
+        c: Rc<RefCell<Closure>>,
+    ) -> Result<(), LuaError> {
+        let rust_fn = c.borrow().rust_fn().cloned().unwrap();
         let mut new_stack = LuaStack::new(nargs + 20, self.registry.clone(), c);
+        new_stack.n_results = nresults;
 
         if nargs > 0 {
             let args = self.stack_mut().pop_n(nargs);
@@ -687,22 +1561,46 @@ impl LuaState {
 
         self.stack_mut().pop();
         self.push_frame(new_stack);
-        let r = rust_fn(self);
-        new_stack = self.pop_frame();
-
-        if nresults != 0 {
-            let results = new_stack.pop_n(r);
-            self.stack_mut().check(results.len());
-            self.stack_mut().push_n(results, nresults);
+        let r = rust_fn.call(self)?;
+        self.check_results(r)?;
+        self.post_call(r);
+        Ok(())
+    }
+
+    // a Rust function can only return values it left on its own stack
+    fn check_results(&self, n_rets: usize) -> Result<(), LuaError> {
+        let top = self.stack().top() as usize;
+        if n_rets > top {
+            let msg = format!(
+                "{}Rust function returned {} results but left only {} values",
+                self.position(1),
+                n_rets,
+                top
+            );
+            return Err(LuaError::runtime(msg));
+        }
+        Ok(())
+    }
+
+    // sets up the frame of a Lua function, the interpreter loop runs it
+    fn push_lua_frame(
+        &mut self,
+        n_args: usize,
+        n_results: isize,
+        c: Rc<RefCell<Closure>>,
+        is_tail: bool,
+    ) -> Result<(), LuaError> {
+        if self.frames.len() >= LUAI_MAXFRAMES {
+            return Err(self.runtime_error("stack overflow".to_string()));
         }
-    }
 
-    fn call_lua_closure(&mut self, n_args: usize, n_results: isize, c: Rc<RefCell<Closure>>) {
         let n_regs = c.borrow().proto().max_stack_size() as usize;
         let n_params = c.borrow().proto().num_params() as usize;
         let is_vararg = c.borrow().proto().is_vararg() == 1;
 
         let mut new_stack = LuaStack::new(n_regs + 20, self.registry.clone(), c);
+        new_stack.n_results = n_results;
+        new_stack.is_tail = is_tail;
         let mut args = self.stack_mut().pop_n(n_args);
         self.stack_mut().pop();
         if n_args > n_params {
@@ -719,24 +1617,165 @@ impl LuaState {
         new_stack.set_top(n_regs as isize);
 
         self.push_frame(new_stack);
-        self.run_lua_closure();
-        new_stack = self.pop_frame();
+        Ok(())
+    }
 
-        if n_results != 0 {
-            let n_rets = new_stack.top() as usize - n_regs;
-            let results = new_stack.pop_n(n_rets);
-            self.stack_mut().check(n_rets);
-            self.stack_mut().push_n(results, n_results);
+    // pops the finished frame and passes its top n_rets values to the caller
+    fn post_call(&mut self, n_rets: usize) {
+        let mut frame = self.pop_frame();
+        if frame.n_results != 0 {
+            let results = frame.pop_n(n_rets);
+            self.stack_mut().check(results.len());
+            self.stack_mut().push_n(results, frame.n_results);
         }
     }
 
-    fn run_lua_closure(&mut self) {
+    // runs Lua frames until the one at depth base returns; calls between Lua
+    // functions stay in this loop instead of recursing
+    fn execute(&mut self, base: usize) -> Result<(), LuaError> {
         loop {
+            let depth = self.frames.len();
             let instr = self.fetch();
-            instr.execute(self);
-            if instr.opcode() == crate::vm::opcode::OP_RETURN {
-                break;
+            instr.execute(self)?;
+            match instr.opcode() {
+                OP_RETURN => {
+                    let n_rets = self.stack().top() as usize - self.register_count();
+                    self.post_call(n_rets);
+                }
+                _ => continue,
+            }
+            if depth == base {
+                return Ok(());
+            }
+            self.finish_call()?;
+            // the thread's body was a Rust function a yield left behind
+            if self.frames.len() < base {
+                return Ok(());
+            }
+        }
+    }
+
+    // completes the instruction of the current frame once the function it
+    // called returned; a Rust function a yield left behind finishes with its
+    // continuation and returns in turn
+    fn finish_call(&mut self) -> Result<(), LuaError> {
+        while self.frames.len() > 1 {
+            if self.is_lua_frame() {
+                let i = {
+                    let frame = self.stack();
+                    let closure = frame.closure.borrow();
+                    closure.proto().code()[frame.pc as usize - 1]
+                };
+                // the '__lt' standing in for '__le' yielded before its result was negated
+                if std::mem::take(&mut self.stack_mut().lt_for_le) {
+                    let result = !self.to_boolean(-1);
+                    self.pop(1);
+                    self.push_boolean(result);
+                }
+                return i.finish_call(self);
+            }
+
+            // only a Rust function that passed a continuation can be left behind
+            let (k, ctx) = self.stack_mut().k.take().unwrap();
+            self.stack_mut().pcall = None;
+            let n_rets = k(self, LUA_YIELD, ctx)?;
+            self.check_results(n_rets)?;
+            self.post_call(n_rets);
+        }
+        Ok(())
+    }
+
+    // whether the current frame runs a Lua function
+    fn is_lua_frame(&self) -> bool {
+        self.frames.len() > 1 && self.stack().closure.borrow().rust_fn().is_none()
+    }
+
+    fn is_main_thread(&self) -> bool {
+        match &self.registry {
+            LuaValue::Table(r) => match r.borrow().get(&LUA_RIDX_MAINTHREAD) {
+                LuaValue::Thread(t) => Rc::ptr_eq(&t, &self.thread),
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    fn thread_at(&self, idx: isize) -> Result<Rc<RefCell<LuaThread>>, LuaError> {
+        match &*self.stack().get(idx).borrow() {
+            LuaValue::Thread(t) => Ok(t.clone()),
+            _ => Err(self.runtime_error("thread expected".to_string())),
+        }
+    }
+
+    // makes `to` the running thread, leaving the current one with status and
+    // returning it
+    fn switch_thread(
+        &mut self,
+        to: Rc<RefCell<LuaThread>>,
+        status: ThreadStatus,
+    ) -> Rc<RefCell<LuaThread>> {
+        {
+            let mut from = self.thread.borrow_mut();
+            from.frames = std::mem::take(&mut self.frames);
+            from.nny = self.nny;
+            from.status = status;
+        }
+        {
+            let mut to = to.borrow_mut();
+            self.frames = std::mem::take(&mut to.frames);
+            self.nny = to.nny;
+            to.status = ThreadStatus::Running;
+        }
+        std::mem::replace(&mut self.thread, to)
+    }
+
+    // starts the running coroutine or continues it after a yield
+    fn run_thread(&mut self, args: Vec<Rc<RefCell<LuaValue>>>) -> Result<(), LuaError> {
+        let n_args = args.len();
+        self.stack_mut().check(n_args);
+        self.stack_mut().push_n(args, -1);
+
+        let mut result = if self.frames.len() == 1 {
+            // the body sits on the base frame below the arguments
+            match self.pre_call(n_args, LUA_MULTRET) {
+                Ok(false) => self.execute(2),
+                result => result.map(|_| ()),
             }
+        } else {
+            // the yielding Rust function returns the values passed to resume,
+            // unless it left a continuation to handle them
+            self.unroll(LUA_YIELD, n_args)
+        };
+
+        // an error unwinds to the innermost pcall_k a yield left behind, which
+        // then finishes with the error status
+        while let Err(err) = result {
+            let pending = self.frames.iter().rposition(|frame| frame.pcall.is_some());
+            let idx = match pending {
+                Some(idx) if err.status() != LUA_YIELD => idx,
+                _ => return Err(err),
+            };
+            let pcall = self.frames[idx].pcall.take().unwrap();
+            let status = self.recover(err, idx + 1, pcall.func_idx, pcall.handler);
+            result = self.unroll(status, 0);
+        }
+        Ok(())
+    }
+
+    // finishes the Rust function on top, which a yield or an error left
+    // behind, then runs the frames below it; without a continuation the
+    // function returns its top n_rets values
+    fn unroll(&mut self, status: u8, n_rets: usize) -> Result<(), LuaError> {
+        let n_rets = match self.stack_mut().k.take() {
+            Some((k, ctx)) => k(self, status, ctx)?,
+            None => n_rets,
+        };
+        self.check_results(n_rets)?;
+        self.post_call(n_rets);
+        self.finish_call()?;
+        if self.frames.len() == 1 {
+            return Ok(());
         }
+        self.execute(2)
     }
 }
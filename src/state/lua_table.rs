@@ -1,36 +1,30 @@
-use std::{
-    cell::RefCell,
-    collections::HashMap,
-    hash::{Hash, Hasher},
-    rc::Rc,
-};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
 use crate::math::number;
 
-use super::lua_value::LuaValue;
+use super::{lua_error::LuaError, lua_value::LuaValue};
 
 #[derive(Clone)]
 pub struct LuaTable {
     arr: Vec<LuaValue>,
-    map: HashMap<LuaValue, LuaValue>,
-    address: usize,
-}
-
-impl Hash for LuaTable {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        self.address.hash(state);
-    }
+    // the hash part keeps its entries in insertion order so next can walk
+    // them in place; removed entries stay as nil slots until enough of
+    // them pile up, and are only compacted when a new key is added
+    nodes: Vec<(LuaValue, LuaValue)>,
+    node_pos: HashMap<LuaValue, usize>,
+    dead: usize,
+    metatable: Option<Rc<RefCell<LuaTable>>>,
 }
 
 impl LuaTable {
     pub fn new(n_arr: usize, n_rec: usize) -> Self {
-        let mut this = Self {
+        Self {
             arr: Vec::with_capacity(n_arr),
-            map: HashMap::with_capacity(n_rec),
-            address: 0,
-        };
-        this.address = std::ptr::addr_of!(this) as usize;
-        this
+            nodes: Vec::with_capacity(n_rec),
+            node_pos: HashMap::with_capacity(n_rec),
+            dead: 0,
+            metatable: None,
+        }
     }
 
     pub fn get(&self, key: &LuaValue) -> LuaValue {
@@ -41,11 +35,9 @@ impl LuaTable {
             }
         }
 
-        // TODO
-        if let Some(val) = self.map.get(key) {
-            val.clone()
-        } else {
-            LuaValue::Nil
+        match self.node_pos.get(&Self::normalize(key)) {
+            Some(&pos) => self.nodes[pos].1.clone(),
+            None => LuaValue::Nil,
         }
     }
 
@@ -59,6 +51,7 @@ impl LuaTable {
                 panic!("table index is NaN!");
             }
         }
+        let key = Self::normalize(&key);
 
         if let Some(idx) = Self::to_integer(&key) {
             let arr_len = self.arr.len();
@@ -66,14 +59,14 @@ impl LuaTable {
                 let is_nil = value.is_nil();
 
                 self.arr[idx - 1] = value;
-                if idx == arr_len && !is_nil {
+                if idx == arr_len && is_nil {
                     self.shrink_array();
                 }
                 return;
             }
 
             if idx == arr_len + 1 {
-                self.map.remove(&key);
+                self.remove_node(&key);
                 if !value.is_nil() {
                     self.arr.push(value);
                     self.expand_array();
@@ -83,12 +76,97 @@ impl LuaTable {
         }
 
         if !value.is_nil() {
-            self.map.insert(key, value);
+            self.insert_node(key, value);
         } else {
-            self.map.remove(&key);
+            self.remove_node(&key);
         }
     }
 
+    fn insert_node(&mut self, key: LuaValue, value: LuaValue) {
+        if let Some(&pos) = self.node_pos.get(&key) {
+            if self.nodes[pos].1.is_nil() {
+                self.dead -= 1;
+            }
+            self.nodes[pos].1 = value;
+            return;
+        }
+        if self.dead > 0 && self.dead >= self.nodes.len() / 2 {
+            self.compact_nodes();
+        }
+        self.node_pos.insert(key.clone(), self.nodes.len());
+        self.nodes.push((key, value));
+    }
+
+    // clears the entry but keeps its slot, so a traversal can go on from it
+    fn remove_node(&mut self, key: &LuaValue) -> Option<LuaValue> {
+        let &pos = self.node_pos.get(key)?;
+        let val = std::mem::replace(&mut self.nodes[pos].1, LuaValue::Nil);
+        if val.is_nil() {
+            return None;
+        }
+        self.dead += 1;
+        Some(val)
+    }
+
+    fn compact_nodes(&mut self) {
+        self.nodes.retain(|(_, v)| !v.is_nil());
+        self.node_pos.clear();
+        for (pos, (k, _)) in self.nodes.iter().enumerate() {
+            self.node_pos.insert(k.clone(), pos);
+        }
+        self.dead = 0;
+    }
+
+    // returns the entry after key (the first one for nil), walking the array
+    // part and then the hash part; fields may be set to nil while traversing,
+    // but a key that was never in the table is an error
+    pub fn next(&self, key: &LuaValue) -> Result<Option<(LuaValue, LuaValue)>, LuaError> {
+        let mut i = match key {
+            LuaValue::Nil => 0,
+            _ => match Self::to_integer(key) {
+                Some(i) if i <= self.arr.len() => i,
+                _ => usize::MAX,
+            },
+        };
+
+        if i != usize::MAX {
+            while i < self.arr.len() {
+                if !self.arr[i].is_nil() {
+                    return Ok(Some((LuaValue::Integer(i as i64 + 1), self.arr[i].clone())));
+                }
+                i += 1;
+            }
+        }
+
+        let mut pos = if i != usize::MAX {
+            0
+        } else {
+            match self.node_pos.get(&Self::normalize(key)) {
+                Some(pos) => pos + 1,
+                // an array index the array part has shrunk below
+                None if Self::to_integer(key).is_some() => 0,
+                None => return Err(LuaError::runtime("invalid key to 'next'".to_string())),
+            }
+        };
+
+        while pos < self.nodes.len() {
+            let (k, v) = &self.nodes[pos];
+            if !v.is_nil() {
+                return Ok(Some((k.clone(), v.clone())));
+            }
+            pos += 1;
+        }
+        Ok(None)
+    }
+
+    pub fn metatable(&self) -> Option<Rc<RefCell<LuaTable>>> {
+        self.metatable.clone()
+    }
+
+    pub fn set_metatable(&mut self, mt: Option<Rc<RefCell<LuaTable>>>) {
+        self.metatable = mt;
+    }
+
     pub fn len(&self) -> usize {
         self.arr.len()
     }
@@ -105,15 +183,20 @@ impl LuaTable {
 
     fn expand_array(&mut self) {
         let mut idx = self.arr.len() + 1;
-        loop {
-            let key = LuaValue::Integer(idx as i64);
-            if self.map.contains_key(&key) {
-                let val = self.map.remove(&key).unwrap();
-                self.arr.push(val);
-                idx += 1;
-            } else {
-                break;
-            }
+        while let Some(val) = self.remove_node(&LuaValue::Integer(idx as i64)) {
+            self.arr.push(val);
+            idx += 1;
+        }
+    }
+
+    // float keys with an exact integer value are stored as integers
+    fn normalize(key: &LuaValue) -> LuaValue {
+        match key {
+            LuaValue::Number(n) => match number::float_to_integer(*n) {
+                Some(i) => LuaValue::Integer(i),
+                None => key.clone(),
+            },
+            _ => key.clone(),
         }
     }
 
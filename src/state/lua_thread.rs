@@ -0,0 +1,39 @@
+use super::lua_stack::LuaStack;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ThreadStatus {
+    Suspended, // not started yet or yielded
+    Running,
+    Normal, // resumed another coroutine
+    Dead,   // finished or stopped by an error
+}
+
+impl ThreadStatus {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ThreadStatus::Suspended => "suspended",
+            ThreadStatus::Running => "running",
+            ThreadStatus::Normal => "normal",
+            ThreadStatus::Dead => "dead",
+        }
+    }
+}
+
+// a coroutine; the running thread's frames live in LuaState and are
+// swapped back in here when it yields or resumes another thread
+#[derive(Debug)]
+pub struct LuaThread {
+    pub frames: Vec<LuaStack>,
+    pub nny: usize, // number of non-yieldable calls in the stack
+    pub status: ThreadStatus,
+}
+
+impl LuaThread {
+    pub fn new(frames: Vec<LuaStack>) -> Self {
+        Self {
+            frames,
+            nny: 0,
+            status: ThreadStatus::Suspended,
+        }
+    }
+}
@@ -4,12 +4,12 @@ use std::{
 };
 
 use crate::{
-    api::{basic::BasicType, lua_vm::RustFn},
+    api::{basic::BasicType, lua_vm::RustFunction},
     binary::chunk::Prototype,
     math::{number, parser},
 };
 
-use super::{closure::Closure, lua_table::LuaTable};
+use super::{closure::Closure, lua_table::LuaTable, lua_thread::LuaThread};
 
 // copy ConstantType
 #[derive(Clone)]
@@ -18,9 +18,10 @@ pub enum LuaValue {
     Boolean(bool),
     Integer(i64),
     Number(f64),
-    String(String),
+    String(Vec<u8>),
     Table(Rc<RefCell<LuaTable>>),
     Function(Rc<RefCell<Closure>>),
+    Thread(Rc<RefCell<LuaThread>>),
 }
 
 impl fmt::Debug for LuaValue {
@@ -30,9 +31,10 @@ impl fmt::Debug for LuaValue {
             LuaValue::Boolean(b) => write!(f, "({})", b),
             LuaValue::Integer(i) => write!(f, "({})", i),
             LuaValue::Number(n) => write!(f, "({})", n),
-            LuaValue::String(s) => write!(f, "({})", s),
+            LuaValue::String(s) => write!(f, "({})", String::from_utf8_lossy(s)),
             LuaValue::Table(_) => write!(f, "()"),
             LuaValue::Function(_) => write!(f, "(function)"),
+            LuaValue::Thread(_) => write!(f, "(thread)"),
         }
     }
 }
@@ -53,6 +55,8 @@ impl PartialEq for LuaValue {
             Rc::ptr_eq(x, y)
         } else if let (LuaValue::Function(x), LuaValue::Function(y)) = (self, other) {
             Rc::ptr_eq(x, y)
+        } else if let (LuaValue::Thread(x), LuaValue::Thread(y)) = (self, other) {
+            Rc::ptr_eq(x, y)
         } else {
             false
         }
@@ -69,12 +73,26 @@ impl Hash for LuaValue {
             LuaValue::Integer(i) => i.hash(state),
             LuaValue::Number(n) => n.to_bits().hash(state),
             LuaValue::String(s) => s.hash(state),
-            LuaValue::Table(t) => t.borrow().hash(state),
-            LuaValue::Function(c) => c.borrow().hash(state),
+            // reference values hash by identity, without borrowing them
+            LuaValue::Table(t) => Rc::as_ptr(t).hash(state),
+            LuaValue::Function(c) => Rc::as_ptr(c).hash(state),
+            LuaValue::Thread(t) => Rc::as_ptr(t).hash(state),
         }
     }
 }
 
+impl From<&str> for LuaValue {
+    fn from(s: &str) -> Self {
+        Self::String(s.as_bytes().to_vec())
+    }
+}
+
+impl From<String> for LuaValue {
+    fn from(s: String) -> Self {
+        Self::String(s.into_bytes())
+    }
+}
+
 impl LuaValue {
     pub fn type_id(&self) -> BasicType {
         match self {
@@ -85,7 +103,7 @@ impl LuaValue {
             Self::String(_) => BasicType::LUA_TSTRING,
             Self::Table(_) => BasicType::LUA_TTABLE,
             Self::Function(_) => BasicType::LUA_TFUNCTION,
-            _ => todo!(),
+            Self::Thread(_) => BasicType::LUA_TTHREAD,
         }
     }
 
@@ -101,7 +119,7 @@ impl LuaValue {
         match self {
             Self::Number(f) => Some(*f),
             Self::Integer(i) => Some(*i as f64),
-            Self::String(s) => parser::parse_float(s),
+            Self::String(s) => std::str::from_utf8(s).ok().and_then(parser::parse_float),
             _ => None,
         }
     }
@@ -110,7 +128,7 @@ impl LuaValue {
         match self {
             Self::Integer(i) => Some(*i),
             Self::Number(f) => number::float_to_integer(*f),
-            Self::String(s) => Self::str_to_integer(s),
+            Self::String(s) => std::str::from_utf8(s).ok().and_then(Self::str_to_integer),
             _ => None,
         }
     }
@@ -123,7 +141,7 @@ impl LuaValue {
         Self::Function(Rc::new(RefCell::new(Closure::new_lua_closure(proto))))
     }
 
-    pub fn new_rust_fn(f: RustFn, n_upvals: usize) -> Self {
+    pub fn new_rust_fn(f: RustFunction, n_upvals: usize) -> Self {
         Self::Function(Rc::new(RefCell::new(Closure::new_rust_closure(f, n_upvals))))
     }
 
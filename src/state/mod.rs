@@ -1,13 +1,16 @@
 mod api_arith;
 mod api_compare;
 mod closure;
+mod lua_debug;
+mod lua_error;
 mod lua_stack;
 mod lua_state;
 mod lua_table;
+mod lua_thread;
 mod lua_value;
 mod util;
 
-pub use self::lua_state::LuaState;
+pub use self::{lua_error::LuaError, lua_state::LuaState};
 
 pub fn new_lua_state() -> LuaState {
     LuaState::new()
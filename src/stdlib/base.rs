@@ -0,0 +1,379 @@
+use std::io::{self, Write};
+
+use crate::{
+    api::{
+        basic::{BasicType, LUA_MULTRET, LUA_OK, LUA_VERSION, LUA_YIELD},
+        lua_vm::{LuaAPI, RustFn, RustFunction},
+    },
+    state::LuaError,
+};
+
+use super::{
+    arg_error, check_any, check_integer, check_type, lib_error, set_funcs, to_string_meta,
+    type_error,
+};
+
+const BASE_FUNCS: &[(&str, RustFn)] = &[
+    ("assert", assert),
+    ("error", error),
+    ("getmetatable", get_metatable),
+    ("ipairs", ipairs),
+    ("load", load),
+    ("next", next),
+    ("pairs", pairs),
+    ("pcall", pcall),
+    ("print", print),
+    ("rawequal", raw_equal),
+    ("rawlen", raw_len),
+    ("rawget", raw_get),
+    ("rawset", raw_set),
+    ("select", select),
+    ("setmetatable", set_metatable),
+    ("tonumber", to_number),
+    ("tostring", to_string),
+    ("type", type_),
+    ("xpcall", xpcall),
+];
+
+pub fn open_base(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    // open lib into global table
+    ls.push_global_table();
+    set_funcs(ls, BASE_FUNCS)?;
+    // set global _G
+    ls.push_value(-1);
+    ls.set_field(-2, "_G")?;
+    // set global _VERSION
+    ls.push_string(LUA_VERSION.to_string());
+    ls.set_field(-2, "_VERSION")?;
+    Ok(1)
+}
+
+// print (···)
+fn print(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    let n = ls.top();
+    ls.global("tostring")?;
+    let mut line = Vec::new();
+    for i in 1..=n {
+        ls.push_value(-1); // function to be called
+        ls.push_value(i); // value to print
+        ls.call(1, 1)?;
+        let s = match ls.to_bytesx(-1) {
+            Some(s) => s,
+            None => return Err(lib_error(ls, "'tostring' must return a string to 'print'")),
+        };
+        if i > 1 {
+            line.push(b'\t');
+        }
+        line.extend_from_slice(&s);
+        ls.pop(1); // pop result
+    }
+    line.push(b'\n');
+    // strings are bytes, so they are written as they are; errors are ignored
+    let _ = io::stdout().write_all(&line);
+    Ok(0)
+}
+
+// type (v)
+fn type_(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    check_any(ls, 1, "type")?;
+    let name = ls.type_name_str(ls.type_enum_id(1)).to_string();
+    ls.push_string(name);
+    Ok(1)
+}
+
+// tostring (v)
+fn to_string(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    check_any(ls, 1, "tostring")?;
+    to_string_meta(ls, 1)?;
+    Ok(1)
+}
+
+// tonumber (e [, base])
+fn to_number(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    if ls.is_none_or_nil(2) {
+        // standard conversion
+        match ls.type_enum_id(1) {
+            BasicType::LUA_TNUMBER => {
+                ls.set_top(1); // yes; return it
+                return Ok(1);
+            }
+            BasicType::LUA_TSTRING => {
+                let s = ls.to_string(1);
+                if ls.string_to_number(&s) {
+                    return Ok(1); // successful conversion to number
+                }
+            }
+            _ => check_any(ls, 1, "tonumber")?, // (but there must be some parameter)
+        }
+    } else {
+        let base = check_integer(ls, 2, "tonumber")?;
+        check_type(ls, 1, "tonumber", BasicType::LUA_TSTRING)?; // no numbers as strings
+        if !(2..=36).contains(&base) {
+            return Err(arg_error(ls, 2, "tonumber", "base out of range"));
+        }
+        if let Some(n) = str_to_int(&ls.to_string(1), base) {
+            ls.push_integer(n);
+            return Ok(1);
+        }
+    }
+    ls.push_nil(); // not a number
+    Ok(1)
+}
+
+// the integer s spells in base, wrapping around on overflow
+fn str_to_int(s: &str, base: i64) -> Option<i64> {
+    let s = s.trim_matches(|c| matches!(c, ' ' | '\t' | '\n' | '\r' | '\x0b' | '\x0c'));
+    let (neg, digits) = match s.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, s),
+    };
+    if digits.is_empty() {
+        return None;
+    }
+
+    let mut n: i64 = 0;
+    for c in digits.chars() {
+        let d = c.to_digit(36)? as i64;
+        if d >= base {
+            return None;
+        }
+        n = n.wrapping_mul(base).wrapping_add(d);
+    }
+    Some(if neg { n.wrapping_neg() } else { n })
+}
+
+// ipairs (t)
+fn ipairs(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    check_any(ls, 1, "ipairs")?;
+    ls.push_rust_fn(RustFunction::Ptr(ipairs_aux)); // iteration function
+    ls.push_value(1); // state
+    ls.push_integer(0); // initial value
+    Ok(3)
+}
+
+// the iterator ipairs returns; it stops at the first nil value
+fn ipairs_aux(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    let i = check_integer(ls, 2, "ipairs")?.wrapping_add(1);
+    ls.push_integer(i);
+    if ls.i(1, i)? == BasicType::LUA_TNIL {
+        Ok(1)
+    } else {
+        Ok(2)
+    }
+}
+
+// pairs (t)
+fn pairs(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    check_any(ls, 1, "pairs")?;
+    if ls.get_metafield(1, "__pairs") == BasicType::LUA_TNIL {
+        // no metamethod
+        ls.push_rust_fn(RustFunction::Ptr(next)); // will return generator,
+        ls.push_value(1); // state,
+        ls.push_nil(); // and initial value
+    } else {
+        ls.push_value(1); // argument 'self' to metamethod
+        ls.call(1, 3)?; // get 3 values from metamethod
+    }
+    Ok(3)
+}
+
+// next (table [, index])
+fn next(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    check_type(ls, 1, "next", BasicType::LUA_TTABLE)?;
+    ls.set_top(2); // create a 2nd argument if there isn't one
+    if ls.next(1)? {
+        Ok(2)
+    } else {
+        ls.push_nil();
+        Ok(1)
+    }
+}
+
+// select (index, ···)
+fn select(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    let n = ls.top() as i64;
+    if ls.type_enum_id(1) == BasicType::LUA_TSTRING && ls.to_string(1).starts_with('#') {
+        ls.push_integer(n - 1);
+        return Ok(1);
+    }
+
+    let mut i = check_integer(ls, 1, "select")?;
+    if i < 0 {
+        i += n;
+    } else if i > n {
+        i = n;
+    }
+    if i < 1 {
+        return Err(arg_error(ls, 1, "select", "index out of range"));
+    }
+    Ok((n - i) as usize)
+}
+
+// assert (v [, message])
+fn assert(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    if ls.to_boolean(1) {
+        // condition is true
+        return Ok(ls.top() as usize); // return all arguments
+    }
+    check_any(ls, 1, "assert")?; // there must be a condition
+    ls.remove(1); // remove it
+    ls.push_string("assertion failed!".to_string()); // default message
+    ls.set_top(1); // leave only message (default if no other one)
+    error(ls) // call 'error'
+}
+
+// rawequal (v1, v2)
+fn raw_equal(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    check_any(ls, 1, "rawequal")?;
+    check_any(ls, 2, "rawequal")?;
+    let eq = ls.raw_equal(1, 2);
+    ls.push_boolean(eq);
+    Ok(1)
+}
+
+// rawlen (v)
+fn raw_len(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    let tp = ls.type_enum_id(1);
+    if tp != BasicType::LUA_TTABLE && tp != BasicType::LUA_TSTRING {
+        return Err(arg_error(ls, 1, "rawlen", "table or string expected"));
+    }
+    let len = ls.raw_len(1);
+    ls.push_integer(len as i64);
+    Ok(1)
+}
+
+// rawget (table, index)
+fn raw_get(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    check_type(ls, 1, "rawget", BasicType::LUA_TTABLE)?;
+    check_any(ls, 2, "rawget")?;
+    ls.set_top(2);
+    ls.raw_get(1)?;
+    Ok(1)
+}
+
+// rawset (table, index, value)
+fn raw_set(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    check_type(ls, 1, "rawset", BasicType::LUA_TTABLE)?;
+    check_any(ls, 2, "rawset")?;
+    check_any(ls, 3, "rawset")?;
+    ls.set_top(3);
+    ls.raw_set(1)?;
+    Ok(1)
+}
+
+// setmetatable (table, metatable)
+fn set_metatable(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    let tp = ls.type_enum_id(2);
+    check_type(ls, 1, "setmetatable", BasicType::LUA_TTABLE)?;
+    if tp != BasicType::LUA_TNIL && tp != BasicType::LUA_TTABLE {
+        return Err(type_error(ls, 2, "setmetatable", "nil or table"));
+    }
+    if ls.get_metafield(1, "__metatable") != BasicType::LUA_TNIL {
+        return Err(lib_error(ls, "cannot change a protected metatable"));
+    }
+    ls.set_top(2);
+    ls.set_metatable(1)?;
+    Ok(1)
+}
+
+// getmetatable (object)
+fn get_metatable(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    check_any(ls, 1, "getmetatable")?;
+    if !ls.get_metatable(1) {
+        ls.push_nil(); // no metatable
+    }
+    Ok(1) // returns either __metatable field (if present) or metatable
+}
+
+// load (chunk [, chunkname [, mode]])
+fn load(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    let mode = match ls.to_stringx(3) {
+        Some(mode) if !ls.is_none_or_nil(3) => mode,
+        _ => "bt".to_string(),
+    };
+    let (chunk, def_name) = if ls.type_enum_id(1) == BasicType::LUA_TSTRING {
+        let chunk = ls.to_bytes(1);
+        let name = String::from_utf8_lossy(&chunk).into_owned();
+        (chunk, name)
+    } else {
+        // loading from a reader function
+        check_type(ls, 1, "load", BasicType::LUA_TFUNCTION)?;
+        let mut chunk = Vec::new();
+        loop {
+            ls.push_value(1);
+            ls.call(0, 1)?; // call the reader
+            if ls.is_none_or_nil(-1) || (ls.is_string(-1) && ls.raw_len(-1) == 0) {
+                ls.pop(1);
+                break; // end of the chunk
+            }
+            if !ls.is_string(-1) {
+                return Err(lib_error(ls, "reader function must return a string"));
+            }
+            chunk.extend_from_slice(&ls.to_bytes(-1));
+            ls.pop(1);
+        }
+        (chunk, "=(load)".to_string())
+    };
+    let chunk_name = match ls.to_stringx(2) {
+        Some(name) if !ls.is_none_or_nil(2) => name,
+        _ => def_name,
+    };
+    if ls.load(chunk, &chunk_name, &mode) == LUA_OK {
+        Ok(1)
+    } else {
+        // error (message is on top of the stack)
+        ls.push_nil();
+        ls.insert(-2); // put before error message
+        Ok(2) // return nil plus error message
+    }
+}
+
+// error (message [, level])
+fn error(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    let level = ls.to_integerx(2).unwrap_or(1);
+    ls.set_top(1);
+    if ls.is_string(1) && level > 0 {
+        // add extra information
+        let pos = ls.position(level as usize);
+        ls.push_string(pos);
+        ls.insert(1);
+        ls.concat(2)?;
+    }
+    Err(ls.error())
+}
+
+// pcall (f [, arg1, ...])
+fn pcall(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    if ls.is_none(1) {
+        return Err(arg_error(ls, 1, "pcall", "value expected"));
+    }
+    ls.push_boolean(true); // first result if no errors
+    ls.insert(1);
+    let n_args = ls.top() as usize - 2;
+    let status = ls.pcall_k(n_args, LUA_MULTRET, 0, 0, finish_pcall)?;
+    finish_pcall(ls, status, 0)
+}
+
+// xpcall (f, msgh [, arg1, ...])
+fn xpcall(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    let n = ls.top();
+    if ls.type_enum_id(2) != BasicType::LUA_TFUNCTION {
+        return Err(arg_error(ls, 2, "xpcall", "function expected"));
+    }
+    ls.push_boolean(true); // first result
+    ls.push_value(1); // function
+    ls.rotate(3, 2); // move them below function's arguments
+    let status = ls.pcall_k(n as usize - 2, LUA_MULTRET, 2, 2, finish_pcall)?;
+    finish_pcall(ls, status, 2)
+}
+
+// continuation shared by pcall and xpcall
+fn finish_pcall(ls: &mut dyn LuaAPI, status: u8, extra: isize) -> Result<usize, LuaError> {
+    if status != LUA_OK && status != LUA_YIELD {
+        ls.push_boolean(false);
+        ls.push_value(-2); // error message
+        Ok(2) // return false, msg
+    } else {
+        Ok((ls.top() - extra) as usize) // return all results
+    }
+}
@@ -0,0 +1,125 @@
+use crate::{
+    api::{
+        basic::{BasicType, LUA_OK, LUA_YIELD},
+        lua_vm::{lua_upvalue_index, LuaAPI, RustFn, RustFunction},
+    },
+    state::LuaError,
+};
+
+use super::{arg_error, set_funcs};
+
+const CO_FUNCS: &[(&str, RustFn)] = &[
+    ("create", create),
+    ("resume", resume),
+    ("running", running),
+    ("status", status),
+    ("wrap", wrap),
+    ("yield", co_yield),
+    ("isyieldable", is_yieldable),
+];
+
+pub fn open_coroutine(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    ls.create_table(0, CO_FUNCS.len());
+    set_funcs(ls, CO_FUNCS)?;
+    Ok(1)
+}
+
+fn check_co(ls: &mut dyn LuaAPI, fname: &str) -> Result<(), LuaError> {
+    if ls.is_thread(1) {
+        Ok(())
+    } else {
+        Err(arg_error(ls, 1, fname, "coroutine expected"))
+    }
+}
+
+// resumes the thread at co with the top n_args values, leaving its results on
+// the stack, or None with the error object on the top
+fn aux_resume(ls: &mut dyn LuaAPI, co: isize, n_args: usize) -> Option<usize> {
+    let base = ls.top() - n_args as isize;
+    match ls.resume(co, n_args) {
+        LUA_OK | LUA_YIELD => Some((ls.top() - base) as usize),
+        _ => None,
+    }
+}
+
+// coroutine.create (f)
+fn create(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    if ls.type_enum_id(1) != BasicType::LUA_TFUNCTION {
+        return Err(arg_error(ls, 1, "create", "function expected"));
+    }
+    ls.new_thread();
+    ls.push_value(1); // move function to top
+    ls.xmove(-2, 1)?; // move function from ls to the new thread
+    Ok(1)
+}
+
+// coroutine.resume (co [, val1, ...])
+fn resume(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    check_co(ls, "resume")?;
+    let n_args = ls.top() as usize - 1;
+    match aux_resume(ls, 1, n_args) {
+        Some(r) => {
+            ls.push_boolean(true);
+            ls.insert(-(r as isize + 1));
+            Ok(r + 1) // return true + 'resume' returns
+        }
+        None => {
+            ls.push_boolean(false);
+            ls.insert(-2);
+            Ok(2) // return false + error message
+        }
+    }
+}
+
+// the function returned by coroutine.wrap, with the thread as upvalue
+fn aux_wrap(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    let n_args = ls.top() as usize;
+    match aux_resume(ls, lua_upvalue_index(1), n_args) {
+        Some(r) => Ok(r),
+        None => {
+            if ls.type_enum_id(-1) == BasicType::LUA_TSTRING {
+                // error object is a string, add extra info
+                let pos = ls.position(1);
+                ls.push_string(pos);
+                ls.insert(-2);
+                ls.concat(2)?;
+            }
+            Err(ls.error()) // propagate error
+        }
+    }
+}
+
+// coroutine.wrap (f)
+fn wrap(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    create(ls)?;
+    ls.push_rust_closure(RustFunction::Ptr(aux_wrap), 1);
+    Ok(1)
+}
+
+// coroutine.yield (...)
+fn co_yield(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    let n = ls.top() as usize;
+    Err(ls.yield_k(n, 0, None))
+}
+
+// coroutine.status (co)
+fn status(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    check_co(ls, "status")?;
+    let status = ls.thread_status(1)?;
+    ls.push_string(status.to_string());
+    Ok(1)
+}
+
+// coroutine.isyieldable ()
+fn is_yieldable(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    let yieldable = ls.is_yieldable();
+    ls.push_boolean(yieldable);
+    Ok(1)
+}
+
+// coroutine.running ()
+fn running(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    let is_main = ls.push_thread();
+    ls.push_boolean(is_main);
+    Ok(2)
+}
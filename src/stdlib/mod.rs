@@ -0,0 +1,148 @@
+pub mod base;
+pub mod coroutine;
+pub mod string;
+pub mod table;
+
+use crate::{
+    api::{
+        basic::BasicType,
+        lua_vm::{LuaAPI, RustFn, RustFunction},
+    },
+    state::LuaError,
+};
+
+const LOADED_LIBS: &[(&str, RustFn)] = &[
+    ("_G", base::open_base),
+    ("coroutine", coroutine::open_coroutine),
+    ("string", string::open_string),
+    ("table", table::open_table),
+];
+
+// opens the standard libraries, each one into the global of its name
+pub fn open_libs(ls: &mut dyn LuaAPI) -> Result<(), LuaError> {
+    for (name, open) in LOADED_LIBS {
+        open(ls)?;
+        ls.set_global(name)?;
+    }
+    Ok(())
+}
+
+// sets the functions into the table on the top of the stack
+fn set_funcs(ls: &mut dyn LuaAPI, funcs: &[(&str, RustFn)]) -> Result<(), LuaError> {
+    for (name, f) in funcs {
+        ls.push_rust_fn(RustFunction::Ptr(*f));
+        ls.set_field(-2, name)?;
+    }
+    Ok(())
+}
+
+// msg at the position of the caller
+fn lib_error(ls: &mut dyn LuaAPI, msg: &str) -> LuaError {
+    let pos = ls.position(1);
+    ls.push_string(format!("{}{}", pos, msg));
+    ls.error()
+}
+
+// "bad argument #arg to 'fname' (msg)", at the position of the caller
+fn arg_error(ls: &mut dyn LuaAPI, arg: isize, fname: &str, msg: &str) -> LuaError {
+    lib_error(ls, &format!("bad argument #{} to '{}' ({})", arg, fname, msg))
+}
+
+// "bad argument #arg to 'fname' (tname expected, got no value)"
+fn type_error(ls: &mut dyn LuaAPI, arg: isize, fname: &str, tname: &str) -> LuaError {
+    let type_arg = if ls.get_metafield(arg, "__name") == BasicType::LUA_TSTRING {
+        let name = ls.to_string(-1);
+        ls.pop(1);
+        name
+    } else {
+        ls.type_name_str(ls.type_enum_id(arg)).to_string()
+    };
+    arg_error(ls, arg, fname, &format!("{} expected, got {}", tname, type_arg))
+}
+
+fn check_any(ls: &mut dyn LuaAPI, arg: isize, fname: &str) -> Result<(), LuaError> {
+    if ls.is_none(arg) {
+        return Err(arg_error(ls, arg, fname, "value expected"));
+    }
+    Ok(())
+}
+
+fn check_type(ls: &mut dyn LuaAPI, arg: isize, fname: &str, tp: BasicType) -> Result<(), LuaError> {
+    if ls.type_enum_id(arg) != tp {
+        return Err(type_error(ls, arg, fname, tp.name()));
+    }
+    Ok(())
+}
+
+fn check_integer(ls: &mut dyn LuaAPI, arg: isize, fname: &str) -> Result<i64, LuaError> {
+    match ls.to_integerx(arg) {
+        Some(i) => Ok(i),
+        None if ls.is_number(arg) => Err(arg_error(
+            ls,
+            arg,
+            fname,
+            "number has no integer representation",
+        )),
+        None => Err(type_error(ls, arg, fname, "number")),
+    }
+}
+
+fn opt_integer(ls: &mut dyn LuaAPI, arg: isize, fname: &str, def: i64) -> Result<i64, LuaError> {
+    if ls.is_none_or_nil(arg) {
+        Ok(def)
+    } else {
+        check_integer(ls, arg, fname)
+    }
+}
+
+fn check_number(ls: &mut dyn LuaAPI, arg: isize, fname: &str) -> Result<f64, LuaError> {
+    match ls.to_numberx(arg) {
+        Some(n) => Ok(n),
+        None => Err(type_error(ls, arg, fname, "number")),
+    }
+}
+
+fn check_bytes(ls: &mut dyn LuaAPI, arg: isize, fname: &str) -> Result<Vec<u8>, LuaError> {
+    match ls.to_bytesx(arg) {
+        Some(s) => Ok(s),
+        None => Err(type_error(ls, arg, fname, "string")),
+    }
+}
+
+// converts the value at idx to a string the way tostring does, honoring
+// '__tostring' and '__name', and pushes the result
+fn to_string_meta(ls: &mut dyn LuaAPI, idx: isize) -> Result<String, LuaError> {
+    let idx = ls.abs_index(idx);
+    if ls.get_metafield(idx, "__tostring") != BasicType::LUA_TNIL {
+        ls.push_value(idx);
+        ls.call(1, 1)?;
+        if !ls.is_string(-1) {
+            return Err(lib_error(ls, "'__tostring' must return a string"));
+        }
+    } else {
+        match ls.type_enum_id(idx) {
+            BasicType::LUA_TNUMBER => {
+                let s = ls.to_string(idx);
+                ls.push_string(s);
+            }
+            BasicType::LUA_TSTRING => ls.push_value(idx),
+            BasicType::LUA_TBOOLEAN => {
+                let b = ls.to_boolean(idx);
+                ls.push_string(b.to_string());
+            }
+            BasicType::LUA_TNIL => ls.push_string("nil".to_string()),
+            tp => {
+                let kind = if ls.get_metafield(idx, "__name") == BasicType::LUA_TSTRING {
+                    let name = ls.to_string(-1);
+                    ls.pop(1);
+                    name
+                } else {
+                    tp.name().to_string()
+                };
+                let s = format!("{}: {:#x}", kind, ls.to_pointer(idx));
+                ls.push_string(s);
+            }
+        }
+    }
+    Ok(ls.to_string(-1))
+}
@@ -0,0 +1,205 @@
+use crate::{
+    api::{basic::BasicType, lua_vm::LuaAPI},
+    math::number::{format_float, format_integer},
+    state::LuaError,
+};
+
+use crate::stdlib::{
+    arg_error, check_bytes, check_integer, check_number, lib_error, to_string_meta,
+};
+
+const L_ESC: u8 = b'%';
+const FLAGS: &[u8] = b"-+ #0"; // valid flags in a format specification
+
+// reads the specification after a '%' at p into a C format such as "%-5.2",
+// returning it with the position of the conversion character
+fn scan_format(
+    ls: &mut dyn LuaAPI,
+    strfrmt: &[u8],
+    start: usize,
+) -> Result<(String, usize), LuaError> {
+    let at = |p: usize| strfrmt.get(p).copied().unwrap_or(0);
+    let mut p = start;
+    while FLAGS.contains(&at(p)) {
+        p += 1; // skip flags
+    }
+    if p - start > FLAGS.len() {
+        return Err(lib_error(ls, "invalid format (repeated flags)"));
+    }
+    if at(p).is_ascii_digit() {
+        p += 1; // skip width
+    }
+    if at(p).is_ascii_digit() {
+        p += 1; // (2 digits at most)
+    }
+    if at(p) == b'.' {
+        p += 1;
+        if at(p).is_ascii_digit() {
+            p += 1; // skip precision
+        }
+        if at(p).is_ascii_digit() {
+            p += 1; // (2 digits at most)
+        }
+    }
+    if at(p).is_ascii_digit() {
+        return Err(lib_error(
+            ls,
+            "invalid format (width or precision too long)",
+        ));
+    }
+    let form = format!("%{}", String::from_utf8_lossy(&strfrmt[start..p]));
+    Ok((form, p))
+}
+
+// pads s to the width of form, which only has the flags meaningful to %c and %s
+fn pad(form: &str, s: &[u8], b: &mut Vec<u8>) {
+    let spec = &form[1..];
+    let left = spec.contains('-');
+    let width = spec
+        .trim_start_matches(|c| FLAGS.contains(&(c as u8)))
+        .bytes()
+        .take_while(u8::is_ascii_digit)
+        .fold(0, |w, d| w * 10 + (d - b'0') as usize);
+    let fill = width.saturating_sub(s.len());
+    if !left {
+        b.resize(b.len() + fill, b' ');
+    }
+    b.extend_from_slice(s);
+    if left {
+        b.resize(b.len() + fill, b' ');
+    }
+}
+
+fn add_quoted(s: &[u8], b: &mut Vec<u8>) {
+    b.push(b'"');
+    for (i, &c) in s.iter().enumerate() {
+        if c == b'"' || c == b'\\' || c == b'\n' {
+            b.push(b'\\');
+            b.push(c);
+        } else if c.is_ascii_control() {
+            // a decimal escape, with 3 digits if a digit follows it
+            let esc = if s.get(i + 1).is_some_and(u8::is_ascii_digit) {
+                format!("\\{:03}", c)
+            } else {
+                format!("\\{}", c)
+            };
+            b.extend_from_slice(esc.as_bytes());
+        } else {
+            b.push(c);
+        }
+    }
+    b.push(b'"');
+}
+
+// the value at arg as source code that reads back to it
+fn add_literal(ls: &mut dyn LuaAPI, arg: isize, b: &mut Vec<u8>) -> Result<(), LuaError> {
+    match ls.type_enum_id(arg) {
+        BasicType::LUA_TSTRING => add_quoted(&ls.to_bytes(arg), b),
+        BasicType::LUA_TNUMBER => {
+            let s = if ls.is_integer(arg) {
+                let n = ls.to_integer(arg);
+                if n == i64::MIN {
+                    // corner case: -9223372036854775808 reads as a float
+                    format_integer("0x%x", n)
+                } else {
+                    format_integer("%d", n)
+                }
+            } else {
+                let n = ls.to_number(arg);
+                if n == f64::INFINITY {
+                    "1e9999".to_string()
+                } else if n == f64::NEG_INFINITY {
+                    "-1e9999".to_string()
+                } else if n.is_nan() {
+                    "(0/0)".to_string()
+                } else {
+                    format_float("%a", n) // hexadecimal keeps every bit
+                }
+            };
+            b.extend_from_slice(s.as_bytes());
+        }
+        BasicType::LUA_TNIL | BasicType::LUA_TBOOLEAN => {
+            let s = to_string_meta(ls, arg)?;
+            ls.pop(1);
+            b.extend_from_slice(s.as_bytes());
+        }
+        _ => return Err(arg_error(ls, arg, "format", "value has no literal form")),
+    }
+    Ok(())
+}
+
+// string.format (formatstring, ···)
+pub fn str_format(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    let top = ls.top();
+    let mut arg = 1;
+    let strfrmt = check_bytes(ls, arg, "format")?;
+    let mut b = Vec::with_capacity(strfrmt.len());
+    let mut p = 0;
+    while p < strfrmt.len() {
+        if strfrmt[p] != L_ESC {
+            b.push(strfrmt[p]);
+            p += 1;
+            continue;
+        }
+        p += 1;
+        if strfrmt.get(p) == Some(&L_ESC) {
+            b.push(L_ESC); // %%
+            p += 1;
+            continue;
+        }
+        // format item
+        arg += 1;
+        if arg > top {
+            return Err(arg_error(ls, arg, "format", "no value"));
+        }
+        let (form, conv) = scan_format(ls, &strfrmt, p)?;
+        let c = strfrmt.get(conv).copied().unwrap_or(0);
+        p = conv + 1;
+        let form = format!("{}{}", form, c as char);
+        match c {
+            b'c' => {
+                let n = check_integer(ls, arg, "format")?;
+                pad(&form, &[n as u8], &mut b);
+            }
+            b'd' | b'i' | b'o' | b'u' | b'x' | b'X' => {
+                let n = check_integer(ls, arg, "format")?;
+                b.extend_from_slice(format_integer(&form, n).as_bytes());
+            }
+            b'a' | b'A' | b'e' | b'E' | b'f' | b'g' | b'G' => {
+                let n = check_number(ls, arg, "format")?;
+                b.extend_from_slice(format_float(&form, n).as_bytes());
+            }
+            b'q' => {
+                if form.len() > 2 {
+                    return Err(lib_error(ls, "specifier '%q' cannot have modifiers"));
+                }
+                add_literal(ls, arg, &mut b)?;
+            }
+            b's' => {
+                to_string_meta(ls, arg)?;
+                let s = ls.to_bytes(-1);
+                ls.pop(1);
+                if form.len() == 2 || (!form.contains('.') && s.len() >= 100) {
+                    // no modifiers, or no precision and too long to be formatted
+                    b.extend_from_slice(&s); // keep entire string
+                } else {
+                    if s.contains(&0) {
+                        return Err(arg_error(ls, arg, "format", "string contains zeros"));
+                    }
+                    let precision = form
+                        .split_once('.')
+                        .map(|(_, prec)| prec.trim_end_matches('s').parse().unwrap_or(0));
+                    let s = &s[..precision.map_or(s.len(), |prec: usize| prec.min(s.len()))];
+                    pad(&form, s, &mut b);
+                }
+            }
+            // also treat cases 'pnLlh'
+            _ => {
+                let msg = format!("invalid option '%{}' to 'format'", c as char);
+                return Err(lib_error(ls, &msg));
+            }
+        }
+    }
+    ls.push_bytes(b);
+    Ok(1)
+}
@@ -0,0 +1,436 @@
+mod format;
+mod pack;
+mod pattern;
+
+use crate::{
+    api::{
+        basic::BasicType,
+        lua_vm::{lua_upvalue_index, LuaAPI, RustFn, RustFunction},
+    },
+    state::LuaError,
+};
+
+use self::pattern::{no_specials, Capture, MatchState};
+use super::{
+    arg_error, check_bytes, check_integer, check_type, lib_error, opt_integer, set_funcs,
+};
+
+// the largest string the library builds, MAXSIZE in lstrlib.c
+const MAX_SIZE: usize = i32::MAX as usize;
+
+const STR_FUNCS: &[(&str, RustFn)] = &[
+    ("byte", byte),
+    ("char", char),
+    ("dump", dump),
+    ("find", find),
+    ("format", format::str_format),
+    ("gmatch", gmatch),
+    ("gsub", gsub),
+    ("len", len),
+    ("lower", lower),
+    ("match", match_),
+    ("pack", pack::str_pack),
+    ("packsize", pack::str_packsize),
+    ("rep", rep),
+    ("reverse", reverse),
+    ("sub", sub),
+    ("unpack", pack::str_unpack),
+    ("upper", upper),
+];
+
+pub fn open_string(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    ls.create_table(0, STR_FUNCS.len());
+    set_funcs(ls, STR_FUNCS)?;
+    create_metatable(ls)?;
+    Ok(1)
+}
+
+// the metatable shared by all strings, indexing into the library table on
+// the top of the stack so that s:upper() works
+fn create_metatable(ls: &mut dyn LuaAPI) -> Result<(), LuaError> {
+    ls.create_table(0, 1); // table to be metatable for strings
+    ls.push_string(String::new()); // dummy string
+    ls.push_value(-2); // copy table
+    ls.set_metatable(-2)?; // set table as metatable for strings
+    ls.pop(1); // pop dummy string
+    ls.push_value(-2); // get string library
+    ls.set_field(-2, "__index")?; // metatable.__index = string
+    ls.pop(1); // pop metatable
+    Ok(())
+}
+
+// translates a relative string position: negative means back from end
+fn pos_relat(pos: i64, len: usize) -> i64 {
+    if pos >= 0 {
+        pos
+    } else if pos.unsigned_abs() > len as u64 {
+        0
+    } else {
+        len as i64 + pos + 1
+    }
+}
+
+// string.len (s)
+fn len(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    let s = check_bytes(ls, 1, "len")?;
+    ls.push_integer(s.len() as i64);
+    Ok(1)
+}
+
+// string.sub (s, i [, j])
+fn sub(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    let s = check_bytes(ls, 1, "sub")?;
+    let l = s.len();
+    let start = pos_relat(check_integer(ls, 2, "sub")?, l).max(1);
+    let end = pos_relat(opt_integer(ls, 3, "sub", -1)?, l).min(l as i64);
+    if start <= end {
+        ls.push_bytes(s[start as usize - 1..end as usize].to_vec());
+    } else {
+        ls.push_string(String::new());
+    }
+    Ok(1)
+}
+
+// string.reverse (s)
+fn reverse(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    let mut s = check_bytes(ls, 1, "reverse")?;
+    s.reverse();
+    ls.push_bytes(s);
+    Ok(1)
+}
+
+// string.lower (s)
+fn lower(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    let s = check_bytes(ls, 1, "lower")?;
+    ls.push_bytes(s.to_ascii_lowercase());
+    Ok(1)
+}
+
+// string.upper (s)
+fn upper(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    let s = check_bytes(ls, 1, "upper")?;
+    ls.push_bytes(s.to_ascii_uppercase());
+    Ok(1)
+}
+
+// string.rep (s, n [, sep])
+fn rep(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    let s = check_bytes(ls, 1, "rep")?;
+    let n = check_integer(ls, 2, "rep")?;
+    let sep = if ls.is_none_or_nil(3) {
+        Vec::new()
+    } else {
+        check_bytes(ls, 3, "rep")?
+    };
+    if n <= 0 {
+        ls.push_string(String::new());
+        return Ok(1);
+    }
+    let total = match (s.len() + sep.len()).checked_mul(n as usize) {
+        Some(total) if total <= MAX_SIZE => total,
+        _ => return Err(lib_error(ls, "resulting string too large")),
+    };
+    let mut b = Vec::new();
+    if b.try_reserve_exact(total).is_err() {
+        return Err(LuaError::memory());
+    }
+    for i in 0..n {
+        if i > 0 {
+            b.extend_from_slice(&sep);
+        }
+        b.extend_from_slice(&s);
+    }
+    ls.push_bytes(b);
+    Ok(1)
+}
+
+// string.byte (s [, i [, j]])
+fn byte(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    let s = check_bytes(ls, 1, "byte")?;
+    let l = s.len();
+    let posi = pos_relat(opt_integer(ls, 2, "byte", 1)?, l);
+    let pose = pos_relat(opt_integer(ls, 3, "byte", posi)?, l).min(l as i64);
+    let posi = posi.max(1);
+    if posi > pose {
+        return Ok(0); // empty interval; return no values
+    }
+    let n = (pose - posi) as usize + 1;
+    if n >= i32::MAX as usize || !ls.check_stack(n) {
+        return Err(lib_error(ls, "string slice too long"));
+    }
+    for &c in &s[posi as usize - 1..pose as usize] {
+        ls.push_integer(c as i64);
+    }
+    Ok(n)
+}
+
+// string.char (···)
+fn char(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    let n = ls.top();
+    let mut b = Vec::with_capacity(n as usize);
+    for i in 1..=n {
+        let c = check_integer(ls, i, "char")?;
+        if !(0..=u8::MAX as i64).contains(&c) {
+            return Err(arg_error(ls, i, "char", "value out of range"));
+        }
+        b.push(c as u8);
+    }
+    ls.push_bytes(b);
+    Ok(1)
+}
+
+// string.dump (function [, strip])
+fn dump(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    let strip = ls.to_boolean(2);
+    check_type(ls, 1, "dump", BasicType::LUA_TFUNCTION)?;
+    ls.set_top(1);
+    match ls.dump(strip) {
+        Some(b) => {
+            ls.push_bytes(b);
+            Ok(1)
+        }
+        None => Err(lib_error(ls, "unable to dump given function")),
+    }
+}
+
+/* pattern matching */
+
+fn push_capture(ls: &mut dyn LuaAPI, cap: Capture) {
+    match cap {
+        Capture::Str(b) => ls.push_bytes(b.to_vec()),
+        Capture::Position(i) => ls.push_integer(i as i64),
+    }
+}
+
+// pushes the captures of the last match, or the whole match when it is given
+// and the pattern has no captures
+fn push_captures(
+    ls: &mut dyn LuaAPI,
+    ms: &MatchState,
+    whole: Option<(usize, usize)>,
+) -> Result<usize, LuaError> {
+    let caps = ms.get_captures(whole).map_err(|msg| lib_error(ls, &msg))?;
+    let n = caps.len();
+    if !ls.check_stack(n) {
+        return Err(lib_error(ls, "stack overflow (too many captures)"));
+    }
+    for cap in caps {
+        push_capture(ls, cap);
+    }
+    Ok(n)
+}
+
+// the first occurrence of needle in haystack
+fn find_bytes(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    if needle.is_empty() {
+        return Some(0); // empty strings are everywhere
+    }
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn str_find_aux(ls: &mut dyn LuaAPI, find: bool) -> Result<usize, LuaError> {
+    let fname = if find { "find" } else { "match" };
+    let s = check_bytes(ls, 1, fname)?;
+    let p = check_bytes(ls, 2, fname)?;
+    let init = pos_relat(opt_integer(ls, 3, fname, 1)?, s.len()).max(1);
+    if init > s.len() as i64 + 1 {
+        // start after string's end?
+        ls.push_nil(); // cannot find anything
+        return Ok(1);
+    }
+    let init = init as usize - 1;
+    // explicit request or no special characters?
+    if find && (ls.to_boolean(4) || no_specials(&p)) {
+        // do a plain search
+        if let Some(i) = find_bytes(&s[init..], &p) {
+            let start = init + i;
+            ls.push_integer(start as i64 + 1);
+            ls.push_integer((start + p.len()) as i64);
+            return Ok(2);
+        }
+    } else {
+        let anchor = p.first() == Some(&b'^');
+        let p = if anchor { &p[1..] } else { &p[..] };
+        let mut ms = MatchState::new(&s, p);
+        for s1 in init..=s.len() {
+            ms.reprep();
+            let res = ms.do_match(s1, 0).map_err(|msg| lib_error(ls, &msg))?;
+            if let Some(e) = res {
+                if find {
+                    ls.push_integer(s1 as i64 + 1); // start
+                    ls.push_integer(e as i64); // end
+                    return Ok(push_captures(ls, &ms, None)? + 2);
+                } else {
+                    return push_captures(ls, &ms, Some((s1, e)));
+                }
+            }
+            if anchor {
+                break;
+            }
+        }
+    }
+    ls.push_nil(); // not found
+    Ok(1)
+}
+
+// string.find (s, pattern [, init [, plain]])
+fn find(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    str_find_aux(ls, true)
+}
+
+// string.match (s, pattern [, init])
+fn match_(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    str_find_aux(ls, false)
+}
+
+// string.gmatch (s, pattern)
+fn gmatch(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    check_bytes(ls, 1, "gmatch")?;
+    check_bytes(ls, 2, "gmatch")?;
+    ls.set_top(2);
+    ls.push_integer(0); // where the next search starts
+    ls.push_nil(); // end of last match
+    ls.push_rust_closure(RustFunction::Ptr(gmatch_aux), 4);
+    Ok(1)
+}
+
+// the iterator returned by gmatch: subject and pattern in upvalues 1 and 2,
+// its position in upvalues 3 and 4
+fn gmatch_aux(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    let s = ls.to_bytes(lua_upvalue_index(1));
+    let p = ls.to_bytes(lua_upvalue_index(2));
+    let src = ls.to_integer(lua_upvalue_index(3)) as usize;
+    let last_match = ls.to_integerx(lua_upvalue_index(4)).map(|e| e as usize);
+    let mut ms = MatchState::new(&s, &p);
+    for start in src..=s.len() {
+        ms.reprep();
+        let res = ms.do_match(start, 0).map_err(|msg| lib_error(ls, &msg))?;
+        if let Some(e) = res {
+            if Some(e) != last_match {
+                ls.push_integer(e as i64);
+                ls.copy(-1, lua_upvalue_index(4));
+                ls.replace(lua_upvalue_index(3));
+                return push_captures(ls, &ms, Some((start, e)));
+            }
+        }
+    }
+    Ok(0) // not found
+}
+
+// appends the replacement string at index 3, expanding its '%' escapes
+fn add_s(
+    ls: &mut dyn LuaAPI,
+    ms: &MatchState,
+    b: &mut Vec<u8>,
+    src: &[u8],
+    (s, e): (usize, usize),
+) -> Result<(), LuaError> {
+    let news = ls.to_bytes(3);
+    let mut iter = news.iter();
+    while let Some(&c) = iter.next() {
+        if c != b'%' {
+            b.push(c);
+            continue;
+        }
+        match iter.next().copied().unwrap_or(0) {
+            b'%' => b.push(b'%'),
+            b'0' => b.extend_from_slice(&src[s..e]),
+            d if d.is_ascii_digit() => {
+                let cap = ms
+                    .get_capture((d - b'1') as usize, s, e)
+                    .map_err(|msg| lib_error(ls, &msg))?;
+                match cap {
+                    Capture::Str(cap) => b.extend_from_slice(cap),
+                    Capture::Position(i) => b.extend_from_slice(i.to_string().as_bytes()),
+                }
+            }
+            _ => return Err(lib_error(ls, "invalid use of '%' in replacement string")),
+        }
+    }
+    Ok(())
+}
+
+// appends the replacement of the match s..e, as given by the value at index 3
+fn add_value(
+    ls: &mut dyn LuaAPI,
+    ms: &MatchState,
+    b: &mut Vec<u8>,
+    src: &[u8],
+    (s, e): (usize, usize),
+    tr: &BasicType,
+) -> Result<(), LuaError> {
+    match tr {
+        BasicType::LUA_TFUNCTION => {
+            ls.push_value(3);
+            let n = push_captures(ls, ms, Some((s, e)))?;
+            ls.call(n, 1)?;
+        }
+        BasicType::LUA_TTABLE => {
+            let cap = ms.get_capture(0, s, e).map_err(|msg| lib_error(ls, &msg))?;
+            push_capture(ls, cap);
+            ls.table(3)?;
+        }
+        // LUA_TNUMBER or LUA_TSTRING
+        _ => return add_s(ls, ms, b, src, (s, e)),
+    }
+    if !ls.to_boolean(-1) {
+        // nil or false? keep original text
+        b.extend_from_slice(&src[s..e]);
+    } else if let Some(r) = ls.to_bytesx(-1) {
+        b.extend_from_slice(&r);
+    } else {
+        let tname = ls.type_name_str(ls.type_enum_id(-1)).to_string();
+        return Err(lib_error(ls, &format!("invalid replacement value (a {})", tname)));
+    }
+    ls.pop(1);
+    Ok(())
+}
+
+// string.gsub (s, pattern, repl [, n])
+fn gsub(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    let src = check_bytes(ls, 1, "gsub")?; // subject
+    let p = check_bytes(ls, 2, "gsub")?; // pattern
+    let tr = ls.type_enum_id(3); // replacement type
+    let max_s = opt_integer(ls, 4, "gsub", src.len() as i64 + 1)?; // max replacements
+    if !matches!(
+        tr,
+        BasicType::LUA_TNUMBER
+            | BasicType::LUA_TSTRING
+            | BasicType::LUA_TFUNCTION
+            | BasicType::LUA_TTABLE
+    ) {
+        return Err(arg_error(ls, 3, "gsub", "string/function/table expected"));
+    }
+    let anchor = p.first() == Some(&b'^');
+    let p = if anchor { &p[1..] } else { &p[..] };
+    let mut ms = MatchState::new(&src, p);
+    let mut b = Vec::with_capacity(src.len());
+    let mut s = 0;
+    let mut last_match = None; // end of last match
+    let mut n = 0; // replacement count
+    while n < max_s {
+        ms.reprep(); // (re)prepare state for new match
+        let res = ms.do_match(s, 0).map_err(|msg| lib_error(ls, &msg))?;
+        match res {
+            Some(e) if Some(e) != last_match => {
+                n += 1;
+                add_value(ls, &ms, &mut b, &src, (s, e), &tr)?; // add replacement to buffer
+                s = e;
+                last_match = Some(e);
+            }
+            // otherwise, skip one character
+            _ if s < src.len() => {
+                b.push(src[s]);
+                s += 1;
+            }
+            _ => break, // end of subject
+        }
+        if anchor {
+            break;
+        }
+    }
+    b.extend_from_slice(&src[s..]);
+    ls.push_bytes(b);
+    ls.push_integer(n); // number of substitutions
+    Ok(2)
+}
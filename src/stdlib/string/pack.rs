@@ -0,0 +1,387 @@
+use crate::{api::lua_vm::LuaAPI, state::LuaError};
+
+use super::{pos_relat, MAX_SIZE};
+use crate::stdlib::{arg_error, check_bytes, check_integer, check_number, lib_error, opt_integer};
+
+const PACK_PAD_BYTE: u8 = 0x00; // value used for padding
+const MAX_INT_SIZE: usize = 16; // maximum size for the binary representation of an integer
+const SZINT: usize = std::mem::size_of::<i64>(); // size of a lua_Integer
+const MAX_ALIGN: usize = 8; // maximum alignment of '!' without a size
+
+#[derive(PartialEq)]
+enum KOption {
+    Int,       // signed integers
+    Uint,      // unsigned integers
+    Float,     // floating-point numbers
+    Char,      // fixed-length strings
+    String,    // strings with prefixed length
+    Zstr,      // zero-terminated strings
+    Padding,   // padding
+    PaddAlign, // padding for alignment
+    Nop,       // no-op (configuration or spaces)
+}
+
+// the state of reading a format string
+struct Header<'a> {
+    fname: &'static str,
+    fmt: &'a [u8],
+    pos: usize,
+    little: bool,
+    max_align: usize,
+}
+
+impl<'a> Header<'a> {
+    fn new(fname: &'static str, fmt: &'a [u8]) -> Self {
+        Header {
+            fname,
+            fmt,
+            pos: 0,
+            little: cfg!(target_endian = "little"),
+            max_align: 1,
+        }
+    }
+
+    fn done(&self) -> bool {
+        self.pos >= self.fmt.len()
+    }
+
+    fn is_digit(&self) -> bool {
+        self.fmt.get(self.pos).is_some_and(u8::is_ascii_digit)
+    }
+
+    // reads an optional number
+    fn get_num(&mut self) -> Option<usize> {
+        if !self.is_digit() {
+            return None;
+        }
+        let mut a = 0;
+        loop {
+            a = a * 10 + (self.fmt[self.pos] - b'0') as usize;
+            self.pos += 1;
+            if !(self.is_digit() && a <= (MAX_SIZE - 9) / 10) {
+                return Some(a);
+            }
+        }
+    }
+
+    // reads an optional integral size, df if it is missing
+    fn get_num_limit(&mut self, ls: &mut dyn LuaAPI, df: usize) -> Result<usize, LuaError> {
+        let sz = self.get_num().unwrap_or(df);
+        if sz > MAX_INT_SIZE || sz == 0 {
+            let msg = format!("integral size ({}) out of limits [1,{}]", sz, MAX_INT_SIZE);
+            return Err(lib_error(ls, &msg));
+        }
+        Ok(sz)
+    }
+
+    // reads the next option, returning it with its size
+    fn get_option(&mut self, ls: &mut dyn LuaAPI) -> Result<(KOption, usize), LuaError> {
+        let opt = self.fmt[self.pos];
+        self.pos += 1;
+        let option = match opt {
+            b'b' => (KOption::Int, 1),
+            b'B' => (KOption::Uint, 1),
+            b'h' => (KOption::Int, 2),
+            b'H' => (KOption::Uint, 2),
+            b'l' | b'j' => (KOption::Int, 8),
+            b'L' | b'J' | b'T' => (KOption::Uint, 8),
+            b'f' => (KOption::Float, 4),
+            b'd' | b'n' => (KOption::Float, 8),
+            b'i' => (KOption::Int, self.get_num_limit(ls, 4)?),
+            b'I' => (KOption::Uint, self.get_num_limit(ls, 4)?),
+            b's' => (KOption::String, self.get_num_limit(ls, 8)?),
+            b'c' => match self.get_num() {
+                Some(size) => (KOption::Char, size),
+                None => return Err(lib_error(ls, "missing size for format option 'c'")),
+            },
+            b'z' => (KOption::Zstr, 0),
+            b'x' => (KOption::Padding, 1),
+            b'X' => (KOption::PaddAlign, 0),
+            b' ' => (KOption::Nop, 0),
+            b'<' => {
+                self.little = true;
+                (KOption::Nop, 0)
+            }
+            b'>' => {
+                self.little = false;
+                (KOption::Nop, 0)
+            }
+            b'=' => {
+                self.little = cfg!(target_endian = "little");
+                (KOption::Nop, 0)
+            }
+            b'!' => {
+                self.max_align = self.get_num_limit(ls, MAX_ALIGN)?;
+                (KOption::Nop, 0)
+            }
+            _ => {
+                let msg = format!("invalid format option '{}'", opt as char);
+                return Err(lib_error(ls, &msg));
+            }
+        };
+        Ok(option)
+    }
+
+    // reads the next option with its size and the padding that aligns it after
+    // total_size bytes
+    fn get_details(
+        &mut self,
+        ls: &mut dyn LuaAPI,
+        total_size: usize,
+    ) -> Result<(KOption, usize, usize), LuaError> {
+        let (opt, size) = self.get_option(ls)?;
+        let mut align = size; // usually, alignment follows size
+        if opt == KOption::PaddAlign {
+            // 'X' gets alignment from following option
+            let next = if self.done() {
+                None
+            } else {
+                Some(self.get_option(ls)?)
+            };
+            match next {
+                Some((next, next_align)) if next != KOption::Char && next_align != 0 => {
+                    align = next_align;
+                }
+                _ => {
+                    let msg = "invalid next option for option 'X'";
+                    return Err(arg_error(ls, 1, self.fname, msg));
+                }
+            }
+        }
+        if align <= 1 || opt == KOption::Char {
+            // need no alignment
+            return Ok((opt, size, 0));
+        }
+        let align = align.min(self.max_align); // enforce maximum alignment
+        if !align.is_power_of_two() {
+            let msg = "format asks for alignment not power of 2";
+            return Err(arg_error(ls, 1, self.fname, msg));
+        }
+        let ntoalign = (align - (total_size & (align - 1))) & (align - 1);
+        Ok((opt, size, ntoalign))
+    }
+}
+
+fn pack_int(b: &mut Vec<u8>, n: u64, little: bool, size: usize, neg: bool) {
+    let start = b.len();
+    for i in 0..size {
+        let byte = if i < SZINT {
+            (n >> (i * 8)) as u8
+        } else if neg {
+            0xff // sign extension of negative numbers
+        } else {
+            0
+        };
+        b.push(byte);
+    }
+    if !little {
+        b[start..].reverse();
+    }
+}
+
+fn unpack_int(
+    ls: &mut dyn LuaAPI,
+    s: &[u8],
+    little: bool,
+    size: usize,
+    signed: bool,
+) -> Result<i64, LuaError> {
+    let byte = |i: usize| if little { s[i] } else { s[size - 1 - i] };
+    let limit = size.min(SZINT);
+    let mut res = (0..limit)
+        .rev()
+        .fold(0u64, |res, i| res << 8 | byte(i) as u64);
+    if size < SZINT {
+        // real size smaller than lua_Integer?
+        if signed {
+            // needs sign extension
+            let mask = 1u64 << (size * 8 - 1);
+            res = (res ^ mask).wrapping_sub(mask);
+        }
+    } else if size > SZINT {
+        // must check unread bytes
+        let mask = if !signed || (res as i64) >= 0 {
+            0
+        } else {
+            0xff
+        };
+        if (limit..size).any(|i| byte(i) != mask) {
+            let msg = format!("{}-byte integer does not fit into Lua Integer", size);
+            return Err(lib_error(ls, &msg));
+        }
+    }
+    Ok(res as i64)
+}
+
+// string.pack (fmt, v1, v2, ···)
+pub fn str_pack(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    let fmt = check_bytes(ls, 1, "pack")?; // format string
+    let mut h = Header::new("pack", &fmt);
+    let mut b = Vec::new();
+    let mut arg = 1; // current argument to pack
+    while !h.done() {
+        let (opt, size, ntoalign) = h.get_details(ls, b.len())?;
+        b.resize(b.len() + ntoalign, PACK_PAD_BYTE); // fill alignment
+        arg += 1;
+        match opt {
+            KOption::Int => {
+                // signed integers
+                let n = check_integer(ls, arg, "pack")?;
+                if size < SZINT {
+                    // need overflow check?
+                    let lim = 1i64 << (size * 8 - 1);
+                    if !(-lim..lim).contains(&n) {
+                        return Err(arg_error(ls, arg, "pack", "integer overflow"));
+                    }
+                }
+                pack_int(&mut b, n as u64, h.little, size, n < 0);
+            }
+            KOption::Uint => {
+                // unsigned integers
+                let n = check_integer(ls, arg, "pack")?;
+                if size < SZINT && (n as u64) >= 1u64 << (size * 8) {
+                    return Err(arg_error(ls, arg, "pack", "unsigned overflow"));
+                }
+                pack_int(&mut b, n as u64, h.little, size, false);
+            }
+            KOption::Float => {
+                // floating-point options
+                let n = check_number(ls, arg, "pack")?;
+                let mut buff = if size == 4 {
+                    (n as f32).to_le_bytes().to_vec()
+                } else {
+                    n.to_le_bytes().to_vec()
+                };
+                if !h.little {
+                    buff.reverse();
+                }
+                b.extend_from_slice(&buff);
+            }
+            KOption::Char => {
+                // fixed-size string
+                let s = check_bytes(ls, arg, "pack")?;
+                if s.len() > size {
+                    return Err(arg_error(ls, arg, "pack", "string longer than given size"));
+                }
+                b.extend_from_slice(&s);
+                b.resize(b.len() + size - s.len(), PACK_PAD_BYTE); // pad extra space
+            }
+            KOption::String => {
+                // strings with length count
+                let s = check_bytes(ls, arg, "pack")?;
+                if size < SZINT && s.len() as u64 >= 1u64 << (size * 8) {
+                    let msg = "string length does not fit in given size";
+                    return Err(arg_error(ls, arg, "pack", msg));
+                }
+                pack_int(&mut b, s.len() as u64, h.little, size, false); // pack length
+                b.extend_from_slice(&s);
+            }
+            KOption::Zstr => {
+                // zero-terminated string
+                let s = check_bytes(ls, arg, "pack")?;
+                if s.contains(&0) {
+                    return Err(arg_error(ls, arg, "pack", "string contains zeros"));
+                }
+                b.extend_from_slice(&s);
+                b.push(0); // add zero at the end
+            }
+            KOption::Padding => {
+                b.push(PACK_PAD_BYTE);
+                arg -= 1; // undo increment
+            }
+            KOption::PaddAlign | KOption::Nop => arg -= 1, // undo increment
+        }
+    }
+    ls.push_bytes(b);
+    Ok(1)
+}
+
+// string.packsize (fmt)
+pub fn str_packsize(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    let fmt = check_bytes(ls, 1, "packsize")?; // format string
+    let mut h = Header::new("packsize", &fmt);
+    let mut total_size = 0; // accumulate total size of result
+    while !h.done() {
+        let (opt, size, ntoalign) = h.get_details(ls, total_size)?;
+        let size = size + ntoalign; // total space used by option
+        if total_size > MAX_SIZE - size.min(MAX_SIZE) {
+            return Err(arg_error(ls, 1, "packsize", "format result too large"));
+        }
+        total_size += size;
+        if matches!(opt, KOption::String | KOption::Zstr) {
+            return Err(arg_error(ls, 1, "packsize", "variable-length format"));
+        }
+    }
+    ls.push_integer(total_size as i64);
+    Ok(1)
+}
+
+// string.unpack (fmt, s [, pos])
+pub fn str_unpack(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    let fmt = check_bytes(ls, 1, "unpack")?;
+    let data = check_bytes(ls, 2, "unpack")?;
+    let ld = data.len();
+    let pos = pos_relat(opt_integer(ls, 3, "unpack", 1)?, ld) - 1;
+    if pos < 0 || pos as usize > ld {
+        return Err(arg_error(ls, 3, "unpack", "initial position out of string"));
+    }
+    let mut pos = pos as usize;
+    let mut h = Header::new("unpack", &fmt);
+    let mut n = 0; // number of results
+    while !h.done() {
+        let (opt, size, ntoalign) = h.get_details(ls, pos)?;
+        if ntoalign + size > ld - pos {
+            return Err(arg_error(ls, 2, "unpack", "data string too short"));
+        }
+        pos += ntoalign; // skip alignment
+        if !ls.check_stack(2) {
+            // no stack space for item + next position
+            return Err(lib_error(ls, "stack overflow (too many results)"));
+        }
+        n += 1;
+        match opt {
+            KOption::Int | KOption::Uint => {
+                let signed = opt == KOption::Int;
+                let res = unpack_int(ls, &data[pos..], h.little, size, signed)?;
+                ls.push_integer(res);
+            }
+            KOption::Float => {
+                let mut buff = data[pos..pos + size].to_vec();
+                if !h.little {
+                    buff.reverse();
+                }
+                let num = if size == 4 {
+                    f32::from_le_bytes(buff.try_into().unwrap()) as f64
+                } else {
+                    f64::from_le_bytes(buff.try_into().unwrap())
+                };
+                ls.push_number(num);
+            }
+            KOption::Char => ls.push_bytes(data[pos..pos + size].to_vec()),
+            KOption::String => {
+                let len = unpack_int(ls, &data[pos..], h.little, size, false)? as u64;
+                if len > (ld - pos - size) as u64 {
+                    return Err(arg_error(ls, 2, "unpack", "data string too short"));
+                }
+                let len = len as usize;
+                ls.push_bytes(data[pos + size..pos + size + len].to_vec());
+                pos += len; // skip string
+            }
+            KOption::Zstr => {
+                let len = match data[pos..].iter().position(|&c| c == 0) {
+                    Some(len) => len,
+                    None => {
+                        let msg = "unfinished string for format 'z'";
+                        return Err(arg_error(ls, 2, "unpack", msg));
+                    }
+                };
+                ls.push_bytes(data[pos..pos + len].to_vec());
+                pos += len + 1; // skip string plus final '\0'
+            }
+            KOption::PaddAlign | KOption::Padding | KOption::Nop => n -= 1, // undo increment
+        }
+        pos += size;
+    }
+    ls.push_integer(pos as i64 + 1); // next position
+    Ok(n + 1)
+}
@@ -0,0 +1,388 @@
+// Lua patterns, a port of the matcher in lstrlib.c working on byte offsets
+// into the subject and the pattern instead of pointers
+
+const L_ESC: u8 = b'%';
+const SPECIALS: &[u8] = b"^$*+?.([%-";
+
+const MAXCCALLS: usize = 200; // maximum recursion depth of 'do_match'
+const LUA_MAXCAPTURES: usize = 32;
+
+const CAP_UNFINISHED: isize = -1;
+const CAP_POSITION: isize = -2;
+
+#[derive(Clone, Copy, Default)]
+struct CaptureSlot {
+    init: usize,
+    len: isize,
+}
+
+pub enum Capture<'a> {
+    Str(&'a [u8]),
+    Position(usize), // 1-based
+}
+
+pub struct MatchState<'a> {
+    src: &'a [u8],
+    pat: &'a [u8],
+    level: usize, // total number of captures (finished or unfinished)
+    capture: [CaptureSlot; LUA_MAXCAPTURES],
+    matchdepth: usize, // control for recursive depth (to avoid stack overflow)
+}
+
+// whether the pattern has no special characters, so it can be searched plainly
+pub fn no_specials(pat: &[u8]) -> bool {
+    !pat.iter().any(|c| SPECIALS.contains(c))
+}
+
+// isspace of the C locale, which unlike is_ascii_whitespace counts '\v'
+fn is_space(c: u8) -> bool {
+    matches!(c, b' ' | b'\t' | b'\n' | b'\x0b' | b'\x0c' | b'\r')
+}
+
+fn match_class(c: u8, cl: u8) -> bool {
+    let res = match cl.to_ascii_lowercase() {
+        b'a' => c.is_ascii_alphabetic(),
+        b'c' => c.is_ascii_control(),
+        b'd' => c.is_ascii_digit(),
+        b'g' => c.is_ascii_graphic(),
+        b'l' => c.is_ascii_lowercase(),
+        b'p' => c.is_ascii_punctuation(),
+        b's' => is_space(c),
+        b'u' => c.is_ascii_uppercase(),
+        b'w' => c.is_ascii_alphanumeric(),
+        b'x' => c.is_ascii_hexdigit(),
+        b'z' => c == 0, // deprecated option
+        _ => return cl == c,
+    };
+    if cl.is_ascii_uppercase() {
+        !res
+    } else {
+        res
+    }
+}
+
+impl<'a> MatchState<'a> {
+    pub fn new(src: &'a [u8], pat: &'a [u8]) -> Self {
+        MatchState {
+            src,
+            pat,
+            level: 0,
+            capture: [CaptureSlot::default(); LUA_MAXCAPTURES],
+            matchdepth: MAXCCALLS,
+        }
+    }
+
+    // prepares the state for a new match attempt
+    pub fn reprep(&mut self) {
+        self.level = 0;
+        self.matchdepth = MAXCCALLS;
+    }
+
+    // the pattern byte at p, '\0' past its end as with C strings
+    fn pat_at(&self, p: usize) -> u8 {
+        self.pat.get(p).copied().unwrap_or(0)
+    }
+
+    fn check_capture(&self, l: u8) -> Result<usize, String> {
+        let l = l as isize - b'1' as isize;
+        if l < 0 || l as usize >= self.level || self.capture[l as usize].len == CAP_UNFINISHED {
+            return Err(format!("invalid capture index %{}", l + 1));
+        }
+        Ok(l as usize)
+    }
+
+    fn capture_to_close(&self) -> Result<usize, String> {
+        (0..self.level)
+            .rev()
+            .find(|&l| self.capture[l].len == CAP_UNFINISHED)
+            .ok_or_else(|| "invalid pattern capture".to_string())
+    }
+
+    fn class_end(&self, mut p: usize) -> Result<usize, String> {
+        let c = self.pat[p];
+        p += 1;
+        match c {
+            L_ESC => {
+                if p >= self.pat.len() {
+                    return Err("malformed pattern (ends with '%')".to_string());
+                }
+                Ok(p + 1)
+            }
+            b'[' => {
+                if self.pat_at(p) == b'^' {
+                    p += 1;
+                }
+                // look for a ']'
+                loop {
+                    if p >= self.pat.len() {
+                        return Err("malformed pattern (missing ']')".to_string());
+                    }
+                    let c = self.pat[p];
+                    p += 1;
+                    if c == L_ESC && p < self.pat.len() {
+                        p += 1; // skip escapes (e.g. '%]')
+                    }
+                    if self.pat_at(p) == b']' {
+                        break;
+                    }
+                }
+                Ok(p + 1)
+            }
+            _ => Ok(p),
+        }
+    }
+
+    // whether c is in the set [...] starting at p and ending at ec (the ']')
+    fn match_bracket_class(&self, c: u8, mut p: usize, ec: usize) -> bool {
+        let mut sig = true;
+        if self.pat[p + 1] == b'^' {
+            sig = false;
+            p += 1; // skip the '^'
+        }
+        loop {
+            p += 1;
+            if p >= ec {
+                break;
+            }
+            if self.pat[p] == L_ESC {
+                p += 1;
+                if match_class(c, self.pat[p]) {
+                    return sig;
+                }
+            } else if self.pat[p + 1] == b'-' && p + 2 < ec {
+                p += 2;
+                if self.pat[p - 2] <= c && c <= self.pat[p] {
+                    return sig;
+                }
+            } else if self.pat[p] == c {
+                return sig;
+            }
+        }
+        !sig
+    }
+
+    fn single_match(&self, s: usize, p: usize, ep: usize) -> bool {
+        if s >= self.src.len() {
+            return false;
+        }
+        let c = self.src[s];
+        match self.pat[p] {
+            b'.' => true, // matches any char
+            L_ESC => match_class(c, self.pat[p + 1]),
+            b'[' => self.match_bracket_class(c, p, ep - 1),
+            pc => pc == c,
+        }
+    }
+
+    fn match_balance(&self, s: usize, p: usize) -> Result<Option<usize>, String> {
+        if p + 1 >= self.pat.len() {
+            return Err("malformed pattern (missing arguments to '%b')".to_string());
+        }
+        if s >= self.src.len() || self.src[s] != self.pat[p] {
+            return Ok(None);
+        }
+        let (b, e) = (self.pat[p], self.pat[p + 1]);
+        let mut cont = 1;
+        for (i, &c) in self.src.iter().enumerate().skip(s + 1) {
+            if c == e {
+                cont -= 1;
+                if cont == 0 {
+                    return Ok(Some(i + 1));
+                }
+            } else if c == b {
+                cont += 1;
+            }
+        }
+        Ok(None) // string ends out of balance
+    }
+
+    fn max_expand(&mut self, s: usize, p: usize, ep: usize) -> Result<Option<usize>, String> {
+        let mut i = 0; // counts maximum expand for item
+        while self.single_match(s + i, p, ep) {
+            i += 1;
+        }
+        // keeps trying to match with the maximum repetitions
+        loop {
+            if let Some(res) = self.do_match(s + i, ep + 1)? {
+                return Ok(Some(res));
+            }
+            if i == 0 {
+                return Ok(None);
+            }
+            i -= 1; // else didn't match; reduce 1 repetition to try again
+        }
+    }
+
+    fn min_expand(&mut self, mut s: usize, p: usize, ep: usize) -> Result<Option<usize>, String> {
+        loop {
+            if let Some(res) = self.do_match(s, ep + 1)? {
+                return Ok(Some(res));
+            } else if self.single_match(s, p, ep) {
+                s += 1; // try with one more repetition
+            } else {
+                return Ok(None);
+            }
+        }
+    }
+
+    fn start_capture(&mut self, s: usize, p: usize, what: isize) -> Result<Option<usize>, String> {
+        if self.level >= LUA_MAXCAPTURES {
+            return Err("too many captures".to_string());
+        }
+        self.capture[self.level] = CaptureSlot { init: s, len: what };
+        self.level += 1;
+        let res = self.do_match(s, p)?;
+        if res.is_none() {
+            self.level -= 1; // undo capture
+        }
+        Ok(res)
+    }
+
+    fn end_capture(&mut self, s: usize, p: usize) -> Result<Option<usize>, String> {
+        let l = self.capture_to_close()?;
+        self.capture[l].len = (s - self.capture[l].init) as isize; // close capture
+        let res = self.do_match(s, p)?;
+        if res.is_none() {
+            self.capture[l].len = CAP_UNFINISHED; // undo capture
+        }
+        Ok(res)
+    }
+
+    fn match_capture(&self, s: usize, l: u8) -> Result<Option<usize>, String> {
+        let l = self.check_capture(l)?;
+        let CaptureSlot { init, len } = self.capture[l];
+        let len = len as usize;
+        if self.src.len() - s >= len && self.src[init..init + len] == self.src[s..s + len] {
+            Ok(Some(s + len))
+        } else {
+            Ok(None)
+        }
+    }
+
+    // matches the pattern from p against the subject from s, returning the
+    // end of the match
+    pub fn do_match(&mut self, mut s: usize, mut p: usize) -> Result<Option<usize>, String> {
+        if self.matchdepth == 0 {
+            return Err("pattern too complex".to_string());
+        }
+        self.matchdepth -= 1;
+        // loops instead of recursing where lstrlib.c optimizes tail calls with goto
+        let res = loop {
+            if p == self.pat.len() {
+                break Some(s); // end of pattern
+            }
+            match self.pat[p] {
+                // start capture
+                b'(' => {
+                    break if self.pat_at(p + 1) == b')' {
+                        // position capture
+                        self.start_capture(s, p + 2, CAP_POSITION)?
+                    } else {
+                        self.start_capture(s, p + 1, CAP_UNFINISHED)?
+                    };
+                }
+                // end capture
+                b')' => break self.end_capture(s, p + 1)?,
+                // is the '$' the last char in pattern?
+                b'$' if p + 1 == self.pat.len() => {
+                    break if s == self.src.len() { Some(s) } else { None };
+                }
+                // balanced string?
+                L_ESC if self.pat_at(p + 1) == b'b' => match self.match_balance(s, p + 2)? {
+                    Some(e) => {
+                        s = e;
+                        p += 4;
+                    }
+                    None => break None,
+                },
+                // frontier?
+                L_ESC if self.pat_at(p + 1) == b'f' => {
+                    p += 2;
+                    if self.pat_at(p) != b'[' {
+                        return Err("missing '[' after '%f' in pattern".to_string());
+                    }
+                    let ep = self.class_end(p)?; // points to what is next
+                    let previous = if s == 0 { 0 } else { self.src[s - 1] };
+                    let current = self.src.get(s).copied().unwrap_or(0);
+                    if !self.match_bracket_class(previous, p, ep - 1)
+                        && self.match_bracket_class(current, p, ep - 1)
+                    {
+                        p = ep;
+                    } else {
+                        break None; // match failed
+                    }
+                }
+                // capture results (%0-%9)?
+                L_ESC if self.pat_at(p + 1).is_ascii_digit() => {
+                    match self.match_capture(s, self.pat[p + 1])? {
+                        Some(e) => {
+                            s = e;
+                            p += 2;
+                        }
+                        None => break None,
+                    }
+                }
+                _ => {
+                    let ep = self.class_end(p)?; // points to optional suffix
+                    if !self.single_match(s, p, ep) {
+                        // does not match at least once?
+                        if matches!(self.pat_at(ep), b'*' | b'?' | b'-') {
+                            // accept empty
+                            p = ep + 1;
+                            continue;
+                        }
+                        break None;
+                    }
+                    // matched once
+                    match self.pat_at(ep) {
+                        // optional
+                        b'?' => match self.do_match(s + 1, ep + 1)? {
+                            Some(res) => break Some(res),
+                            None => p = ep + 1,
+                        },
+                        // 1 or more repetitions
+                        b'+' => break self.max_expand(s + 1, p, ep)?,
+                        // 0 or more repetitions
+                        b'*' => break self.max_expand(s, p, ep)?,
+                        // 0 or more repetitions (minimum)
+                        b'-' => break self.min_expand(s, p, ep)?,
+                        // no suffix
+                        _ => {
+                            s += 1;
+                            p = ep;
+                        }
+                    }
+                }
+            }
+        };
+        self.matchdepth += 1;
+        Ok(res)
+    }
+
+    // capture i, or the whole match s..e when the pattern has no captures
+    pub fn get_capture(&self, i: usize, s: usize, e: usize) -> Result<Capture<'a>, String> {
+        if i >= self.level {
+            if i != 0 {
+                return Err(format!("invalid capture index %{}", i + 1));
+            }
+            return Ok(Capture::Str(&self.src[s..e])); // add whole match
+        }
+        let CaptureSlot { init, len } = self.capture[i];
+        match len {
+            CAP_UNFINISHED => Err("unfinished capture".to_string()),
+            CAP_POSITION => Ok(Capture::Position(init + 1)),
+            len => Ok(Capture::Str(&self.src[init..init + len as usize])),
+        }
+    }
+
+    // all the captures, or the whole match when there are none and it is given
+    pub fn get_captures(&self, whole: Option<(usize, usize)>) -> Result<Vec<Capture<'a>>, String> {
+        let n = if self.level == 0 && whole.is_some() {
+            1
+        } else {
+            self.level
+        };
+        let (s, e) = whole.unwrap_or((0, 0));
+        (0..n).map(|i| self.get_capture(i, s, e)).collect()
+    }
+}
@@ -0,0 +1,401 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{
+    api::{
+        basic::{BasicType, Comparison},
+        lua_vm::{LuaAPI, RustFn},
+    },
+    state::LuaError,
+};
+
+use super::{arg_error, check_bytes, check_integer, check_type, lib_error, opt_integer, set_funcs};
+
+// operations needed from a table argument that is not a table
+const TAB_R: u8 = 1; // read
+const TAB_W: u8 = 2; // write
+const TAB_L: u8 = 4; // length
+const TAB_RW: u8 = TAB_R | TAB_W; // read/write
+
+// sizes above which sort randomizes its pivots
+const RANLIMIT: i64 = 100;
+
+const TAB_FUNCS: &[(&str, RustFn)] = &[
+    ("concat", concat),
+    ("insert", insert),
+    ("move", move_),
+    ("pack", pack),
+    ("remove", remove),
+    ("sort", sort),
+    ("unpack", unpack),
+];
+
+pub fn open_table(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    ls.create_table(0, TAB_FUNCS.len());
+    set_funcs(ls, TAB_FUNCS)?;
+    Ok(1)
+}
+
+// whether the metatable n slots down the stack has the field key
+fn check_field(ls: &mut dyn LuaAPI, key: &str, n: isize) -> Result<bool, LuaError> {
+    ls.push_string(key.to_string());
+    Ok(ls.raw_get(-n)? != BasicType::LUA_TNIL)
+}
+
+// checks that arg is a table, or has the metamethods 'what' needs
+fn check_tab(ls: &mut dyn LuaAPI, arg: isize, fname: &str, what: u8) -> Result<(), LuaError> {
+    if ls.type_enum_id(arg) != BasicType::LUA_TTABLE {
+        let mut n = 1; // number of elements to pop
+        if ls.get_metatable(arg)
+            && (what & TAB_R == 0 || {
+                n += 1;
+                check_field(ls, "__index", n)?
+            })
+            && (what & TAB_W == 0 || {
+                n += 1;
+                check_field(ls, "__newindex", n)?
+            })
+            && (what & TAB_L == 0 || {
+                n += 1;
+                check_field(ls, "__len", n)?
+            })
+        {
+            ls.pop(n as usize); // pop metatable and tested metamethods
+        } else {
+            check_type(ls, arg, fname, BasicType::LUA_TTABLE)?; // force an error
+        }
+    }
+    Ok(())
+}
+
+// the length of the value at idx, honoring '__len'
+fn len_of(ls: &mut dyn LuaAPI, idx: isize) -> Result<i64, LuaError> {
+    ls.len(idx)?;
+    match ls.to_integerx(-1) {
+        Some(n) => {
+            ls.pop(1);
+            Ok(n)
+        }
+        None => Err(lib_error(ls, "object length is not an integer")),
+    }
+}
+
+fn aux_getn(ls: &mut dyn LuaAPI, arg: isize, fname: &str, what: u8) -> Result<i64, LuaError> {
+    check_tab(ls, arg, fname, what | TAB_L)?;
+    len_of(ls, arg)
+}
+
+// table.insert (list, [pos,] value)
+fn insert(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    let e = aux_getn(ls, 1, "insert", TAB_RW)?.wrapping_add(1); // first empty element
+    let pos = match ls.top() {
+        // called with only 2 arguments: insert new element at the end
+        2 => e,
+        3 => {
+            let pos = check_integer(ls, 2, "insert")?; // 2nd argument is the position
+            if !(1..=e).contains(&pos) {
+                return Err(arg_error(ls, 2, "insert", "position out of bounds"));
+            }
+            // move up elements
+            for i in (pos + 1..=e).rev() {
+                ls.i(1, i - 1)?;
+                ls.set_i(1, i)?; // t[i] = t[i - 1]
+            }
+            pos
+        }
+        _ => return Err(lib_error(ls, "wrong number of arguments to 'insert'")),
+    };
+    ls.set_i(1, pos)?; // t[pos] = v
+    Ok(0)
+}
+
+// table.remove (list [, pos])
+fn remove(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    let size = aux_getn(ls, 1, "remove", TAB_RW)?;
+    let mut pos = opt_integer(ls, 2, "remove", size)?;
+    // validate 'pos' if given
+    if pos != size && (pos as u64).wrapping_sub(1) > size as u64 {
+        return Err(arg_error(ls, 2, "remove", "position out of bounds"));
+    }
+    ls.i(1, pos)?; // result = t[pos]
+    while pos < size {
+        ls.i(1, pos + 1)?;
+        ls.set_i(1, pos)?; // t[pos] = t[pos + 1]
+        pos += 1;
+    }
+    ls.push_nil();
+    ls.set_i(1, pos)?; // t[pos] = nil
+    Ok(1)
+}
+
+// table.move (a1, f, e, t [,a2])
+fn move_(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    let f = check_integer(ls, 2, "move")?;
+    let e = check_integer(ls, 3, "move")?;
+    let t = check_integer(ls, 4, "move")?;
+    let tt = if ls.is_none_or_nil(5) { 1 } else { 5 }; // destination table
+    check_tab(ls, 1, "move", TAB_R)?;
+    check_tab(ls, tt, "move", TAB_W)?;
+    if e >= f {
+        // otherwise, nothing to move
+        if !(f > 0 || e < i64::MAX + f) {
+            return Err(arg_error(ls, 3, "move", "too many elements to move"));
+        }
+        let n = e - f + 1; // number of elements to move
+        if t > i64::MAX - n + 1 {
+            return Err(arg_error(ls, 4, "move", "destination wrap around"));
+        }
+        if t > e || t <= f || (tt != 1 && !ls.compare(1, tt, Comparison::LUA_OPEQ)?) {
+            for i in 0..n {
+                ls.i(1, f + i)?;
+                ls.set_i(tt, t + i)?;
+            }
+        } else {
+            // overlapping ranges, copied from the end
+            for i in (0..n).rev() {
+                ls.i(1, f + i)?;
+                ls.set_i(tt, t + i)?;
+            }
+        }
+    }
+    ls.push_value(tt); // return destination table
+    Ok(1)
+}
+
+fn add_field(ls: &mut dyn LuaAPI, b: &mut Vec<u8>, i: i64) -> Result<(), LuaError> {
+    ls.i(1, i)?;
+    if !ls.is_string(-1) {
+        let msg = format!("invalid value (at index {}) in table for 'concat'", i);
+        return Err(lib_error(ls, &msg));
+    }
+    b.extend_from_slice(&ls.to_bytes(-1));
+    ls.pop(1);
+    Ok(())
+}
+
+// table.concat (list [, sep [, i [, j]]])
+fn concat(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    let last = aux_getn(ls, 1, "concat", TAB_R)?;
+    let sep = if ls.is_none_or_nil(2) {
+        Vec::new()
+    } else {
+        check_bytes(ls, 2, "concat")?
+    };
+    let mut i = opt_integer(ls, 3, "concat", 1)?;
+    let last = opt_integer(ls, 4, "concat", last)?;
+    let mut b = Vec::new();
+    while i < last {
+        add_field(ls, &mut b, i)?;
+        b.extend_from_slice(&sep);
+        i += 1;
+    }
+    if i == last {
+        // add last value (if interval was not empty)
+        add_field(ls, &mut b, i)?;
+    }
+    ls.push_bytes(b);
+    Ok(1)
+}
+
+// table.pack (···)
+fn pack(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    let n = ls.top(); // number of elements to pack
+    ls.create_table(n as usize, 1); // create result table
+    ls.insert(1); // put it at index 1
+    for i in (1..=n).rev() {
+        // assign elements
+        ls.set_i(1, i as i64)?;
+    }
+    ls.push_integer(n as i64);
+    ls.set_field(1, "n")?; // t.n = number of elements
+    Ok(1) // return table
+}
+
+// table.unpack (list [, i [, j]])
+fn unpack(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    let mut i = opt_integer(ls, 2, "unpack", 1)?;
+    let e = if ls.is_none_or_nil(3) {
+        len_of(ls, 1)?
+    } else {
+        check_integer(ls, 3, "unpack")?
+    };
+    if i > e {
+        return Ok(0); // empty range
+    }
+    let n = (e as u64).wrapping_sub(i as u64); // number of elements minus 1 (avoid overflows)
+    if n >= i32::MAX as u64 || !ls.check_stack(n as usize + 1) {
+        return Err(lib_error(ls, "too many results to unpack"));
+    }
+    // push arg[i..e - 1] (to avoid overflows)
+    while i < e {
+        ls.i(1, i)?;
+        i += 1;
+    }
+    ls.i(1, e)?; // push last element
+    Ok(n as usize + 1)
+}
+
+/* quicksort, as in ltablib.c */
+
+// a[a] < a[b], with the comparison function at index 2 if there is one
+fn sort_comp(ls: &mut dyn LuaAPI, a: isize, b: isize) -> Result<bool, LuaError> {
+    if ls.is_nil(2) {
+        // no function
+        return ls.compare(a, b, Comparison::LUA_OPLT); // a < b
+    }
+    ls.push_value(2); // push function
+    ls.push_value(a - 1); // -1 to compensate function
+    ls.push_value(b - 2); // -2 to compensate function and 'a'
+    ls.call(2, 1)?; // call function
+    let res = ls.to_boolean(-1); // get result
+    ls.pop(1); // pop result
+    Ok(res)
+}
+
+// sets a[i] and a[j] to the top two values, popping them
+fn set2(ls: &mut dyn LuaAPI, i: i64, j: i64) -> Result<(), LuaError> {
+    ls.set_i(1, i)?;
+    ls.set_i(1, j)
+}
+
+fn sort_error(ls: &mut dyn LuaAPI) -> LuaError {
+    lib_error(ls, "invalid order function for sorting")
+}
+
+// partitions a[lo..up] around the pivot P on the top of the stack, which is
+// also in a[up - 1], returning its final position
+fn partition(ls: &mut dyn LuaAPI, lo: i64, up: i64) -> Result<i64, LuaError> {
+    let mut i = lo; // will be incremented before first use
+    let mut j = up - 1; // will be decremented before first use
+
+    // loop invariant: a[lo .. i] <= P <= a[j .. up], a[up - 1] == P
+    loop {
+        // next loop: repeat ++i while a[i] < P
+        loop {
+            i += 1;
+            ls.i(1, i)?;
+            if !sort_comp(ls, -1, -2)? {
+                break;
+            }
+            if i == up - 1 {
+                // a[i] < P  but a[up - 1] == P  ??
+                return Err(sort_error(ls));
+            }
+            ls.pop(1); // remove a[i]
+        }
+        // after the loop, a[i] >= P and a[lo .. i - 1] < P
+        // next loop: repeat --j while P < a[j]
+        loop {
+            j -= 1;
+            ls.i(1, j)?;
+            if !sort_comp(ls, -3, -1)? {
+                break;
+            }
+            if j < i {
+                // j < i  but  a[j] > P ??
+                return Err(sort_error(ls));
+            }
+            ls.pop(1); // remove a[j]
+        }
+        // after the loop, a[j] <= P and a[j + 1 .. up] >= P
+        if j < i {
+            // no elements to be exchanged
+            ls.pop(1); // pop a[j]
+
+            // swap pivot (a[up - 1]) with a[i] to satisfy pred.: a[up - 1] <= P
+            set2(ls, up - 1, i)?;
+            return Ok(i);
+        }
+        // otherwise, swap a[i] - a[j] to restore invariant and repeat
+        set2(ls, i, j)?;
+    }
+}
+
+// a pivot in the middle half of lo..up, chosen by rnd
+fn choose_pivot(lo: i64, up: i64, rnd: u32) -> i64 {
+    let r4 = (up - lo) / 4; // range/4
+    (rnd as i64) % (r4 * 2) + (lo + r4)
+}
+
+fn randomize_pivot() -> u32 {
+    let t = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    t.subsec_nanos() ^ t.as_secs() as u32
+}
+
+fn aux_sort(ls: &mut dyn LuaAPI, mut lo: i64, mut up: i64, mut rnd: u32) -> Result<(), LuaError> {
+    // loop for tail recursion
+    while lo < up {
+        // sort elements 'lo', 'p', and 'up'
+        ls.i(1, lo)?;
+        ls.i(1, up)?;
+        if sort_comp(ls, -1, -2)? {
+            set2(ls, lo, up)?; // a[up] < a[lo]: swap a[lo] - a[up]
+        } else {
+            ls.pop(2); // remove both values
+        }
+        if up - lo == 1 {
+            break; // only 2 elements: already sorted
+        }
+        let mut p = if up - lo < RANLIMIT || rnd == 0 {
+            (lo + up) / 2 // middle element is a good pivot
+        } else {
+            // for larger intervals, it is expensive to solve worst cases
+            choose_pivot(lo, up, rnd)
+        };
+        ls.i(1, p)?;
+        ls.i(1, lo)?;
+        if sort_comp(ls, -2, -1)? {
+            set2(ls, p, lo)?; // a[p] < a[lo]: swap a[p] - a[lo]
+        } else {
+            ls.pop(1); // remove second element
+            ls.i(1, up)?;
+            if sort_comp(ls, -1, -2)? {
+                set2(ls, p, up)?; // a[up] < a[p]: swap up - p
+            } else {
+                ls.pop(2); // clean stack
+            }
+        }
+        if up - lo == 2 {
+            break; // only 3 elements: already sorted
+        }
+        ls.i(1, p)?; // get median (Pivot)
+        ls.push_value(-1); // push Pivot
+        ls.i(1, up - 1)?; // push a[up - 1]
+        set2(ls, p, up - 1)?; // a[p] = a[up - 1]; a[up - 1] = a[p]
+        p = partition(ls, lo, up)?;
+        // a[lo .. p - 1] <= a[p] == P <= a[p + 1 .. up]
+        let n; // size of the smaller interval
+        if p - lo < up - p {
+            // lower interval is shorter
+            aux_sort(ls, lo, p - 1, rnd)?;
+            n = p - lo;
+            lo = p + 1; // tail call for [p + 1 .. up] (upper interval)
+        } else {
+            aux_sort(ls, p + 1, up, rnd)?;
+            n = up - p;
+            up = p - 1; // tail call for [lo .. p - 1]  (lower interval)
+        }
+        if (up - lo) / 128 > n {
+            rnd = randomize_pivot(); // partition too imbalanced: try a new randomization
+        }
+    }
+    Ok(())
+}
+
+// table.sort (list [, comp])
+fn sort(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+    let n = aux_getn(ls, 1, "sort", TAB_RW)?;
+    if n > 1 {
+        // non-trivial interval
+        if n >= i32::MAX as i64 {
+            return Err(arg_error(ls, 1, "sort", "array too big"));
+        }
+        if !ls.is_none_or_nil(2) {
+            check_type(ls, 2, "sort", BasicType::LUA_TFUNCTION)?; // must be a function
+        }
+        ls.set_top(2); // make sure there are two arguments
+        aux_sort(ls, 1, n, 0)?;
+    }
+    Ok(0)
+}
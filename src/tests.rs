@@ -0,0 +1,179 @@
+use std::{cell::Cell, rc::Rc};
+
+use crate::{
+    api::{
+        basic::LUA_OK,
+        lua_vm::{LuaAPI, RustFunction},
+    },
+    binary::lister,
+    compiler,
+    state::{self, LuaError, LuaState},
+};
+
+// a state with the standard libraries open
+fn new_state() -> LuaState {
+    let mut ls = state::new_lua_state();
+    ls.open_libs().unwrap();
+    ls
+}
+
+// runs chunk in protected mode, returning its status and the results or the error message
+fn run(ls: &mut LuaState, chunk: &str) -> (u8, Vec<String>) {
+    let status = ls.load(chunk.as_bytes().to_vec(), "=test", "t");
+    let status = if status == LUA_OK {
+        ls.pcall(0, -1, 0)
+    } else {
+        status
+    };
+    let results = (1..=ls.top()).map(|i| ls.to_string(i)).collect();
+    ls.set_top(0);
+    (status, results)
+}
+
+#[test]
+fn rust_closures_keep_captured_state() {
+    let mut ls = new_state();
+    let calls = Rc::new(Cell::new(0));
+    let counter = calls.clone();
+    ls.register(
+        "count",
+        RustFunction::new_fn(move |ls| {
+            counter.set(counter.get() + 1);
+            ls.push_integer(counter.get());
+            Ok(1)
+        }),
+    );
+    let mut total = 0;
+    ls.push_rust_fn(RustFunction::new_fn_mut(move |ls| {
+        total += ls.to_integer(1);
+        ls.push_integer(total);
+        Ok(1)
+    }));
+    ls.set_global("add").unwrap();
+
+    let (status, results) = run(
+        &mut ls,
+        "count() count() add(5) return count(), add(10), add(-1)",
+    );
+    assert_eq!(status, LUA_OK);
+    assert_eq!(results, ["3", "15", "14"]);
+    assert_eq!(calls.get(), 3);
+    // the captured state lives as long as the function values
+    let (_, results) = run(&mut ls, "return count(), add(1)");
+    assert_eq!(results, ["4", "15"]);
+    assert_eq!(calls.get(), 4);
+}
+
+#[test]
+fn rust_fn_mut_closures_cannot_reenter() {
+    let mut ls = new_state();
+    ls.register(
+        "reenter",
+        RustFunction::new_fn_mut(|ls| {
+            ls.global("reenter")?;
+            ls.call(0, 0)?;
+            Ok(0)
+        }),
+    );
+    let (status, results) = run(&mut ls, "return reenter()");
+    assert_ne!(status, LUA_OK);
+    assert_eq!(results, ["attempt to re-enter a running Rust closure"]);
+}
+
+#[test]
+fn api_calls_on_wrong_types_return_errors() {
+    let mut ls = new_state();
+    ls.new_table();
+    ls.push_integer(1);
+    assert_eq!(
+        ls.set_metatable(1).unwrap_err().to_string(),
+        "table expected"
+    );
+    ls.push_integer(7);
+    ls.push_integer(1);
+    assert_eq!(ls.raw_get(2).unwrap_err().to_string(), "table expected");
+    assert_eq!(
+        ls.raw_get_i(2, 1).unwrap_err().to_string(),
+        "table expected"
+    );
+    ls.push_integer(1);
+    ls.push_boolean(true);
+    assert_eq!(ls.raw_set(2).unwrap_err().to_string(), "table expected");
+    assert_eq!(
+        ls.thread_status(2).unwrap_err().to_string(),
+        "thread expected"
+    );
+    ls.push_nil();
+    assert_eq!(ls.xmove(2, 1).unwrap_err().to_string(), "thread expected");
+    assert_ne!(ls.resume(2, 0), LUA_OK);
+    assert_eq!(ls.to_string(-1), "thread expected");
+}
+
+#[test]
+fn rust_functions_cannot_return_more_results_than_they_pushed() {
+    fn overclaims(ls: &mut dyn LuaAPI) -> Result<usize, LuaError> {
+        ls.push_integer(1);
+        Ok(3)
+    }
+    let mut ls = new_state();
+    ls.register("overclaims", RustFunction::Ptr(overclaims));
+    let (status, results) = run(&mut ls, "return overclaims('arg')");
+    assert_ne!(status, LUA_OK);
+    assert_eq!(
+        results,
+        ["test:1: Rust function returned 3 results but left only 2 values"]
+    );
+    let (status, results) = run(&mut ls, "return select(2, pcall(overclaims))");
+    assert_eq!(status, LUA_OK);
+    assert_eq!(
+        results,
+        ["Rust function returned 3 results but left only 1 values"]
+    );
+}
+
+// what `luac -l -l` prints for the source below, with the addresses masked
+const LUAC_LISTING: &str = "
+main <l.lua:0,0> (13 instructions at 0x?)
+0+ params, 6 slots, 1 upvalue, 5 locals, 5 constants, 0 functions
+	1	[1]	NEWTABLE 	0 0 0
+	2	[2]	LOADK    	1 -1	; 1
+	3	[2]	LOADK    	2 -2	; 3
+	4	[2]	LOADK    	3 -1	; 1
+	5	[2]	FORPREP  	1 2	; to 8
+	6	[3]	MUL      	5 4 -3	; - 2
+	7	[3]	SETTABLE 	0 4 5
+	8	[2]	FORLOOP  	1 -3	; to 6
+	9	[5]	GETTABUP 	1 0 -4	; _ENV \"print\"
+	10	[5]	GETTABLE 	2 0 -3	; 2
+	11	[5]	LOADK    	3 -5	; \"x\"
+	12	[5]	CALL     	1 3 1
+	13	[5]	RETURN   	0 1
+constants (5) for 0x?:
+	1	1
+	2	3
+	3	2
+	4	\"print\"
+	5	\"x\"
+locals (5) for 0x?:
+	0	t	2	14
+	1	(for index)	3	9
+	2	(for limit)	4	9
+	3	(for step)	5	9
+	4	i	6	8
+upvalues (1) for 0x?:
+	0	_ENV	1	0
+";
+
+#[test]
+fn listing_matches_luac() {
+    let source = "local t = {}\nfor i = 1, 3 do\n  t[i] = i * 2\nend\nprint(t[2], \"x\")\n";
+    let proto = compiler::compile(source.as_bytes(), "@l.lua").unwrap();
+    let listing = lister::list(&proto, true);
+    let mut parts = listing.split("0x");
+    let mut masked = parts.next().unwrap().to_string();
+    for part in parts {
+        masked.push_str("0x?");
+        masked.push_str(part.trim_start_matches(|c: char| c.is_ascii_hexdigit()));
+    }
+    assert_eq!(masked, LUAC_LISTING);
+}
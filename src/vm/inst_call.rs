@@ -1,25 +1,46 @@
-use super::instruction::Instruction;
-use crate::api::lua_vm::LuaVM;
+use super::{
+    instruction::Instruction,
+    opcode::{OP_CALL, OP_TAILCALL, OP_TFORCALL},
+};
+use crate::{api::lua_vm::LuaVM, state::LuaError};
 
 // R(A) := closure(KPROTO[Bx])
-pub fn closure(i: u32, vm: &mut dyn LuaVM) {
+pub fn closure(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
     let (mut a, bx) = i.a_bx();
     a += 1;
 
     vm.load_proto(bx as usize);
     vm.replace(a);
+    Ok(())
 }
 
-pub fn call(i: u32, vm: &mut dyn LuaVM) {
+pub fn call(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
     let (mut a, b, c) = i.abc();
     a += 1;
 
     let nargs = push_func_and_args(a, b, vm);
-    vm.call(nargs, c - 1);
-    pop_results(a, c, vm);
+    // a Lua callee runs in the interpreter loop, which calls finish_call
+    if vm.pre_call(nargs, c - 1)? {
+        pop_results(a, c, vm);
+    }
+    Ok(())
 }
 
-fn push_func_and_args(a: isize, b: isize, vm: &mut dyn LuaVM) -> usize {
+// moves the results of the call made by instruction i into its registers
+pub fn finish_call(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
+    let (mut a, _, c) = i.abc();
+    a += 1;
+
+    match i.opcode() {
+        OP_CALL => pop_results(a, c, vm),
+        OP_TAILCALL => pop_results(a, 0, vm),
+        OP_TFORCALL => pop_results(a + 3, c + 1, vm),
+        _ => unreachable!("{} does not call", i.opname()),
+    }
+    Ok(())
+}
+
+pub(super) fn push_func_and_args(a: isize, b: isize, vm: &mut dyn LuaVM) -> usize {
     if b >= 1 {
         vm.check_stack(b as usize);
         for i in a..(a + b) {
@@ -32,7 +53,7 @@ fn push_func_and_args(a: isize, b: isize, vm: &mut dyn LuaVM) -> usize {
     }
 }
 
-fn pop_results(a: isize, c: isize, vm: &mut dyn LuaVM) {
+pub(super) fn pop_results(a: isize, c: isize, vm: &mut dyn LuaVM) {
     match c.cmp(&1) {
         std::cmp::Ordering::Less => {
             vm.check_stack(1);
@@ -59,10 +80,11 @@ fn fix_stack(a: isize, vm: &mut dyn LuaVM) {
 }
 
 // return R(A), ... ,R(A+B-2)
-pub fn call_return(i: u32, vm: &mut dyn LuaVM) {
+pub fn call_return(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
     let (mut a, b, _) = i.abc();
     a += 1;
 
+    vm.close_upvalues(1);
     match b.cmp(&1) {
         std::cmp::Ordering::Less => {
             fix_stack(a, vm);
@@ -75,10 +97,11 @@ pub fn call_return(i: u32, vm: &mut dyn LuaVM) {
             }
         }
     }
+    Ok(())
 }
 
 // R(A), R(A+1), ..., R(A+B-2) = vararg
-pub fn vararg(i: u32, vm: &mut dyn LuaVM) {
+pub fn vararg(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
     let (mut a, b, _) = i.abc();
     a += 1;
 
@@ -86,28 +109,32 @@ pub fn vararg(i: u32, vm: &mut dyn LuaVM) {
         vm.load_vararg(b - 1);
         pop_results(a, b, vm)
     }
+    Ok(())
 }
 
 // return R(A)(R(A+1), ... ,R(A+B-1))
-pub fn tail_call(i: u32, vm: &mut dyn LuaVM) {
+pub fn tail_call(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
     let (mut a, b, _) = i.abc();
     a += 1;
 
-    // todo: optimize tail call!
-    let c = 0;
+    // a Lua callee takes the place of this frame and returns to its caller;
+    // a Rust one returns here, and the RETURN that follows passes its results on
     let nargs = push_func_and_args(a, b, vm);
-    vm.call(nargs, c - 1);
-    pop_results(a, c, vm);
+    if vm.pre_tail_call(nargs)? {
+        pop_results(a, 0, vm);
+    }
+    Ok(())
 }
 
 // R(A+1) := R(B); R(A) := R(B)[RK(C)]
-pub fn call_self(i: u32, vm: &mut dyn LuaVM) {
+pub fn call_self(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
     let (mut a, mut b, c) = i.abc();
     a += 1;
     b += 1;
 
     vm.copy(b, a + 1);
     vm.get_rk(c);
-    vm.table(b);
+    vm.table(b)?;
     vm.replace(a);
+    Ok(())
 }
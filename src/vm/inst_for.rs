@@ -1,54 +1,90 @@
-use super::instruction::Instruction;
-use crate::api::{
-    basic::{Arithmetic, BasicType, Comparison},
-    lua_vm::LuaVM,
+use super::{
+    inst_call::{pop_results, push_func_and_args},
+    instruction::Instruction,
+};
+use crate::{
+    api::{
+        basic::{Arithmetic, BasicType, Comparison},
+        lua_vm::LuaVM,
+    },
+    state::LuaError,
 };
 
 // R(A)-=R(A+2); pc+=sBx
-pub fn for_prep(i: u32, vm: &mut dyn LuaVM) {
+pub fn for_prep(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
     let (mut a, sbx) = i.a_sbx();
     a += 1;
 
-    if vm.type_enum_id(a) == BasicType::LUA_TSTRING {
-        vm.push_number(vm.to_number(a));
-        vm.replace(a);
-    }
-    if vm.type_enum_id(a + 1) == BasicType::LUA_TSTRING {
-        vm.push_number(vm.to_number(a + 1));
-        vm.replace(a + 1);
-    }
-    if vm.type_enum_id(a + 2) == BasicType::LUA_TSTRING {
-        vm.push_number(vm.to_number(a + 2));
-        vm.replace(a + 2);
+    for (idx, what) in [(a, "initial value"), (a + 1, "limit"), (a + 2, "step")] {
+        match vm.type_enum_id(idx) {
+            BasicType::LUA_TNUMBER => {}
+            BasicType::LUA_TSTRING if vm.to_numberx(idx).is_some() => {
+                vm.push_number(vm.to_number(idx));
+                vm.replace(idx);
+            }
+            _ => {
+                let pos = vm.position(0);
+                vm.push_string(format!("{}'for' {} must be a number", pos, what));
+                return Err(vm.error());
+            }
+        }
     }
 
     vm.push_value(a);
     vm.push_value(a + 2);
-    vm.arith(Arithmetic::LUA_OPSUB);
+    vm.arith(Arithmetic::LUA_OPSUB)?;
     vm.replace(a);
     vm.add_pc(sbx);
+    Ok(())
 }
 
 // R(A)+=R(A+2);
 // if R(A) <?= R(A+1) then {
 //   pc+=sBx; R(A+3)=R(A)
 // }
-pub fn for_loop(i: u32, vm: &mut dyn LuaVM) {
+pub fn for_loop(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
     let (mut a, sbx) = i.a_sbx();
     a += 1;
 
     // R(A)+=R(A+2);
     vm.push_value(a + 2);
     vm.push_value(a);
-    vm.arith(Arithmetic::LUA_OPADD);
+    vm.arith(Arithmetic::LUA_OPADD)?;
     vm.replace(a);
 
     let positive_step = vm.to_number(a + 2) >= 0.0;
-    if positive_step && vm.compare(a, a + 1, Comparison::LUA_OPLE)
-        || !positive_step && vm.compare(a + 1, a, Comparison::LUA_OPLE)
+    if positive_step && vm.compare(a, a + 1, Comparison::LUA_OPLE)?
+        || !positive_step && vm.compare(a + 1, a, Comparison::LUA_OPLE)?
     {
         // pc+=sBx; R(A+3)=R(A)
         vm.add_pc(sbx);
         vm.copy(a, a + 3);
     }
+    Ok(())
+}
+
+// R(A+3), ... ,R(A+2+C) := R(A)(R(A+1), R(A+2));
+pub fn tfor_call(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
+    let (mut a, _, c) = i.abc();
+    a += 1;
+
+    push_func_and_args(a, 3, vm);
+    if vm.pre_call(2, c)? {
+        pop_results(a + 3, c + 1, vm);
+    }
+    Ok(())
+}
+
+// if R(A+1) ~= nil then {
+//   R(A)=R(A+1); pc += sBx
+// }
+pub fn tfor_loop(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
+    let (mut a, sbx) = i.a_sbx();
+    a += 1;
+
+    if !vm.is_nil(a + 1) {
+        vm.copy(a + 1, a);
+        vm.add_pc(sbx);
+    }
+    Ok(())
 }
@@ -1,8 +1,8 @@
 use super::instruction::Instruction;
-use crate::api::lua_vm::LuaVM;
+use crate::{api::lua_vm::LuaVM, state::LuaError};
 
 // R(A), R(A+1), ..., R(A+B) := nil
-pub fn load_nil(i: u32, vm: &mut dyn LuaVM) {
+pub fn load_nil(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
     let (mut a, b, _) = i.abc();
     a += 1;
 
@@ -11,10 +11,11 @@ pub fn load_nil(i: u32, vm: &mut dyn LuaVM) {
         vm.copy(-1, i);
     }
     vm.pop(1);
+    Ok(())
 }
 
 // R(A) := (bool)B; if (C) pc++
-pub fn load_bool(i: u32, vm: &mut dyn LuaVM) {
+pub fn load_bool(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
     let (mut a, b, c) = i.abc();
     a += 1;
 
@@ -24,23 +25,26 @@ pub fn load_bool(i: u32, vm: &mut dyn LuaVM) {
     if c != 0 {
         vm.add_pc(1);
     }
+    Ok(())
 }
 
 // R(A) := Kst(Bx)
-pub fn load_k(i: u32, vm: &mut dyn LuaVM) {
+pub fn load_k(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
     let (mut a, bx) = i.a_bx();
     a += 1;
 
     vm.get_const(bx);
     vm.replace(a);
+    Ok(())
 }
 
 // R(A) := Kst(extra arg)
-pub fn load_kx(i: u32, vm: &mut dyn LuaVM) {
+pub fn load_kx(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
     let (mut a, _) = i.a_bx();
     a += 1;
     let ax = vm.fetch().ax();
 
     vm.get_const(ax);
     vm.replace(a);
+    Ok(())
 }
@@ -1,22 +1,23 @@
 use super::instruction::Instruction;
-use crate::api::lua_vm::LuaVM;
+use crate::{api::lua_vm::LuaVM, state::LuaError};
 
 // R(A) := R(B)
-pub fn misc_move(i: u32, vm: &mut dyn LuaVM) {
+pub fn misc_move(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
     let (mut a, mut b, _) = i.abc();
     a += 1;
     b += 1;
 
     vm.copy(b, a);
+    Ok(())
 }
 
 // pc+=sBx; if (A) close all upvalues >= R(A - 1)
-pub fn misc_jump(i: u32, vm: &mut dyn LuaVM) {
+pub fn misc_jump(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
     let (a, sbx) = i.a_sbx();
     vm.add_pc(sbx);
 
     if a != 0 {
         vm.close_upvalues(a);
     }
-
+    Ok(())
 }
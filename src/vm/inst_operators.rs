@@ -1,111 +1,125 @@
 use super::instruction::Instruction;
-use crate::api::{
-    basic::{Arithmetic, Comparison},
-    lua_vm::LuaVM,
+use crate::{
+    api::{
+        basic::{Arithmetic, Comparison},
+        lua_vm::LuaVM,
+    },
+    state::LuaError,
 };
 
 // R(A) := RK(B) op RK(C)
-fn binary_arith(i: u32, vm: &mut dyn LuaVM, op: Arithmetic) {
+fn binary_arith(i: u32, vm: &mut dyn LuaVM, op: Arithmetic) -> Result<(), LuaError> {
     let (mut a, b, c) = i.abc();
     a += 1;
     vm.get_rk(b);
     vm.get_rk(c);
-    vm.arith(op);
+    vm.arith(op)?;
     vm.replace(a);
+    Ok(())
 }
 
 // R(A) := op R(B)
-fn unary_arith(i: u32, vm: &mut dyn LuaVM, op: Arithmetic) {
+fn unary_arith(i: u32, vm: &mut dyn LuaVM, op: Arithmetic) -> Result<(), LuaError> {
     let (mut a, mut b, _) = i.abc();
     a += 1;
     b += 1;
     vm.push_value(b);
-    vm.arith(op);
+    vm.arith(op)?;
     vm.replace(a);
+    Ok(())
+}
+
+// R(A) := the result of the metamethod that yielded
+pub fn finish_arith(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
+    let (mut a, _, _) = i.abc();
+    a += 1;
+    vm.replace(a);
+    Ok(())
 }
 
 // +
-pub fn binary_add(i: u32, vm: &mut dyn LuaVM) {
-    binary_arith(i, vm, Arithmetic::LUA_OPADD);
+pub fn binary_add(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
+    binary_arith(i, vm, Arithmetic::LUA_OPADD)
 }
 
 // -
-pub fn binary_sub(i: u32, vm: &mut dyn LuaVM) {
-    binary_arith(i, vm, Arithmetic::LUA_OPSUB);
+pub fn binary_sub(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
+    binary_arith(i, vm, Arithmetic::LUA_OPSUB)
 }
 
 // *
-pub fn binary_mul(i: u32, vm: &mut dyn LuaVM) {
-    binary_arith(i, vm, Arithmetic::LUA_OPMUL);
+pub fn binary_mul(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
+    binary_arith(i, vm, Arithmetic::LUA_OPMUL)
 }
 
 // %
-pub fn binary_mod(i: u32, vm: &mut dyn LuaVM) {
-    binary_arith(i, vm, Arithmetic::LUA_OPMOD);
+pub fn binary_mod(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
+    binary_arith(i, vm, Arithmetic::LUA_OPMOD)
 }
 
 // ^
-pub fn binary_pow(i: u32, vm: &mut dyn LuaVM) {
-    binary_arith(i, vm, Arithmetic::LUA_OPPOW);
+pub fn binary_pow(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
+    binary_arith(i, vm, Arithmetic::LUA_OPPOW)
 }
 
 // /
-pub fn binary_div(i: u32, vm: &mut dyn LuaVM) {
-    binary_arith(i, vm, Arithmetic::LUA_OPDIV);
+pub fn binary_div(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
+    binary_arith(i, vm, Arithmetic::LUA_OPDIV)
 }
 
 // //
-pub fn binary_idiv(i: u32, vm: &mut dyn LuaVM) {
-    binary_arith(i, vm, Arithmetic::LUA_OPIDIV);
+pub fn binary_idiv(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
+    binary_arith(i, vm, Arithmetic::LUA_OPIDIV)
 }
 
 // &
-pub fn binary_band(i: u32, vm: &mut dyn LuaVM) {
-    binary_arith(i, vm, Arithmetic::LUA_OPBAND);
+pub fn binary_band(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
+    binary_arith(i, vm, Arithmetic::LUA_OPBAND)
 }
 
 // |
-pub fn binary_bor(i: u32, vm: &mut dyn LuaVM) {
-    binary_arith(i, vm, Arithmetic::LUA_OPBOR);
+pub fn binary_bor(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
+    binary_arith(i, vm, Arithmetic::LUA_OPBOR)
 }
 
 // ~
-pub fn binary_bxor(i: u32, vm: &mut dyn LuaVM) {
-    binary_arith(i, vm, Arithmetic::LUA_OPBXOR);
+pub fn binary_bxor(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
+    binary_arith(i, vm, Arithmetic::LUA_OPBXOR)
 }
 
 // <<
-pub fn binary_shl(i: u32, vm: &mut dyn LuaVM) {
-    binary_arith(i, vm, Arithmetic::LUA_OPSHL);
+pub fn binary_shl(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
+    binary_arith(i, vm, Arithmetic::LUA_OPSHL)
 }
 
 // >>
-pub fn binary_shr(i: u32, vm: &mut dyn LuaVM) {
-    binary_arith(i, vm, Arithmetic::LUA_OPSHR);
+pub fn binary_shr(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
+    binary_arith(i, vm, Arithmetic::LUA_OPSHR)
 }
 
 // -
-pub fn unary_unm(i: u32, vm: &mut dyn LuaVM) {
-    unary_arith(i, vm, Arithmetic::LUA_OPUNM);
+pub fn unary_unm(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
+    unary_arith(i, vm, Arithmetic::LUA_OPUNM)
 }
 
 // ~
-pub fn unary_bnot(i: u32, vm: &mut dyn LuaVM) {
-    unary_arith(i, vm, Arithmetic::LUA_OPBNOT);
+pub fn unary_bnot(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
+    unary_arith(i, vm, Arithmetic::LUA_OPBNOT)
 }
 
 // R(A) := length of R(B)
-pub fn len(i: u32, vm: &mut dyn LuaVM) {
+pub fn len(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
     let (mut a, mut b, _) = i.abc();
     a += 1;
     b += 1;
 
-    vm.len(b);
+    vm.len(b)?;
     vm.replace(a);
+    Ok(())
 }
 
 // R(A) := R(B).. ... ..R(C)
-pub fn concat(i: u32, vm: &mut dyn LuaVM) {
+pub fn concat(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
     let (mut a, mut b, mut c) = i.abc();
     a += 1;
     b += 1;
@@ -116,49 +130,75 @@ pub fn concat(i: u32, vm: &mut dyn LuaVM) {
     for i in b..(c + 1) {
         vm.push_value(i);
     }
-    vm.concat(n);
+    vm.concat(n)?;
+    vm.replace(a);
+    Ok(())
+}
+
+// joins the values left after a '__concat' metamethod yielded, its result
+// in place of the last two
+pub fn finish_concat(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
+    let (mut a, _, _) = i.abc();
+    a += 1;
+
+    let n = vm.top() - vm.register_count() as isize;
+    vm.concat(n)?;
     vm.replace(a);
+    Ok(())
 }
 
 // if ((RK(B) op RK(C)) ~= A) then pc++
-fn compare(i: u32, vm: &mut dyn LuaVM, op: Comparison) {
+fn compare(i: u32, vm: &mut dyn LuaVM, op: Comparison) -> Result<(), LuaError> {
     let (a, b, c) = i.abc();
 
     vm.get_rk(b);
     vm.get_rk(c);
-    if vm.compare(-2, -1, op) != (a != 0) {
+    if vm.compare(-2, -1, op)? != (a != 0) {
         vm.add_pc(1);
     }
     vm.pop(2);
+    Ok(())
+}
+
+// compare with the result of the metamethod that yielded above RK(B) and RK(C)
+pub fn finish_compare(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
+    let (a, _, _) = i.abc();
+
+    if vm.to_boolean(-1) != (a != 0) {
+        vm.add_pc(1);
+    }
+    vm.pop(3);
+    Ok(())
 }
 
 // ==
-pub fn eq(i: u32, vm: &mut dyn LuaVM) {
-    compare(i, vm, Comparison::LUA_OPEQ);
+pub fn eq(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
+    compare(i, vm, Comparison::LUA_OPEQ)
 }
 
 // <
-pub fn lt(i: u32, vm: &mut dyn LuaVM) {
-    compare(i, vm, Comparison::LUA_OPLT);
+pub fn lt(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
+    compare(i, vm, Comparison::LUA_OPLT)
 }
 
 // <=
-pub fn le(i: u32, vm: &mut dyn LuaVM) {
-    compare(i, vm, Comparison::LUA_OPLE);
+pub fn le(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
+    compare(i, vm, Comparison::LUA_OPLE)
 }
 
 // R(A) := not R(B)
-pub fn not(i: u32, vm: &mut dyn LuaVM) {
+pub fn not(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
     let (mut a, mut b, _) = i.abc();
     a += 1;
     b += 1;
 
     vm.push_boolean(!vm.to_boolean(b));
     vm.replace(a);
+    Ok(())
 }
 
 // if (R(B) <=> C) then R(A) := R(B) else pc++
-pub fn test_set(i: u32, vm: &mut dyn LuaVM) {
+pub fn test_set(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
     let (mut a, mut b, c) = i.abc();
     a += 1;
     b += 1;
@@ -168,14 +208,16 @@ pub fn test_set(i: u32, vm: &mut dyn LuaVM) {
     } else {
         vm.add_pc(1);
     }
+    Ok(())
 }
 
 // if not (R(A) <=> C) then pc++
-pub fn test(i: u32, vm: &mut dyn LuaVM) {
+pub fn test(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
     let (mut a, _, c) = i.abc();
     a += 1;
 
     if vm.to_boolean(a) != (c != 0) {
         vm.add_pc(1);
     }
+    Ok(())
 }
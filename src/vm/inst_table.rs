@@ -1,11 +1,11 @@
 use super::{fpd::fb2int, instruction::Instruction};
-use crate::api::lua_vm::LuaVM;
+use crate::{api::lua_vm::LuaVM, state::LuaError};
 
 /* number of list items to accumulate before a SETLIST instruction */
 const LFIELDS_PER_FLUSH: isize = 50;
 
 // R(A) := {} (size = B,C)
-pub fn new_table(i: u32, vm: &mut dyn LuaVM) {
+pub fn new_table(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
     let (mut a, b, c) = i.abc();
 
     a += 1;
@@ -13,38 +13,55 @@ pub fn new_table(i: u32, vm: &mut dyn LuaVM) {
     let n_rec = fb2int(c as usize);
     vm.create_table(n_arr, n_rec);
     vm.replace(a);
+    Ok(())
 }
 
 // R(A) := R(B)[RK(C)]
-pub fn table(i: u32, vm: &mut dyn LuaVM) {
+pub fn table(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
     let (mut a, mut b, c) = i.abc();
     a += 1;
     b += 1;
 
     vm.get_rk(c);
-    vm.table(b);
+    vm.table(b)?;
     vm.replace(a);
+    Ok(())
+}
+
+// R(A) := the result of the '__index' metamethod that yielded
+pub fn finish_table(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
+    let (mut a, _, _) = i.abc();
+    a += 1;
+    vm.replace(a);
+    Ok(())
 }
 
 // R(A)[RK(B)] := RK(C)
-pub fn set_table(i: u32, vm: &mut dyn LuaVM) {
+pub fn set_table(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
     let (mut a, b, c) = i.abc();
     a += 1;
 
     vm.get_rk(b);
     vm.get_rk(c);
-    vm.set_table(a);
+    vm.set_table(a)?;
+    Ok(())
+}
+
+// drops the result of the '__newindex' metamethod that yielded
+pub fn finish_set_table(_: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
+    vm.pop(1);
+    Ok(())
 }
 
 // R(A)[(C-1)*FPF+i] := R(A+i), 1 <= i <= B
-pub fn set_list(i: u32, vm: &mut dyn LuaVM) {
+pub fn set_list(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
     let (mut a, mut b, mut c) = i.abc();
     a += 1;
 
     if c > 0 {
         c -= 1;
     } else {
-        c = vm.fetch().ax();
+        c = vm.fetch().ax() - 1;
     }
 
     let b_is_zero = b == 0;
@@ -58,7 +75,7 @@ pub fn set_list(i: u32, vm: &mut dyn LuaVM) {
     for j in 1..(b + 1) {
         idx += 1;
         vm.push_value(a + j);
-        vm.set_i(a, idx);
+        vm.set_i(a, idx)?;
     }
 
     if b_is_zero {
@@ -66,10 +83,11 @@ pub fn set_list(i: u32, vm: &mut dyn LuaVM) {
         for j in (nreg + 1)..(vm.top() + 1) {
             idx += 1;
             vm.push_value(j);
-            vm.set_i(a, idx);
+            vm.set_i(a, idx)?;
         }
 
         // clear stack
         vm.set_top(nreg);
     }
+    Ok(())
 }
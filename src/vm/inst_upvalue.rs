@@ -1,50 +1,58 @@
 use super::instruction::Instruction;
-use crate::api::lua_vm::{lua_upvalue_index, LuaAPI, LuaVM};
+use crate::{
+    api::lua_vm::{lua_upvalue_index, LuaAPI, LuaVM},
+    state::LuaError,
+};
 
 // R(A) := UpValue[B][RK(C)]
-pub fn tab_up(i: u32, vm: &mut dyn LuaVM) {
+pub fn tab_up(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
     let (mut a, _, c) = i.abc();
     a += 1;
 
     vm.push_global_table();
     vm.get_rk(c);
-    vm.table(-2);
+    vm.table(-2)?;
     vm.replace(a);
     vm.pop(1);
+    Ok(())
 }
 
 // R(A) := UpValue[B]
-pub fn get_upval(i: u32, vm: &mut dyn LuaVM) {
+pub fn get_upval(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
     let (mut a, mut b, _) = i.abc();
     a += 1;
     b += 1;
     vm.copy(lua_upvalue_index(b), a);
+    Ok(())
 }
 
 // UpValue[B] := R(A)
-pub fn set_upval(i: u32, vm: &mut dyn LuaVM) {
+pub fn set_upval(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
     let (mut a, mut b, _) = i.abc();
     a += 1;
     b += 1;
     vm.copy(a, lua_upvalue_index(b));
+    Ok(())
 }
 
 // R(A) := UpValue[B][RK(C)]
-pub fn get_tabup(i: u32, vm: &mut dyn LuaVM) {
+pub fn get_tabup(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
     let (mut a, mut b, c) = i.abc();
     a += 1;
     b += 1;
     vm.get_rk(c);
-    vm.table(lua_upvalue_index(b));
+    vm.table(lua_upvalue_index(b))?;
     vm.replace(a);
+    Ok(())
 }
 
 // UpValue[A][RK(B)] := RK(C)
-pub fn set_tabup(i: u32, vm: &mut dyn LuaVM) {
+pub fn set_tabup(i: u32, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
     let (mut a, b, c) = i.abc();
     a += 1;
 
     vm.get_rk(b);
     vm.get_rk(c);
-    vm.set_table(lua_upvalue_index(a));
+    vm.set_table(lua_upvalue_index(a))?;
+    Ok(())
 }
@@ -1,15 +1,17 @@
-use crate::api::lua_vm::LuaVM;
+use crate::{api::lua_vm::LuaVM, state::LuaError};
 
 use super::{
-    inst_call::{call, call_return, closure, tail_call, vararg}, inst_for::{for_loop, for_prep}, inst_load::{load_bool, load_k, load_kx, load_nil}, inst_misc::{misc_jump, misc_move}, inst_operators::{
-        binary_add, binary_band, binary_bor, binary_bxor, binary_div, binary_idiv, binary_mod, binary_mul, binary_pow, binary_shl, binary_shr, binary_sub, concat, eq, le, len, lt, not, test, test_set, unary_bnot, unary_unm
-    }, inst_table::{new_table, set_list, set_table, table}, inst_upvalue::{get_tabup, get_upval, set_tabup, set_upval, tab_up}, opcode::{
-        code_map, OP_ADD, OP_BAND, OP_BNOT, OP_BOR, OP_BXOR, OP_CALL, OP_CLOSURE, OP_CONCAT, OP_DIV, OP_EQ, OP_FORLOOP, OP_FORPREP, OP_GETTABLE, OP_GETTABUP, OP_GETUPVAL, OP_IDIV, OP_JMP, OP_LE, OP_LEN, OP_LOADBOOL, OP_LOADK, OP_LOADKX, OP_LOADNIL, OP_LT, OP_MOD, OP_MOVE, OP_MUL, OP_NEWTABLE, OP_NOT, OP_POW, OP_RETURN, OP_SELF, OP_SETLIST, OP_SETTABLE, OP_SETTABUP, OP_SETUPVAL, OP_SHL, OP_SHR, OP_SUB, OP_TAILCALL, OP_TEST, OP_TESTSET, OP_UNM, OP_VARARG
+    inst_call::{call, call_return, call_self, closure, finish_call, tail_call, vararg}, inst_for::{for_loop, for_prep, tfor_call, tfor_loop}, inst_load::{load_bool, load_k, load_kx, load_nil}, inst_misc::{misc_jump, misc_move}, inst_operators::{
+        binary_add, binary_band, binary_bor, binary_bxor, binary_div, binary_idiv, binary_mod, binary_mul, binary_pow, binary_shl, binary_shr, binary_sub, concat, eq, finish_arith, finish_compare, finish_concat, le, len, lt, not, test, test_set, unary_bnot, unary_unm
+    }, inst_table::{finish_set_table, finish_table, new_table, set_list, set_table, table}, inst_upvalue::{get_tabup, get_upval, set_tabup, set_upval, tab_up}, opcode::{
+        code_map, OP_ADD, OP_BAND, OP_BNOT, OP_BOR, OP_BXOR, OP_CALL, OP_CLOSURE, OP_CONCAT, OP_DIV, OP_EQ, OP_FORLOOP, OP_FORPREP, OP_GETTABLE, OP_GETTABUP, OP_GETUPVAL, OP_IDIV, OP_JMP, OP_LE, OP_LEN, OP_LOADBOOL, OP_LOADK, OP_LOADKX, OP_LOADNIL, OP_LT, OP_MOD, OP_MOVE, OP_MUL, OP_NEWTABLE, OP_NOT, OP_POW, OP_RETURN, OP_SELF, OP_SETLIST, OP_SETTABLE, OP_SETTABUP, OP_SETUPVAL, OP_SHL, OP_SHR, OP_SUB, OP_TAILCALL, OP_TFORCALL, OP_TFORLOOP, OP_TEST, OP_TESTSET, OP_UNM, OP_VARARG
     }
 };
 
-const MAXARG_BX: isize = (1 << 18) - 1; // 2^18 - 1
-const MAXARG_S_BX: isize = MAXARG_BX >> 1; // Floor(2^18 - 1)/2)
+pub const MAXARG_C: isize = (1 << 9) - 1; // 2^9 - 1
+pub const MAXARG_BX: isize = (1 << 18) - 1; // 2^18 - 1
+pub const MAXARG_S_BX: isize = MAXARG_BX >> 1; // Floor(2^18 - 1)/2)
+pub const MAXARG_AX: isize = (1 << 26) - 1; // 2^26 - 1
 
 pub trait Instruction {
     fn opname(self) -> &'static str;
@@ -21,7 +23,8 @@ pub trait Instruction {
     fn a_bx(self) -> (isize, isize);
     fn a_sbx(self) -> (isize, isize);
     fn ax(self) -> isize;
-    fn execute(self, vm: &mut dyn LuaVM);
+    fn execute(self, vm: &mut dyn LuaVM) -> Result<(), LuaError>;
+    fn finish_call(self, vm: &mut dyn LuaVM) -> Result<(), LuaError>;
 }
 
 impl Instruction for u32 {
@@ -67,7 +70,7 @@ impl Instruction for u32 {
         (self >> 6) as isize
     }
 
-    fn execute(self, vm: &mut dyn LuaVM) {
+    fn execute(self, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
         match self.opcode() {
             OP_MOVE => misc_move(self, vm),
             OP_LOADK => load_k(self, vm),
@@ -81,7 +84,7 @@ impl Instruction for u32 {
             OP_SETUPVAL => set_upval(self, vm),
             OP_SETTABLE => set_table(self, vm),
             OP_NEWTABLE => new_table(self, vm),
-            OP_SELF => call_return(self, vm),
+            OP_SELF => call_self(self, vm),
             OP_ADD => binary_add(self, vm),
             OP_SUB => binary_sub(self, vm),
             OP_MUL => binary_mul(self, vm),
@@ -110,6 +113,8 @@ impl Instruction for u32 {
             OP_RETURN => call_return(self, vm),
             OP_FORLOOP => for_loop(self, vm),
             OP_FORPREP => for_prep(self, vm),
+            OP_TFORCALL => tfor_call(self, vm),
+            OP_TFORLOOP => tfor_loop(self, vm),
             // TODO
             OP_SETLIST => set_list(self, vm),
             OP_CLOSURE => closure(self, vm),
@@ -120,4 +125,20 @@ impl Instruction for u32 {
             }
         }
     }
+
+    // completes the instruction once the function it called returned to the
+    // interpreter loop instead of to the instruction's own code: a Lua callee,
+    // or a metamethod that yielded
+    fn finish_call(self, vm: &mut dyn LuaVM) -> Result<(), LuaError> {
+        match self.opcode() {
+            OP_CALL | OP_TAILCALL | OP_TFORCALL => finish_call(self, vm),
+            OP_ADD | OP_SUB | OP_MUL | OP_MOD | OP_POW | OP_DIV | OP_IDIV | OP_BAND | OP_BOR
+            | OP_BXOR | OP_SHL | OP_SHR | OP_UNM | OP_BNOT | OP_LEN => finish_arith(self, vm),
+            OP_CONCAT => finish_concat(self, vm),
+            OP_EQ | OP_LT | OP_LE => finish_compare(self, vm),
+            OP_GETTABUP | OP_GETTABLE | OP_SELF => finish_table(self, vm),
+            OP_SETTABUP | OP_SETTABLE => finish_set_table(self, vm),
+            _ => unreachable!("{} calls no function", self.opname()),
+        }
+    }
 }
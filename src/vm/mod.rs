@@ -1,4 +1,4 @@
-mod fpd;
+pub mod fpd;
 mod inst_call;
 mod inst_for;
 mod inst_load;
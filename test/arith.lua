@@ -0,0 +1,22 @@
+print(pcall(function () return 1 % 0 end)) --> false	test/arith.lua:1: attempt to perform 'n%0'
+print(pcall(function () return 1 // 0 end)) --> false	test/arith.lua:2: attempt to perform 'n//0'
+print(1 // 0.0, -1 // 0.0) --> inf	-inf
+print("10" + 1, "0x10" * 1, "3" // 2, "3.0" + 1, " 7 " % "4", -"2") --> 11	16	1	4.0	3	-2
+print(pcall(function () return "1" % "0" end)) --> false	test/arith.lua:5: attempt to perform 'n%0'
+
+-- floor division and modulo round towards minus infinity
+print(7 // 2, -7 // 2, 7 // -2, -7 // -2) --> 3	-4	-4	3
+print(7 % 3, -7 % 3, 7 % -3, -7 % -3) --> 1	2	-2	-1
+print(7.0 // 2, -7 // 2.0, 7.5 % 2, -7.5 % 2, 5.0 % -3) --> 3.0	-4.0	1.5	0.5	-1.0
+local mininteger = -9223372036854775807 - 1
+print(mininteger // -1, mininteger % -1, 9223372036854775807 + 1 == mininteger) --> -9223372036854775808	0	true
+print(1 / 2, 3 / 1, 2 ^ 2, 10 // 3.0, 1e308 * 10, -1e308 * 10) --> 0.5	3.0	4.0	3.0	inf	-inf
+print(1 == 1.0, 2^53 == 2^53 + 1, 0.1 + 0.2 == 0.3, 2^63, -2^63 == mininteger) --> true	true	false	9.2233720368548e+18	true
+
+-- bitwise operations need integer representations
+print(3 | 5, 3 & 5, 3 ~ 5, ~0, 2.0 | 1, "3" & 1) --> 7	1	6	-1	3	1
+print(1 << 63, 1 << 64, -1 >> 63, 1 >> -1, -1 << -70) --> -9223372036854775808	0	1	2	0
+print(pcall(function () return 1.5 | 1 end)) --> false	test/arith.lua:19: number has no integer representation
+print(pcall(function () return {} + 1 end)) --> false	test/arith.lua:20: attempt to perform arithmetic on a table value
+print(pcall(function () return "a" | 1 end)) --> false	test/arith.lua:21: attempt to perform bitwise operation on a string value
+print(pcall(function () return 2^64 | 0 end)) --> false	test/arith.lua:22: number has no integer representation
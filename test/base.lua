@@ -0,0 +1,36 @@
+print(nil, true, 1, 1.5, 10 / 2, "s") --> nil	true	1	1.5	5.0	s
+print(type(nil), type(2), type({}), type(print)) --> nil	number	table	function
+
+local point = setmetatable({x = 1, y = 2}, {
+  __tostring = function (p) return "(" .. p.x .. ", " .. p.y .. ")" end,
+})
+print(point) --> (1, 2)
+
+print(tonumber("0x10"), tonumber(" 3.5 "), tonumber("z")) --> 16	3.5	nil
+print(tonumber("ff", 16), tonumber("-101", 2), tonumber("8", 8)) --> 255	-5	nil
+
+local s = ""
+for i, v in ipairs({"a", "b", nil, "d"}) do s = s .. i .. v end
+print(s) --> 1a2b
+local n = 0
+for k, v in pairs({1, 2, x = 3}) do n = n + v end
+print(n) --> 6
+local t = {1, 2, 3, x = 1, y = 2}
+for k in pairs(t) do t[k] = nil end
+print(next(t), next({}), next({5})) --> nil	nil	1	5
+print(pcall(next, {a = 1}, "b")) --> false	invalid key to 'next'
+local t, seen = {}, 0
+for i = 1, 100 do t["k" .. i] = i end
+for k in pairs(t) do t[k] = nil seen = seen + 1 end
+for i = 1, 50 do t["n" .. i] = i end
+for k, v in pairs(t) do seen = seen + v end
+print(seen) --> 1375
+
+print(select("#", 1, nil, 3), select(-1, "a", "b")) --> 3	b
+print(assert(1, "unused")) --> 1	unused
+print(pcall(assert, false, "failed")) --> false	failed
+
+local proxy = setmetatable({}, {__index = function () return "default" end})
+print(proxy.key, rawget(proxy, "key"), rawlen({1, 2, 3})) --> default	nil	3
+print(getmetatable(setmetatable({}, {__metatable = "locked"}))) --> locked
+print(_G._G == _G, _VERSION) --> true	Lua 5.3
@@ -0,0 +1,39 @@
+print(load("return 1 + 2")(), load("return ...", "=vararg")(1, 2)) --> 3	1	2
+local parts = {"return ", "40 ", "+ 2"}
+local i = 0
+print(load(function () i = i + 1; return parts[i] end)()) --> 42
+print(pcall(load, function () return {} end)) --> false	reader function must return a string
+print(load("return 1", "=text", "b")) --> nil	attempt to load a text chunk (mode is 'b')
+
+print(load("x = ")) --> nil	[string "x = "]:1: unexpected symbol near <eof>
+print(load("x = = 1")) --> nil	[string "x = = 1"]:1: unexpected symbol near '='
+print(load("x = 1 2")) --> nil	[string "x = 1 2"]:1: unexpected symbol near '2'
+print(load("a, b() = 1")) --> nil	[string "a, b() = 1"]:1: syntax error near '='
+print(load("do x = 1")) --> nil	[string "do x = 1"]:1: 'end' expected near <eof>
+print(load("do\nx = 1")) --> nil	[string "do..."]:2: 'end' expected (to close 'do' at line 1) near <eof>
+print(load("for i = 1 do end", "=for")) --> nil	for:1: ',' expected near 'do'
+print(load("local function end", "=name")) --> nil	name:1: <name> expected near 'end'
+print(load("x = 3x", "=number")) --> nil	number:1: malformed number near '3x'
+print(load("x = 'abc", "=string")) --> nil	string:1: unfinished string near ''abc'
+print(load("x = '\\q'", "=escape")) --> nil	escape:1: invalid escape sequence near ''\q'
+print(load("x = [==[ abc", "=long")) --> nil	long:1: unfinished long string near '<eof>'
+
+print(load("goto l", "=goto")) --> nil	goto:1: no visible label 'l' for <goto> at line 1
+print(load("break", "=break")) --> nil	break:1: <break> at line 1 not inside a loop
+print(load("::a:: ::a::", "=label")) --> nil	label:1: label 'a' already defined on line 1
+print(load("function f() return ... end", "=vararg")) --> nil	vararg:1: cannot use '...' outside a vararg function near '...'
+
+-- nesting is limited instead of overflowing the stack
+local function nested(open, close, n) return string.rep(open, n) .. "1" .. string.rep(close, n) end
+print(load("return " .. nested("(", ")", 150))()) --> 1
+print(load("return " .. nested("(", ")", 100000), "=parens")) --> nil	parens:1: chunk has too many syntax levels
+print(load("x = " .. nested("{", "}", 1000), "=tables")) --> nil	tables:1: chunk has too many syntax levels
+print(load(string.rep("do ", 300) .. string.rep("end ", 300), "=blocks")) --> nil	blocks:1: chunk has too many syntax levels
+print(load("return " .. string.rep("- ", 100000) .. "1", "=unary")) --> nil	unary:1: chunk has too many syntax levels
+print(load("return 2" .. string.rep("^2", 1000), "=pow")) --> nil	pow:1: chunk has too many syntax levels
+
+-- the local limit is reported on the line declaring the local past it
+local decls = {}
+for i = 1, 200 do decls[i] = "local a" .. i end
+print(load(table.concat(decls, "\n") .. "\nlocal x,\n  y = 1\n\n\nprint(x)", "=locals")) --> nil	locals:201: too many local variables (limit is 200) in main function
+print(load("local function f(" .. string.rep("a, ", 199) .. "b, c)\nend", "=params")) --> nil	params:1: too many local variables (limit is 200) in function at line 1
@@ -0,0 +1,113 @@
+local gen = coroutine.wrap(function (a, b)
+  local c = coroutine.yield(a + b)
+  local d, e = coroutine.yield(c * 2)
+  return d + e
+end)
+print(gen(1, 2)) --> 3
+print(gen(10)) --> 20
+print(gen(3, 4)) --> 7
+
+local co = coroutine.create(function (...) return ... end)
+print(coroutine.status(co), coroutine.resume(co, 1, nil, 3)) --> suspended	true	1	nil	3
+print(coroutine.status(co), coroutine.resume(co)) --> dead	false	cannot resume dead coroutine
+print(coroutine.isyieldable(), coroutine.wrap(coroutine.isyieldable)()) --> false	true
+print(pcall(coroutine.yield)) --> false	attempt to yield from outside a coroutine
+print(coroutine.resume(coroutine.create(function () error("oops", 0) end))) --> false	oops
+
+-- yields inside pcall and xpcall, resumed into the protected function
+local co = coroutine.create(function ()
+  local ok, v = pcall(function ()
+    local x = coroutine.yield(1)
+    return x * 2
+  end)
+  coroutine.yield(ok, v)
+  local ok, e = pcall(function ()
+    coroutine.yield(2)
+    error("after resume")
+  end)
+  coroutine.yield(ok, e)
+  coroutine.yield(xpcall(function () coroutine.yield(3) error({}) end, function (m) return type(m) end))
+  coroutine.yield(pcall(pcall, coroutine.yield, 4))
+  return coroutine.isyieldable(), pcall(coroutine.isyieldable)
+end)
+print(coroutine.resume(co)) --> true	1
+print(coroutine.resume(co, 21)) --> true	true	42
+print(coroutine.resume(co)) --> true	2
+print(coroutine.resume(co)) --> true	false	test/coroutine.lua:26: after resume
+print(coroutine.resume(co)) --> true	3
+print(coroutine.resume(co)) --> true	false	table
+print(coroutine.resume(co)) --> true	4
+print(coroutine.resume(co, "a", "b")) --> true	true	true	a	b
+print(coroutine.resume(co)) --> true	true	true	true
+print(coroutine.status(co)) --> dead
+
+-- errors after a resume unwind to the innermost pcall still waiting
+local co = coroutine.create(function ()
+  local ok, e = pcall(function ()
+    local ok, e = pcall(function () coroutine.yield("inner") error("inner") end)
+    coroutine.yield(ok, e)
+    error("outer")
+  end)
+  coroutine.yield(ok, e)
+  error("body")
+end)
+print(coroutine.resume(co)) --> true	inner
+print(coroutine.resume(co)) --> true	false	test/coroutine.lua:47: inner
+print(coroutine.resume(co)) --> true	false	test/coroutine.lua:49: outer
+print(coroutine.resume(co)) --> false	test/coroutine.lua:52: body
+local body = coroutine.wrap(pcall)
+print(body(function (a) return coroutine.yield(a) + 1 end, 1)) --> 1
+print(body(41)) --> true	42
+print(select(2, coroutine.resume(coroutine.create(function () return pcall(coroutine.yield, 1) end)))) --> 1
+
+-- yields inside metamethods, resumed into the interrupted instruction
+local mt = {}
+mt.__index = function (t, k) return coroutine.yield("index " .. k) end
+mt.__newindex = function (t, k, v) rawset(t, k, coroutine.yield("newindex " .. k)) end
+mt.__add = function (a, b) return coroutine.yield("add") end
+mt.__unm = function (a) return coroutine.yield("unm") end
+mt.__len = function (a) return coroutine.yield("len") end
+mt.__concat = function (a, b) return coroutine.yield("concat") end
+mt.__eq = function (a, b) return coroutine.yield("eq") end
+mt.__lt = function (a, b) return coroutine.yield("lt") end
+local co = coroutine.wrap(function ()
+  local t, u = setmetatable({}, mt), setmetatable({}, mt)
+  local x = t.x
+  t.y = 1
+  local sum, neg, len = t + 1, -t, #t
+  local s = "a" .. t .. "b" .. "c"
+  local eq, ne, lt, le = t == u, t ~= u, t < u, t <= u
+  return x, rawget(t, "y"), sum, neg, len, s, eq, ne, lt, le, t < u and "taken" or "not taken"
+end)
+print(co()) --> index x
+print(co("X")) --> newindex y
+print(co(42)) --> add
+print(co(7)) --> unm
+print(co(-1)) --> len
+print(co(5)) --> concat
+print(co("C")) --> eq
+print(co(true)) --> eq
+print(co(false)) --> lt
+print(co(true)) --> lt
+print(co(true)) --> lt
+print(co(false)) --> X	42	7	-1	5	aC	true	true	true	false	not taken
+
+local co = coroutine.wrap(function ()
+  local t = setmetatable({}, {__index = function (t, k)
+    local v = coroutine.yield(k)
+    if v == nil then error("no value for " .. k) end
+    return v
+  end})
+  local sum = select(2, pcall(function () return t.a + t.b end))
+  return sum, pcall(function () return t.c end)
+end)
+print(co()) --> a
+print(co(1)) --> b
+print(co(2)) --> c
+print(co(nil)) --> 3	false	test/coroutine.lua:98: no value for c
+
+-- a Rust function calling back into Lua still cannot be yielded across
+local co = coroutine.wrap(function ()
+  return pcall(table.sort, {3, 2, 1}, function (a, b) coroutine.yield() return a < b end)
+end)
+print(co()) --> false	attempt to yield across a Rust-call boundary
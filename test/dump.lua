@@ -0,0 +1,35 @@
+-- test/luac.out, as written by luac 5.3
+local luac_out = table.concat({
+  "\27LuaS\0\25\147\13\10\26\10\4\8\4\8\8xV\0\0\0\0\0\0\0\0\0\0\0(w@\1",
+  "\10@main.lua\0\0\0\0\0\0\0\0\0\1\2\29\0\0\0,\0\0\0\8\0\0\128\6\0@\0$",
+  "\128\128\0\8\0\128\128\6\128@\0F@@\0d\0\128\0$@\0\0\6\128@\0F@@\0d\0",
+  "\128\0$@\0\0\6\0@\0$\128\128\0\8\0\128\129\6\128@\0F\192@\0d\0\128\0",
+  "$@\0\0\6\128@\0F@@\0d\0\128\0$@\0\0\6\128@\0F\192@\0d\0\128\0$@\0\0&",
+  "\0\128\0\4\0\0\0\4\11newCounter\4\3c1\4\6print\4\3c2\1\0\0\0\1\0\1\0",
+  "\0\0\0\1\0\0\0\7\0\0\0\0\0\2\4\0\0\0\1\0\0\0l\0\0\0f\0\0\1&\0\128\0",
+  "\1\0\0\0\19\0\0\0\0\0\0\0\0\0\0\0\0\1\0\0\0\0\3\0\0\0\6\0\0\0\0\0\2",
+  "\6\0\0\0\5\0\0\0\13\0@\0\9\0\0\0\5\0\0\0&\0\0\1&\0\128\0\1\0\0\0\19",
+  "\1\0\0\0\0\0\0\0\1\0\0\0\1\0\0\0\0\0\6\0\0\0\4\0\0\0\4\0\0\0\4\0\0\0",
+  "\5\0\0\0\5\0\0\0\6\0\0\0\0\0\0\0\1\0\0\0\6count\4\0\0\0\2\0\0\0\6\0",
+  "\0\0\6\0\0\0\7\0\0\0\1\0\0\0\6count\1\0\0\0\4\0\0\0\0\0\0\0\29\0\0\0",
+  "\7\0\0\0\1\0\0\0\9\0\0\0\9\0\0\0\9\0\0\0\10\0\0\0\10\0\0\0\10\0\0\0",
+  "\10\0\0\0\11\0\0\0\11\0\0\0\11\0\0\0\11\0\0\0\13\0\0\0\13\0\0\0\13\0",
+  "\0\0\14\0\0\0\14\0\0\0\14\0\0\0\14\0\0\0\15\0\0\0\15\0\0\0\15\0\0\0",
+  "\15\0\0\0\16\0\0\0\16\0\0\0\16\0\0\0\16\0\0\0\16\0\0\0\0\0\0\0\1\0\0",
+  "\0\5_ENV",})
+print(#luac_out, string.dump(load(luac_out, "=luac.out", "b")) == luac_out) --> 549	true
+
+local function counter(step)
+  local n = 0
+  return function () n = n + step; return n end
+end
+local bin = string.dump(counter)
+print(bin:sub(1, 4) == "\27Lua", string.dump(load(bin, "=bin", "b")) == bin) --> true	true
+print(#string.dump(counter, true) < #bin) --> true
+local c = load(bin, "=bin", "b")(2)
+print(c(), c()) --> 2	4
+
+-- like luac, a stripped chunk keeps no source, so dumping it again strips nothing more
+local stripped = string.dump(counter, true)
+print(string.dump(load(stripped, "=stripped", "b")) == stripped) --> true
+print(#string.dump(load(bin, "=bin", "b"), true) == #stripped) --> true
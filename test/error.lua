@@ -0,0 +1,49 @@
+print(pcall(error, "plain")) --> false	plain
+print(pcall(error)) --> false	nil
+print(pcall(function () error("where") end)) --> false	test/error.lua:3: where
+print(pcall(function () error("no position", 0) end)) --> false	no position
+local function callee() error("blame the caller", 2) end
+print(pcall(function () callee() end)) --> false	test/error.lua:6: blame the caller
+local err = {code = 42}
+local ok, e = pcall(error, err)
+print(ok, e == err, e.code) --> false	true	42
+print(pcall(error, 12)) --> false	12
+
+-- pcall returns every result, and errors unwind nested calls
+print(pcall(function (...) return ... end, 1, nil, 3)) --> true	1	nil	3
+local depth = 0
+local function dive(n) depth = n; if n == 10 then error("bottom") end; dive(n + 1) end
+print(pcall(dive, 1)) --> false	test/error.lua:15: bottom
+print(depth, pcall(pcall, error, "nested")) --> 10	true	false	nested
+print(select("#", pcall(error))) --> 2
+
+-- xpcall passes the error through the message handler
+print(xpcall(function () error("raw") end, function (m) return "handled: " .. m end)) --> false	handled: test/error.lua:21: raw
+print(xpcall(function (a, b) return a + b end, print, 1, 2)) --> true	3
+print(xpcall(error, function (m) return type(m) end, {})) --> false	table
+print(pcall(xpcall)) --> false	bad argument #2 to 'xpcall' (function expected)
+print(xpcall(error, function () error("again") end, "x")) --> false	error in error handling
+print(pcall(assert, false)) --> false	assertion failed!
+print(select(2, pcall(assert, nil, err)) == err) --> true
+
+-- runtime errors carry the position of the failing instruction
+print(pcall(function () local t = nil; return t.x end)) --> false	test/error.lua:30: attempt to index a nil value (local 't')
+print(pcall(function () return undefined_global.x end)) --> false	test/error.lua:31: attempt to index a nil value (global 'undefined_global')
+print(pcall(function () return #5 end)) --> false	test/error.lua:32: attempt to get length of a number value
+print(pcall(function () return {} .. "" end)) --> false	test/error.lua:33: attempt to concatenate a table value
+print(pcall(function () local t = {} t[nil] = 1 end)) --> false	test/error.lua:34: table index is nil
+print(pcall(function () local t = {} t[0/0] = 1 end)) --> false	test/error.lua:35: table index is NaN
+
+-- nesting through Rust calls is bounded instead of overflowing the Rust stack
+local looped = setmetatable({}, {__index = function (t, k) return t[k] end})
+print(pcall(function () return looped.x end)) --> false	test/error.lua:38: C stack overflow
+local function nest () local ok, e = pcall(nest) if not ok then error(e, 0) end end
+print(pcall(nest)) --> false	C stack overflow
+local shown = setmetatable({}, {__tostring = function (s) return tostring(s) end})
+print(pcall(tostring, shown)) --> false	C stack overflow
+local function spawn ()
+  local ok, e = coroutine.resume(coroutine.create(spawn))
+  if not ok then error(e, 0) end
+end
+print(pcall(spawn)) --> false	C stack overflow
+print(pcall(string.rep, "x", 3)) --> true	xxx
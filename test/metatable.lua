@@ -0,0 +1,88 @@
+local t = {}
+t[t] = 1
+rawset(t, print, t)
+print(t[t], rawget(t, t), t[print] == t, rawget(t, print) == t) --> 1	1	true	true
+t[t] = t[t] + 1
+local n = 0
+for k, v in pairs(t) do n = n + 1 end
+print(t[t], n) --> 2	2
+t[t] = nil
+print(t[t], next(t) == print) --> nil	true
+
+-- __index and __newindex chains
+local base = {greet = "hi"}
+local mid = setmetatable({}, {__index = base})
+local obj = setmetatable({}, {__index = mid})
+print(obj.greet, rawget(obj, "greet")) --> hi	nil
+local log = {}
+local proxy = setmetatable({}, {__newindex = function (_, k, v) log[k] = v end})
+proxy.x = 1
+print(rawget(proxy, "x"), log.x) --> nil	1
+local store = {}
+local redirect = setmetatable({}, {__newindex = store})
+redirect.y = 2
+print(rawget(redirect, "y"), store.y) --> nil	2
+local class = {}
+class.__index = class
+function class.name() return "class" end
+local inst = setmetatable({}, class)
+print(inst.name(), inst.missing) --> class	nil
+setmetatable(class, class)
+print(class.name()) --> class
+
+-- __metatable protection
+local locked = setmetatable({}, {__metatable = "locked"})
+print(getmetatable(locked)) --> locked
+print(pcall(setmetatable, locked, {})) --> false	cannot change a protected metatable
+
+-- arithmetic, bitwise, concat and length metamethods
+local V = {}
+V.__index = V
+local function vec(x, y) return setmetatable({x = x, y = y}, V) end
+V.__add = function (a, b) return vec(a.x + b.x, a.y + b.y) end
+V.__mul = function (a, b)
+  if type(a) == "number" then return vec(a * b.x, a * b.y) end
+  return vec(a.x * b, a.y * b)
+end
+V.__unm = function (a) return vec(-a.x, -a.y) end
+V.__len = function (a) return 2 end
+V.__concat = function (a, b)
+  local function s(v) return type(v) == "table" and "(" .. v.x .. "," .. v.y .. ")" or v end
+  return s(a) .. s(b)
+end
+V.__band = function () return "band" end
+V.__shl = function () return "shl" end
+V.__bnot = function () return "bnot" end
+V.__idiv = function () return "idiv" end
+V.__mod = function () return "mod" end
+V.__pow = function () return "pow" end
+local p = vec(1, 2) + vec(3, 4)
+print(p.x, p.y, #p, (2 * p).x, (p * 3).y, (-p).x) --> 4	6	2	8	18	-4
+print("p = " .. p, p .. "!", 1 .. p .. 2) --> p = (4,6)	(4,6)!	1(4,6)2
+print(p & 1, 1 << p, ~p, p // 2, p % 2, 2 ^ p) --> band	shl	bnot	idiv	mod	pow
+print(pcall(function () return p - p end)) --> false	test/metatable.lua:63: attempt to perform arithmetic on a table value
+
+-- comparison metamethods
+local C = {}
+C.__eq = function (a, b) return a.v == b.v end
+C.__lt = function (a, b) return a.v < b.v end
+local function cmp(v) return setmetatable({v = v}, C) end
+local a, b, c = cmp(1), cmp(2), cmp(1)
+print(a == c, a ~= b, a < b, b > a, rawequal(a, c)) --> true	true	true	true	false
+print(a <= c, b <= a, a >= c) --> true	false	true
+C.__le = function () return false end
+print(a <= c, a >= c) --> false	false
+print(pcall(function () return {} < {} end)) --> false	test/metatable.lua:75: attempt to compare two table values
+
+-- __call, including for objects called through other metamethods
+local callable = setmetatable({}, {__call = function (self, x, y) return x + y, self end})
+local sum, self = callable(1, 2)
+print(sum, self == callable) --> 3	true
+local indexer = setmetatable({}, {__index = callable})
+print(pcall(indexer.missing)) --> false	attempt to call a nil value
+print(select(2, pcall(function () local t = {} t() end))) --> test/metatable.lua:83: attempt to call a table value (local 't')
+local count = setmetatable({}, {__call = function (...) return select("#", ...) end})
+local chained = setmetatable({}, {__call = count})
+print(count(1), chained(1), setmetatable({}, {__call = chained})(1)) --> 2	3	4
+local looped = {}
+print(pcall(setmetatable(looped, {__call = looped}))) --> false	'__call' chain too long; possible loop
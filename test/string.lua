@@ -0,0 +1,74 @@
+local s = "hello world"
+print(#s, s:len(), s:upper(), ("ABC"):lower()) --> 11	11	HELLO WORLD	abc
+print(s:sub(1, 5), s:sub(-5), s:sub(7, 100), s:sub(5, 2) == "") --> hello	world	world	true
+print(s:reverse(), ("ab"):rep(3, "-"), ("x"):rep(0) == "") --> dlrow olleh	ab-ab-ab	true
+print(s:byte(), s:byte(-1), s:byte(1, 3)) --> 104	100	104	101	108
+print(string.char(72, 105), string.len(123)) --> Hi	3
+
+print(s:find("o"), s:find("o", 6), s:find("l+")) --> 5	8	3	4
+print(s:find(".", 1, true), s:find("xyz"), s:find("", 20)) --> nil	nil	nil
+print(s:find("(o)(r)")) --> 8	9	o	r
+print(s:match("^(%w+)"), s:match("(%w+)$"), s:match("^world")) --> hello	world	nil
+print(("key = value"):match("(%w+)%s*=%s*(%w+)")) --> key	value
+print(("  trim  "):match("^%s*(.-)%s*$") .. "|") --> trim|
+print(("[[x]]"):match("%[(%b[])%]"), ("f(a(b)c)"):match("%b()")) --> [x]	(a(b)c)
+print(("THE (quick) fox"):find("%f[%a]%a+%f[%A]", 5)) --> 6	10
+print(("hello"):match("()ll()")) --> 3	5
+print(("abcabc"):match("(a)(b)c%1%2")) --> a	b
+print(("x = 1.5e3"):match("[%d%.eE]+"), ("a-b"):match("[a%-]+")) --> 1.5e3	a-
+print(("[]"):match("[]]"), ("^x"):match("[%^x]+")) --> ]	^x
+
+local words = {}
+for w in ("one two  three"):gmatch("%a+") do words[#words + 1] = w end
+print(#words, words[1], words[3]) --> 3	one	three
+for k, v in ("a=1, b=2"):gmatch("(%w+)=(%w+)") do print(k, v) end
+--> a	1
+--> b	2
+
+print(("hello world"):gsub("o", "0")) --> hell0 w0rld	2
+print(("hello world"):gsub("(%w+)", "<%1>")) --> <hello> <world>	2
+print(("hello world"):gsub("%w+", "%0 %0", 1)) --> hello hello world	1
+print(("abc"):gsub("", "-")) --> -a-b-c-	4
+print(("$name is $age"):gsub("%$(%w+)", {name = "bob", age = 42})) --> bob is 42	2
+print(("1 2 3"):gsub("%d", function (d) return tonumber(d) * 2 end)) --> 2 4 6	3
+print(("keep this"):gsub("%w+", function () end)) --> keep this	2
+print(("abc"):gsub("^.", "X")) --> Xbc	1
+print(("100%"):gsub("%%", "%%%%")) --> 100%%	1
+
+print(pcall(string.rep)) --> false	bad argument #1 to 'rep' (string expected, got no value)
+print(pcall(string.find, "a", "(")) --> false	unfinished capture
+print(pcall(string.match, "a", "%")) --> false	malformed pattern (ends with '%')
+print(pcall(string.gsub, "a", "(a)", "%2")) --> false	invalid capture index %2
+print(pcall(string.gsub, "a", "a", true)) --> false	bad argument #3 to 'gsub' (string/function/table expected)
+print(pcall(string.char, 256)) --> false	bad argument #1 to 'char' (value out of range)
+
+print(string.format("%5d|%-5d|%05.1f|%x|%X|%o|%+i", 42, 42, 3.14159, 255, 255, 8, 7)) -->    42|42   |003.1|ff|FF|10|+7
+print(string.format("%c%c%c|%3c|%e|%g", 76, 117, 97, 65, 12345.678, 1e20)) --> Lua|  A|1.234568e+04|1e+20
+print(string.format("%a|%s|%10s|%-4s|%.2s|", 1.0, nil, "abc", "ab", "abc")) --> 0x1p+0|nil|       abc|ab  |ab|
+print(string.format("%d%%", 3.0)) --> 3%
+print(string.format("%q", 'a "quoted"\n\\ string\0' .. "1")) --> "a \"quoted\"\
+--> \\ string\0001"
+print(string.format("%q|%q|%q", 10, -9223372036854775807 - 1, 0.1)) --> 10|0x8000000000000000|0x1.999999999999ap-4
+print(0x8000000000000000, 0x1.999999999999ap-4) --> -9223372036854775808	0.1
+print(pcall(string.format, "%d", 3.5)) --> false	bad argument #2 to 'format' (number has no integer representation)
+print(pcall(string.format, "%y", 1)) --> false	invalid option '%y' to 'format'
+print(pcall(string.format, "%q", {})) --> false	bad argument #2 to 'format' (value has no literal form)
+
+local bytes = "\xff\0\x80"
+print(#bytes, bytes:byte(1, -1)) --> 3	255	0	128
+print(string.char(255, 200) == "\255\200", ("\xe4\xbd\xa0"):len()) --> true	3
+print(string.pack("<i4", 1):byte(1, -1)) --> 1	0	0	0
+print(string.pack(">i4", -2):byte(1, -1)) --> 255	255	255	254
+print(string.unpack("<i4", string.pack("<i4", -123456))) --> -123456	5
+print(string.unpack("b", "\255"), string.unpack("B", "\255")) --> -1	255	2
+print(string.unpack("<d >f", string.pack("<d >f", 3.5, 0.25))) --> 3.5	0.25	13
+print(string.unpack("z s1", string.pack("z s1", "hello", "abc"))) --> hello	abc	11
+print(#string.pack("!4 b i4", 1, 2), #string.pack("b Xi4 b", 1, 2)) --> 8	2
+print(string.packsize("i4 i8"), string.packsize("!8 b d"), string.packsize("c10 b")) --> 12	16	11
+print(string.unpack("<i16", string.pack("<i16", -3))) --> -3	17
+print(pcall(string.pack, "i17", 1)) --> false	integral size (17) out of limits [1,16]
+print(pcall(string.pack, "b", 200)) --> false	bad argument #2 to 'pack' (integer overflow)
+print(pcall(string.pack, "B", -1)) --> false	bad argument #2 to 'pack' (unsigned overflow)
+print(pcall(string.packsize, "s")) --> false	bad argument #1 to 'packsize' (variable-length format)
+print(pcall(string.unpack, "i4", "abc")) --> false	bad argument #2 to 'unpack' (data string too short)
+print(pcall(string.unpack, "<i9", "\0\0\0\0\0\0\0\0\1")) --> false	9-byte integer does not fit into Lua Integer
@@ -0,0 +1,62 @@
+local t = {1, 2, 3}
+table.insert(t, 4)
+table.insert(t, 1, 0)
+print(#t, table.concat(t, ",")) --> 5	0,1,2,3,4
+print(table.remove(t), table.remove(t, 1), #t, table.concat(t, ",")) --> 4	0	3	1,2,3
+print(table.remove({}), #t, table.remove(t, #t + 1)) --> nil	3	nil
+print(table.concat({}), table.concat({1, "a", 2.5}, ", ", 2), table.concat(t, "", 2, 3)) --> 	a, 2.5	23
+
+local p = table.pack("a", nil, "c")
+print(p.n, p[1], p[2], p[3]) --> 3	a	nil	c
+print(table.unpack({1, 2, 3})) --> 1	2	3
+print(table.unpack({1, 2, 3}, 2), table.unpack({1, 2, 3}, 2, 4)) --> 2	2	3	nil
+print(select("#", table.unpack({}, 1, 0))) --> 0
+
+local m = table.move({1, 2, 3}, 1, 3, 2)
+print(table.concat(m, ",")) --> 1,1,2,3
+print(table.concat(table.move({1, 2, 3, 4}, 2, 4, 1), ",")) --> 2,3,4,4
+print(table.concat(table.move({1, 2}, 1, 2, 3, {"a", "b"}), ",")) --> a,b,1,2
+
+-- proxies go through __index, __newindex and __len
+local store = {10, 20, 30}
+local log = {}
+local proxy = setmetatable({}, {
+  __index = function (_, k) return store[k] end,
+  __newindex = function (_, k, v) log[#log + 1] = k; store[k] = v end,
+  __len = function () return #store end,
+})
+table.insert(proxy, 40)
+table.insert(proxy, 1, 5)
+print(#store, table.concat(store, ","), table.concat(log, ",")) --> 5	5,10,20,30,40	4,5,4,3,2,1
+print(table.remove(proxy, 2), table.concat(store, ",")) --> 10	5,20,30,40
+print(table.concat(proxy, "-"), table.unpack(proxy, 2, 3)) --> 5-20-30-40	20	30
+table.sort(proxy, function (a, b) return a > b end)
+print(table.concat(store, ",")) --> 40,30,20,5
+
+local s = {5, 2, 8, 1, 9, 3}
+table.sort(s)
+print(table.concat(s, " ")) --> 1 2 3 5 8 9
+table.sort(s, function (a, b) return a > b end)
+print(table.concat(s, " ")) --> 9 8 5 3 2 1
+local names = {"bob", "alice", "Carol", "dave"}
+table.sort(names, function (a, b) return a:lower() < b:lower() end)
+print(table.concat(names, " ")) --> alice bob Carol dave
+local big = {}
+for i = 1, 500 do big[i] = (i * 7919) % 500 end
+table.sort(big)
+local ok = true
+for i = 2, #big do ok = ok and big[i - 1] <= big[i] end
+print(ok, big[1], big[500]) --> true	0	499
+
+print(pcall(table.sort, {3, 1, 2, 5, 4}, function () return true end)) --> false	invalid order function for sorting
+print(pcall(table.sort, {1, "x"})) --> false	attempt to compare string with number
+print(pcall(table.sort, {1, 2}, 3)) --> false	bad argument #2 to 'sort' (function expected, got number)
+print(pcall(table.insert, {}, 1, 2, 3)) --> false	wrong number of arguments to 'insert'
+print(pcall(table.insert, {}, 5, 1)) --> false	bad argument #2 to 'insert' (position out of bounds)
+print(pcall(table.remove, {}, 3)) --> false	bad argument #2 to 'remove' (position out of bounds)
+print(pcall(table.concat, {1, {}, 3})) --> false	invalid value (at index 2) in table for 'concat'
+print(pcall(table.insert, 1, 2)) --> false	bad argument #1 to 'insert' (table expected, got number)
+print(pcall(table.move, {}, 1, 9223372036854775807, 2)) --> false	bad argument #4 to 'move' (destination wrap around)
+print(pcall(table.unpack, {}, 1, 1e8)) --> false	too many results to unpack
+print(pcall(table.unpack, {}, 1, 2147483000)) --> false	too many results to unpack
+print(select("#", table.unpack({}, 1, 10000))) --> 10000
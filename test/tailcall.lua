@@ -0,0 +1,27 @@
+function count (n) -- deeper than the frame limit, runs in constant space
+  if n == 0 then return "done" end
+  return count(n - 1)
+end
+print(count(250000)) --> done
+
+function even (n) if n == 0 then return "even" end return odd(n - 1) end
+function odd (n) if n == 0 then return "odd" end return even(n - 1) end
+print(even(100001)) --> odd
+
+function pass (n, ...)
+  if n == 0 then return ... end
+  return pass(n - 1, ...)
+end
+print(pass(100000, 1, 2, 3)) --> 1	2	3
+
+co = coroutine.wrap(function (a) return coroutine.yield(a) end) -- into a Rust function
+print(co(1)) --> 1
+print(co(2)) --> 2
+
+-- a Rust function in tail position runs within the caller's frame
+local function boom () return error("boom") end
+print(pcall(boom)) --> false	test/tailcall.lua:22: boom
+print(pcall(function () return string.rep("x", {}) end)) --> false	test/tailcall.lua:24: bad argument #2 to 'rep' (number expected, got table)
+print(pcall(function () return select(-5, 1) end)) --> false	test/tailcall.lua:25: bad argument #1 to 'select' (index out of range)
+local function tostr (...) return tostring(...) end
+print(tostr(12), select("#", tostr(nil))) --> 12	1
@@ -0,0 +1,31 @@
+-- binary chunks are checked while loading, so corrupt ones fail in load
+local header = string.dump(function () end):sub(1, 34)
+local RETURN = 0x00800026 -- RETURN 0 1
+
+-- a function with 2 registers, the given code and no constants, nesting f
+local function proto(code, f)
+  local s = "\0" .. string.pack("<i4i4BBB", 0, 0, 0, 0, 2) .. string.pack("<i4", #code)
+  for _, i in ipairs(code) do s = s .. string.pack("<I4", i) end
+  s = s .. string.pack("<i4i4i4", 0, 0, f and 1 or 0) .. (f or "")
+  return s .. string.pack("<i4i4i4", 0, 0, 0)
+end
+print(type(load(header .. proto({RETURN}, proto({RETURN})), "=ok", "b"))) --> function
+
+local deep = proto({RETURN})
+for _ = 1, 300 do deep = proto({RETURN}, deep) end
+print(load(header .. deep, "=deep", "b")) --> nil	deep: too many nested functions in precompiled chunk
+
+print(load(header .. proto({0x0B | 5 << 6, RETURN}), "=newtable", "b")) --> nil	newtable: register out of range in instruction 1 of precompiled chunk
+print(load(header .. proto({0x01 | 3 << 14, RETURN}), "=loadk", "b")) --> nil	loadk: constant out of range in instruction 1 of precompiled chunk
+print(load(header .. proto({0x0D | 0x1FF << 23, RETURN}), "=add", "b")) --> nil	add: operand out of range in instruction 1 of precompiled chunk
+print(load(header .. proto({0x1E | (0x1FFFF + 5) << 14, RETURN}), "=jmp", "b")) --> nil	jmp: jump out of range in instruction 1 of precompiled chunk
+print(load(header .. proto({0x2C, RETURN}), "=closure", "b")) --> nil	closure: function out of range in instruction 1 of precompiled chunk
+print(load(header .. proto({0x3F, RETURN}), "=opcode", "b")) --> nil	opcode: bad opcode in instruction 1 of precompiled chunk
+print(load(header .. proto({0x00}), "=return", "b")) --> nil	return: missing final RETURN in instruction 1 of precompiled chunk
+
+-- large chunks load in linear time, including constants past LOADK's range
+local items = {}
+for i = 1, 262200 do items[i] = i end
+local big = string.dump(load("return {" .. table.concat(items, ",") .. "}"))
+local t = load(big, "=big", "b")()
+print(#big > 2000000, #t, t[262143], t[262200]) --> true	262200	262143	262200
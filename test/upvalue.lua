@@ -0,0 +1,89 @@
+function newAccount (balance, rate)
+  local history = 0
+  return function (amount) -- three upvalues
+    history = history + 1
+    balance = balance + amount * rate
+    return balance, history
+  end
+end
+
+a1 = newAccount(100, 1)
+print(a1(10)) --> 110	1
+print(a1(5)) --> 115	2
+
+a2 = newAccount(0, 2)
+print(a2(10)) --> 20	1
+print(a1(1)) --> 116	3
+
+local function fib (n) -- recursive local function, fib is its own upvalue
+  if n < 2 then return n end
+  return fib(n - 1) + fib(n - 2)
+end
+print(fib(10)) --> 55
+
+function nested ()
+  local a, b = 1, 2
+  return function ()
+    local c = 3
+    return function () -- a and b reach through the middle closure
+      a, b, c = b, c, a
+      return a, b, c
+    end
+  end
+end
+
+f = nested()()
+print(f()) --> 2	3	1
+print(f()) --> 3	1	2
+
+-- each wrapped coroutine is a Rust closure holding its thread as upvalue 1
+g1 = coroutine.wrap(function () coroutine.yield(1) coroutine.yield(2) end)
+g2 = coroutine.wrap(function () coroutine.yield("a") coroutine.yield("b") end)
+print(g1(), g2(), g1(), g2()) --> 1	a	2	b
+
+local t = {x = 1}
+local y = 2
+local function sum () -- two closures capture different locals of one frame
+  return t.x + y
+end
+print(sum()) --> 3
+t.x, y = 10, 5
+print(sum()) --> 15
+
+function pair ()
+  local n = 0
+  local function get () return n end
+  local function inc () n = n + 1 end -- shares n with get
+  return get, inc
+end
+get, inc = pair()
+inc() inc()
+print(get()) --> 2
+
+fs = {}
+for i = 1, 3 do
+  local j = i * 10 -- a fresh j for every iteration
+  fs[i] = function () j = j + 1 return i, j end
+end
+print(fs[1]()) --> 1	11
+print(fs[3]()) --> 3	31
+print(fs[1]()) --> 1	12
+
+function failing ()
+  local z = 1
+  h = function () return z end
+  z = 7
+  error("unwound")
+end
+pcall(failing)
+print(h()) --> 7
+
+-- Rust closures carry their own upvalues: each gmatch iterator keeps its subject,
+-- pattern and position in four
+local words, nums = ("one two three"):gmatch("%a+"), ("1,22,333"):gmatch("%d+")
+print(words(), nums(), words(), nums(), nums(), words(), nums(), words()) --> one	1	two	22	333	three	nil
+local out = {}
+for w in ("ab  cd"):gmatch("%a*") do out[#out + 1] = "[" .. w .. "]" end
+print(table.concat(out)) --> [ab][][cd]
+local gen1, gen2 = coroutine.wrap(function () coroutine.yield(1) end), coroutine.wrap(function () coroutine.yield(2) end)
+print(gen2(), gen1()) --> 2	1
@@ -0,0 +1,41 @@
+local function count(...) return select("#", ...) end
+print(count(), count(nil), count(1, nil), count(nil, nil, nil)) --> 0	1	2	3
+local function pass(...) return ... end
+print(pass(1, 2, 3)) --> 1	2	3
+print(pass(1, 2, 3), "last") --> 1	last
+print((pass(1, 2, 3))) --> 1
+print(({pass(1, 2, 3)})[3], #{pass(1, 2, 3), 4}) --> 3	2
+print(count(pass(nil, nil)), count(pass())) --> 2	0
+
+local function fixed(a, b, ...)
+  local x, y = ...
+  return a, b, x, y, select("#", ...)
+end
+print(fixed(1)) --> 1	nil	nil	nil	0
+print(fixed(1, 2, 3, 4, 5)) --> 1	2	3	4	3
+
+local function packed(...)
+  local t = {...}
+  local n = select("#", ...)
+  return n, #t, t[1], t[n]
+end
+print(packed("a", "b", "c")) --> 3	3	a	c
+print(select(2, "a", "b", "c")) --> b	c
+print(select(-2, "a", "b", "c")) --> b	c
+print(pcall(select, 0)) --> false	bad argument #1 to 'select' (index out of range)
+
+-- varargs in the main chunk are the script's arguments
+print(select("#", ...)) --> 0
+
+-- varargs survive nested calls and multiple assignment
+local function outer(...)
+  local function inner(...) return select("#", ...), ... end
+  return inner(...)
+end
+print(outer(nil, 2, nil)) --> 3	nil	2	nil
+local a, b, c, d = pass(1, 2)
+print(a, b, c, d) --> 1	2	nil	nil
+local t = {n = 0}
+for i, v in ipairs({pass(10, 20, 30)}) do t.n = t.n + i * v end
+print(t.n) --> 140
+print(string.format("%d-%d", pass(7, 8, 9))) --> 7-8